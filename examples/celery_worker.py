@@ -0,0 +1,35 @@
+"""Celery worker deduplicating concurrent task work per worker process.
+
+Run with:
+    celery -A celery_worker worker --pool=threads --concurrency=16 --workdir examples
+
+With the prefork pool every child process needs its own cache, so it is
+created in ``worker_process_init`` instead of at import time.
+"""
+import os
+import time
+
+from celery import Celery
+from celery.signals import worker_process_init
+
+from rustflight import PyCache
+
+app = Celery("celery_worker", broker=os.environ.get("BROKER_URL", "memory://"))
+cache = PyCache(wait_timeout=10.0, ttl=60.0)
+
+
+@worker_process_init.connect
+def init_cache(**kwargs):
+    global cache
+    cache = PyCache(wait_timeout=10.0, ttl=60.0)
+
+
+def fetch_exchange_rate(currency):
+    time.sleep(1)  # -> Stand-in for a rate-limited upstream API
+    return {"currency": currency, "rate": 1.0, "pid": os.getpid()}
+
+
+@app.task
+def convert(amount, currency):
+    rate = cache.py_call(fetch_exchange_rate, (currency,), {}, "rate:%s" % currency)
+    return amount * rate["rate"]
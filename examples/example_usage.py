@@ -6,7 +6,7 @@ from typing import Callable
 
 from rustflight import PyCache
 
-cache = PyCache(timeout=5000)  # Wait 5 seconds
+cache = PyCache(timeout=5.0)  # Wait 5 seconds
 
 
 def call_with_cache(func: Callable):
@@ -0,0 +1,46 @@
+"""WSGI app sharing one rustflight cache between the threads of a worker.
+
+Run with:
+    gunicorn -c examples/gunicorn_conf.py gunicorn_app:app --chdir examples
+
+gunicorn forks its workers from the master, so every worker owns a separate
+cache. Create it after the fork (see ``post_fork`` in gunicorn_conf.py)
+rather than at import time in the master.
+"""
+import json
+import os
+import time
+
+from rustflight import PyCache
+
+cache = None
+
+
+def init_cache():
+    global cache
+    cache = PyCache(wait_timeout=5.0, ttl=30.0, max_size=10_000)
+
+
+def load_report(report_id):
+    time.sleep(0.5)  # -> Stand-in for a slow database query
+    return {"id": report_id, "pid": os.getpid(), "loaded_at": time.time()}
+
+
+def app(environ, start_response):
+    if cache is None:  # -> Also works without the post_fork hook
+        init_cache()
+    report_id = environ.get("PATH_INFO", "/").strip("/") or "default"
+    report = cache.py_call(load_report, (report_id,), {}, "report:%s" % report_id)
+    stats = cache.stats()
+    body = json.dumps(
+        {
+            "report": report,
+            "worker": {
+                "pid": os.getpid(),
+                "entries": stats["entries"],
+                "forked_flights_dropped": stats["forked_flights_dropped"],
+            },
+        }
+    ).encode()
+    start_response("200 OK", [("Content-Type", "application/json")])
+    return [body]
@@ -0,0 +1,14 @@
+"""gunicorn settings for gunicorn_app.py.
+
+Threaded workers let concurrent requests for the same report share one
+computation; each process still computes it once for itself.
+"""
+workers = 4
+threads = 16
+worker_class = "gthread"
+
+
+def post_fork(server, worker):
+    import gunicorn_app
+
+    gunicorn_app.init_cache()
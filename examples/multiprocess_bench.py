@@ -0,0 +1,69 @@
+"""End-to-end check and benchmark of the recommended multi-process setup.
+
+Forks N workers that each run T threads against their own cache, then checks
+that every process computed each key exactly once and reports throughput.
+
+    python examples/multiprocess_bench.py --processes 4 --threads 16
+"""
+import argparse
+import multiprocessing as mp
+import time
+from concurrent.futures import ThreadPoolExecutor
+
+from rustflight import PyCache
+
+# Created in the parent on purpose: the children inherit it through fork
+cache = PyCache(wait_timeout=10.0)
+
+
+def compute(key, calls):
+    calls.append(key)
+    time.sleep(0.05)
+    return key * 2
+
+
+def worker(keys, threads, requests, results):
+    calls = []
+    started = time.perf_counter()
+    with ThreadPoolExecutor(threads) as pool:
+        values = list(
+            pool.map(
+                lambda i: cache.py_call(compute, (keys[i % len(keys)], calls), {}, str(keys[i % len(keys)])),
+                range(requests),
+            )
+        )
+    elapsed = time.perf_counter() - started
+    assert values == [keys[i % len(keys)] * 2 for i in range(requests)]
+    assert sorted(calls) == sorted(keys), "Each key must be computed once per process"
+    results.put((requests / elapsed, cache.stats()["forked_flights_dropped"]))
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--processes", type=int, default=4)
+    parser.add_argument("--threads", type=int, default=16)
+    parser.add_argument("--keys", type=int, default=100)
+    parser.add_argument("--requests", type=int, default=10_000)
+    args = parser.parse_args()
+
+    context = mp.get_context("fork")
+    results = context.Queue()
+    keys = list(range(args.keys))
+    processes = [
+        context.Process(target=worker, args=(keys, args.threads, args.requests, results))
+        for _ in range(args.processes)
+    ]
+    for process in processes:
+        process.start()
+    for process in processes:
+        process.join()
+        assert process.exitcode == 0, "Worker failed"
+
+    throughput = [results.get()[0] for _ in processes]
+    print("Processes: %s, threads each: %s" % (args.processes, args.threads))
+    print("Calls/s per process: %s" % ", ".join("%.0f" % value for value in throughput))
+    print("Total calls/s: %.0f" % sum(throughput))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,19 @@
+"""Single-flight caching for Python, backed by a native extension.
+
+When the extension cannot be loaded (e.g. on an unsupported platform) the
+core API is served by a pure Python implementation instead. ``NATIVE`` tells
+which one is in use.
+"""
+
+try:
+    from .rustflight import *  # noqa: F401,F403
+    from .rustflight import __all__ as _native_all
+
+    NATIVE = True
+    __all__ = [*_native_all, "NATIVE"]
+except ImportError:
+    from ._fallback import *  # noqa: F401,F403
+    from ._fallback import __all__ as _fallback_all
+
+    NATIVE = False
+    __all__ = [*_fallback_all, "NATIVE"]
@@ -0,0 +1,345 @@
+"""Pure Python stand-in for the native extension.
+
+Covers the core caching API with plain threading primitives so code written
+against rustflight keeps working where the extension is unavailable.
+Options only the native cache understands are accepted and ignored.
+"""
+
+import fnmatch
+import math
+import threading
+import time
+from collections import OrderedDict
+
+__all__ = [
+    "PyCache",
+    "QuotaExceeded",
+    "ComputeTimeout",
+    "CacheDegraded",
+    "LeaseHeld",
+    "CacheClosed",
+    "FunctionMismatch",
+    "default_cache",
+    "call",
+    "get",
+    "set",
+    "invalidate",
+    "stats",
+    "reset_stats",
+]
+
+
+class QuotaExceeded(Exception):
+    pass
+
+
+class ComputeTimeout(Exception):
+    pass
+
+
+class CacheDegraded(Exception):
+    pass
+
+
+class LeaseHeld(Exception):
+    pass
+
+
+class CacheClosed(Exception):
+    pass
+
+
+class FunctionMismatch(Exception):
+    pass
+
+
+class _Flight:
+    def __init__(self):
+        self.done = threading.Event()
+        self.value = None
+        self.error = None
+        self.expires_at = None
+        self.waiters = 0
+
+
+def _seconds(timeout):
+    if timeout is None or math.isinf(timeout):
+        return None
+    if math.isnan(timeout) or timeout < 0:
+        raise ValueError(
+            "Timeout must be a non-negative number of seconds, got {}".format(timeout)
+        )
+    return timeout
+
+
+class PyCache:
+    def __init__(
+        self,
+        timeout=None,
+        *,
+        wait_timeout=None,
+        ttl=None,
+        max_size=None,
+        store_results=True,
+        timeout_policy="lead",
+        timeout_fallback=None,
+        **_native_only,
+    ):
+        if timeout_policy not in ("raise", "lead", "fallback"):
+            raise ValueError(
+                "Unknown timeout policy '{}', expected 'raise', 'lead' or "
+                "'fallback'".format(timeout_policy)
+            )
+        if wait_timeout is None:
+            wait_timeout = timeout
+        self._wait_timeout = _seconds(wait_timeout)
+        self._ttl = _seconds(ttl)
+        self._max_size = max(max_size, 1) if max_size is not None else None
+        self._store_results = store_results
+        self._timeout_policy = timeout_policy
+        self._timeout_fallback = timeout_fallback
+        self._lock = threading.Lock()
+        self._entries = OrderedDict()
+        self._closed = False
+        self.reset_stats()
+
+    def py_call(self, py_func, args, kwargs, key, *, timeout=None, **_options):
+        return self._call(py_func, args, kwargs, key, timeout)[0]
+
+    def py_call_with_info(self, py_func, args, kwargs, key, **_options):
+        return self._call(py_func, args, kwargs, key, None)
+
+    def _call(self, py_func, args, kwargs, key, timeout):
+        if self._closed:
+            raise CacheClosed("The cache is closed")
+        wait_timeout = self._wait_timeout if timeout is None else _seconds(timeout)
+        while True:
+            with self._lock:
+                flight = self._entries.get(key)
+                if flight is not None and self._expired(flight):
+                    del self._entries[key]
+                    flight = None
+                if flight is None:
+                    flight = _Flight()
+                    self._entries[key] = flight
+                    self._misses += 1
+                    break
+                self._entries.move_to_end(key)
+                if flight.done.is_set():
+                    self._hits += 1
+                    return flight.value, False, 0
+                flight.waiters += 1
+                self._coalesced_waits += 1
+            if flight.done.wait(wait_timeout):
+                if flight.error is not None:
+                    raise flight.error
+                return flight.value, False, flight.waiters
+            with self._lock:
+                self._timeouts += 1
+            if self._timeout_policy == "raise":
+                raise TimeoutError(
+                    "Timed out after {}s waiting for cache entry '{}'".format(
+                        wait_timeout, key
+                    )
+                )
+            if self._timeout_policy == "fallback":
+                return self._timeout_fallback, False, 0
+            with self._lock:
+                if self._entries.get(key) is flight:
+                    del self._entries[key]
+
+        try:
+            value = py_func(*args, **kwargs)
+        except BaseException as err:
+            flight.error = err
+            self._remove(key, flight)
+            flight.done.set()
+            raise
+        if not self._store_results:
+            self._remove(key, flight)
+        flight.value = value
+        if self._ttl is not None:
+            flight.expires_at = time.monotonic() + self._ttl
+        flight.done.set()
+        self._enforce_size_limit()
+        return value, True, flight.waiters
+
+    def _expired(self, flight):
+        return flight.expires_at is not None and time.monotonic() >= flight.expires_at
+
+    def _remove(self, key, flight):
+        with self._lock:
+            if self._entries.get(key) is flight:
+                del self._entries[key]
+
+    def _enforce_size_limit(self):
+        if self._max_size is None:
+            return
+        with self._lock:
+            ready = [key for key, flight in self._entries.items() if flight.done.is_set()]
+            for key in ready[: max(len(self._entries) - self._max_size, 0)]:
+                del self._entries[key]
+                self._evictions += 1
+
+    def _lookup(self, key):
+        with self._lock:
+            flight = self._entries.get(key)
+            if flight is None or not flight.done.is_set() or self._expired(flight):
+                return None
+            return flight.value
+
+    def _fresh(self, flight):
+        return flight.done.is_set() and flight.error is None and not self._expired(flight)
+
+    def __contains__(self, key):
+        with self._lock:
+            flight = self._entries.get(key)
+            return flight is not None and self._fresh(flight)
+
+    def __len__(self):
+        return len(self.keys())
+
+    def __bool__(self):
+        return True
+
+    def __getitem__(self, key):
+        with self._lock:
+            flight = self._entries.get(key)
+            if flight is None or not self._fresh(flight):
+                raise KeyError(key)
+            return flight.value
+
+    def keys(self):
+        with self._lock:
+            return [key for key, flight in self._entries.items() if self._fresh(flight)]
+
+    def get(self, key, default=None):
+        value = self._lookup(key)
+        return default if value is None else value
+
+    def set(self, key, value, *, ttl=None, refresher=None):
+        flight = _Flight()
+        flight.value = value
+        ttl = self._ttl if ttl is None else _seconds(ttl)
+        if ttl is not None:
+            flight.expires_at = time.monotonic() + ttl
+        flight.done.set()
+        with self._lock:
+            current = self._entries.get(key)
+            if current is not None and not current.done.is_set():
+                return False
+            self._entries[key] = flight
+        self._enforce_size_limit()
+        return True
+
+    def drop(self, key):
+        with self._lock:
+            self._entries.pop(key, None)
+
+    def clear(self):
+        with self._lock:
+            ready = [key for key, flight in self._entries.items() if flight.done.is_set()]
+            for key in ready:
+                del self._entries[key]
+        return len(ready)
+
+    def drain(self, timeout=None):
+        deadline = None if timeout is None else time.monotonic() + timeout
+        with self._lock:
+            flights = [flight for flight in self._entries.values() if not flight.done.is_set()]
+        for flight in flights:
+            left = None if deadline is None else max(deadline - time.monotonic(), 0)
+            if not flight.done.wait(left):
+                return False
+        with self._lock:
+            self._entries.clear()
+        return True
+
+    def close(self):
+        with self._lock:
+            if self._closed:
+                return
+            self._closed = True
+            flights = list(self._entries.values())
+            self._entries.clear()
+        for flight in flights:
+            if not flight.done.is_set():
+                flight.error = CacheClosed("The cache was closed")
+                flight.done.set()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc_info):
+        self.close()
+        return False
+
+    def drop_prefix(self, prefix):
+        return self._drop_where(lambda key: key.startswith(prefix))
+
+    def drop_matching(self, pattern):
+        return self._drop_where(lambda key: fnmatch.fnmatchcase(key, pattern))
+
+    def _drop_where(self, predicate):
+        with self._lock:
+            keys = [key for key in self._entries if predicate(key)]
+            for key in keys:
+                del self._entries[key]
+            return len(keys)
+
+    def stats(self):
+        with self._lock:
+            return {
+                "entries": len(self._entries),
+                "hits": self._hits,
+                "misses": self._misses,
+                "coalesced_waits": self._coalesced_waits,
+                "timeouts": self._timeouts,
+                "evictions": self._evictions,
+            }
+
+    def reset_stats(self):
+        self._hits = 0
+        self._misses = 0
+        self._coalesced_waits = 0
+        self._timeouts = 0
+        self._evictions = 0
+
+    def __len__(self):
+        return len(self._entries)
+
+
+_DEFAULT_CACHE = None
+_DEFAULT_CACHE_LOCK = threading.Lock()
+
+
+def default_cache():
+    global _DEFAULT_CACHE
+    with _DEFAULT_CACHE_LOCK:
+        if _DEFAULT_CACHE is None:
+            _DEFAULT_CACHE = PyCache(60.0)
+        return _DEFAULT_CACHE
+
+
+def call(func, *args, key, **kwargs):
+    return default_cache().py_call(func, args, kwargs, key)
+
+
+def get(key):
+    return default_cache()._lookup(key)
+
+
+def set(key, value, *, refresher=None):
+    return default_cache().set(key, value, refresher=refresher)
+
+
+def invalidate(key):
+    default_cache().drop(key)
+
+
+def stats():
+    return default_cache().stats()
+
+
+def reset_stats():
+    default_cache().reset_stats()
@@ -0,0 +1 @@
+"""Integrations built on top of the core cache."""
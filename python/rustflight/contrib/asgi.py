@@ -0,0 +1,158 @@
+"""ASGI middleware caching whole HTTP responses.
+
+Responses are keyed by method, path, query string and the values of the
+request headers listed in ``vary``. Concurrent identical requests share one
+upstream call: the first runs the wrapped app, the others wait for its
+response. Streamed bodies are buffered while the leader runs and replayed
+chunk by chunk, so clients still receive them as a stream.
+
+Usage::
+
+    from rustflight import PyCache
+    from rustflight.contrib.asgi import CacheMiddleware
+
+    app = CacheMiddleware(app, PyCache(timeout=10.0, ttl=30.0), vary=["accept"])
+"""
+
+import asyncio
+
+__all__ = ["CacheMiddleware"]
+
+
+class _Response:
+    def __init__(self, status, headers, chunks):
+        self.status = status
+        self.headers = headers
+        self.chunks = chunks
+
+    @property
+    def size(self):
+        return sum(len(chunk) for chunk in self.chunks)
+
+
+class CacheMiddleware:
+    """Wraps an ASGI app, serving repeated requests from ``cache``.
+
+    Only requests whose method is in ``methods`` are cached, and only
+    responses whose status is in ``statuses`` and whose body is at most
+    ``max_body_size`` bytes are kept; others still go to every waiter of
+    their flight but are dropped right after. Requests and responses
+    carrying ``Cache-Control: no-store`` are never cached. With
+    ``cache_header`` set, responses carry that header with ``hit`` or
+    ``miss``.
+    """
+
+    def __init__(
+        self,
+        app,
+        cache,
+        *,
+        methods=("GET", "HEAD"),
+        vary=(),
+        statuses=(200, 203, 204, 301, 404, 410),
+        max_body_size=1024 * 1024,
+        cache_header="x-cache",
+        prefix="asgi",
+    ):
+        self.app = app
+        self.cache = cache
+        self.methods = {method.upper() for method in methods}
+        self.vary = [name.lower().encode("latin-1") for name in vary]
+        self.statuses = set(statuses)
+        self.max_body_size = max_body_size
+        self.cache_header = cache_header.lower().encode("latin-1") if cache_header else None
+        self.prefix = prefix
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http" or scope["method"] not in self.methods:
+            await self.app(scope, receive, send)
+            return
+        headers = _header_map(scope.get("headers", ()))
+        if b"no-store" in headers.get(b"cache-control", b""):
+            await self.app(scope, receive, send)
+            return
+
+        key = self.key(scope, headers)
+        loop = asyncio.get_running_loop()
+        led = False
+
+        # The cache blocks while waiting on a flight, so it runs on a worker
+        # thread; the leader hands the app back to this event loop
+        def fetch():
+            nonlocal led
+            led = True
+            future = asyncio.run_coroutine_threadsafe(self._capture(scope, receive), loop)
+            return future.result()
+
+        response = await loop.run_in_executor(
+            None, self.cache.py_call, fetch, (), {}, key
+        )
+        if led and not self._storable(response):
+            self.cache.drop(key)
+        await self._replay(response, send, "miss" if led else "hit")
+
+    def key(self, scope, headers):
+        """Cache key of a request; override to change what tells them apart."""
+        parts = [
+            self.prefix,
+            scope["method"],
+            scope.get("root_path", "") + scope["path"],
+            scope.get("query_string", b"").decode("latin-1"),
+        ]
+        for name in self.vary:
+            parts.append(headers.get(name, b"").decode("latin-1"))
+        return "|".join(parts)
+
+    def _storable(self, response):
+        if response.status not in self.statuses:
+            return False
+        if response.size > self.max_body_size:
+            return False
+        cache_control = _header_map(response.headers).get(b"cache-control", b"")
+        return b"no-store" not in cache_control and b"private" not in cache_control
+
+    async def _capture(self, scope, receive):
+        status = 500
+        headers = []
+        chunks = []
+
+        async def send(message):
+            nonlocal status, headers
+            if message["type"] == "http.response.start":
+                status = message["status"]
+                headers = list(message.get("headers", ()))
+            elif message["type"] == "http.response.body":
+                body = message.get("body", b"")
+                if body:
+                    chunks.append(bytes(body))
+
+        await self.app(scope, receive, send)
+        return _Response(status, headers, chunks)
+
+    async def _replay(self, response, send, outcome):
+        headers = list(response.headers)
+        if self.cache_header is not None:
+            headers.append((self.cache_header, outcome.encode("latin-1")))
+        await send(
+            {"type": "http.response.start", "status": response.status, "headers": headers}
+        )
+        if not response.chunks:
+            await send({"type": "http.response.body", "body": b"", "more_body": False})
+            return
+        last = len(response.chunks) - 1
+        for index, chunk in enumerate(response.chunks):
+            await send(
+                {"type": "http.response.body", "body": chunk, "more_body": index < last}
+            )
+
+
+def _header_map(headers):
+    # Repeated headers are joined as HTTP allows; names are lower case in ASGI
+    joined = {}
+    for name, value in headers:
+        name = bytes(name).lower()
+        if name in joined:
+            joined[name] += b", " + bytes(value)
+        else:
+            joined[name] = bytes(value)
+    return joined
@@ -0,0 +1,243 @@
+use crate::mismatch::func_identity;
+use crate::trace::key_hash;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{
+    PyBool, PyBytes, PyDict, PyFloat, PyFrozenSet, PyInt, PyList, PySet, PyString, PyTuple,
+};
+
+// Containers nested deeper than this, self-referencing ones included, are
+// rejected
+const MAX_KEY_DEPTH: usize = 32;
+
+// Objects of other types name the value that stands for them in the key
+const KEY_METHOD: &str = "__rustflight_key__";
+
+// Key for a call of `func` with these arguments: the function's qualified
+// name followed by a hash of the arguments. Keyword order does not matter.
+//
+// The arguments are encoded and hashed in Rust rather than with Python's
+// `hash`, so keys do not depend on PYTHONHASHSEED and match across
+// processes, platforms and interpreter runs.
+pub(crate) fn derive_key(
+    func: &Bound<'_, PyAny>,
+    args: &Bound<'_, PyTuple>,
+    kwargs: &Bound<'_, PyDict>,
+) -> PyResult<String> {
+    let mut items: Vec<(String, Bound<'_, PyAny>)> = kwargs
+        .iter()
+        .map(|(name, value)| Ok((name.extract()?, value)))
+        .collect::<PyResult<_>>()?;
+    items.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    let mut encoded = Vec::new();
+    encode(args.as_any(), 0, &mut encoded)?;
+    for (name, value) in items {
+        encode_bytes(b'k', name.as_bytes(), &mut encoded);
+        encode(&value, 1, &mut encoded)?;
+    }
+    Ok(format!(
+        "{}:{:016x}",
+        func_identity(func),
+        key_hash(&encoded)
+    ))
+}
+
+// Tags every value with its type, so `1`, `1.0` and `True` give different
+// keys. Dicts and sets are sorted by their encoding. Values of any other
+// type need `__rustflight_key__`: a repr may carry an address, which would
+// give every call its own key.
+fn encode(value: &Bound<'_, PyAny>, depth: usize, out: &mut Vec<u8>) -> PyResult<()> {
+    if depth > MAX_KEY_DEPTH {
+        return Err(PyTypeError::new_err(format!(
+            "Cannot derive a cache key from arguments nested deeper than {} levels, pass a key",
+            MAX_KEY_DEPTH
+        )));
+    }
+    if value.is_none() {
+        out.push(b'N');
+    } else if let Ok(flag) = value.downcast::<PyBool>() {
+        out.push(if flag.is_true() { b'T' } else { b'F' });
+    } else if value.is_instance_of::<PyInt>() {
+        // Arbitrary precision, so go through the decimal digits
+        out.push(b'i');
+        out.extend_from_slice(value.str()?.to_string_lossy().as_bytes());
+        out.push(b';');
+    } else if let Ok(float) = value.downcast::<PyFloat>() {
+        out.push(b'f');
+        out.extend_from_slice(&float.value().to_bits().to_le_bytes());
+    } else if let Ok(string) = value.downcast::<PyString>() {
+        encode_bytes(b's', string.to_string_lossy().as_bytes(), out);
+    } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+        encode_bytes(b'b', bytes.as_bytes(), out);
+    } else if let Ok(tuple) = value.downcast::<PyTuple>() {
+        encode_len(b'(', tuple.len(), out);
+        for item in tuple.iter() {
+            encode(&item, depth + 1, out)?;
+        }
+    } else if let Ok(list) = value.downcast::<PyList>() {
+        encode_len(b'[', list.len(), out);
+        for item in list.iter() {
+            encode(&item, depth + 1, out)?;
+        }
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        let mut pairs = Vec::with_capacity(dict.len());
+        for (key, value) in dict.iter() {
+            let mut pair = Vec::new();
+            encode(&key, depth + 1, &mut pair)?;
+            encode(&value, depth + 1, &mut pair)?;
+            pairs.push(pair);
+        }
+        encode_sorted(b'{', pairs, out);
+    } else if let Ok(set) = value.downcast::<PySet>() {
+        let items = set
+            .iter()
+            .map(|item| encoded(&item, depth + 1))
+            .collect::<PyResult<_>>()?;
+        encode_sorted(b'<', items, out);
+    } else if let Ok(set) = value.downcast::<PyFrozenSet>() {
+        let items = set
+            .iter()
+            .map(|item| encoded(&item, depth + 1))
+            .collect::<PyResult<_>>()?;
+        encode_sorted(b'<', items, out);
+    } else if value.hasattr(KEY_METHOD)? {
+        // Tagged with the type, so equal keys of different types differ
+        encode_bytes(
+            b'o',
+            value
+                .get_type()
+                .fully_qualified_name()?
+                .to_str()?
+                .as_bytes(),
+            out,
+        );
+        encode(&value.call_method0(KEY_METHOD)?, depth + 1, out)?;
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "Cannot derive a cache key from an argument of type '{}', define {} on it or pass a key",
+            value.get_type().fully_qualified_name()?,
+            KEY_METHOD
+        )));
+    }
+    Ok(())
+}
+
+fn encoded(value: &Bound<'_, PyAny>, depth: usize) -> PyResult<Vec<u8>> {
+    let mut out = Vec::new();
+    encode(value, depth, &mut out)?;
+    Ok(out)
+}
+
+// Lengths are written out in full so no two encodings run into each other
+fn encode_len(tag: u8, len: usize, out: &mut Vec<u8>) {
+    out.push(tag);
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn encode_bytes(tag: u8, bytes: &[u8], out: &mut Vec<u8>) {
+    encode_len(tag, bytes.len(), out);
+    out.extend_from_slice(bytes);
+}
+
+fn encode_sorted(tag: u8, mut items: Vec<Vec<u8>>, out: &mut Vec<u8>) {
+    items.sort();
+    encode_len(tag, items.len(), out);
+    for item in items {
+        out.extend_from_slice(&item);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::IntoPyDict;
+
+    fn key(func: &Bound<'_, PyAny>, args: &Bound<'_, PyTuple>, kwargs: &[(&str, i32)]) -> String {
+        let kwargs = kwargs.into_py_dict(func.py()).unwrap();
+        derive_key(func, args, &kwargs).unwrap()
+    }
+
+    #[test]
+    fn test_derive_key() {
+        Python::with_gil(|py| {
+            let func = py.import("math").unwrap().getattr("pow").unwrap();
+            let args = PyTuple::new(py, [1, 2]).unwrap();
+
+            let plain = key(&func, &args, &[("a", 1), ("b", 2)]);
+            assert!(plain.starts_with("math.pow:"));
+            assert_eq!(plain, key(&func, &args, &[("b", 2), ("a", 1)]));
+            assert_ne!(plain, key(&func, &args, &[("a", 1)]));
+
+            // Unhashable arguments still give a stable key
+            let list = (vec![1, 2],).into_pyobject(py).unwrap();
+            assert_eq!(key(&func, &list, &[]), key(&func, &list, &[]));
+        });
+    }
+
+    #[test]
+    fn test_derive_key_is_stable() {
+        // Fixed values: these must not change across runs, processes or
+        // releases, or keys shared through other processes stop matching
+        Python::with_gil(|py| {
+            let func = py.import("math").unwrap().getattr("pow").unwrap();
+            let args = PyTuple::new(py, [1, 2]).unwrap();
+            assert_eq!(key(&func, &args, &[("a", 1)]), "math.pow:e4ed8abd916c7fce");
+            let args = PyTuple::new(py, ["hello"]).unwrap();
+            assert_eq!(key(&func, &args, &[]), "math.pow:c5ceaf12cc5eaeb2");
+        });
+    }
+
+    #[test]
+    fn test_unsupported_arguments() {
+        Python::with_gil(|py| {
+            let func = py.import("math").unwrap().getattr("pow").unwrap();
+            let globals = PyDict::new(py);
+            py.run(
+                c"
+class Plain:
+    pass
+
+class Keyed:
+    def __init__(self, id):
+        self.id = id
+
+    def __rustflight_key__(self):
+        return self.id
+
+nested = []
+for _ in range(40):
+    nested = [nested]
+",
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            let object = |code: &std::ffi::CStr| {
+                let value = py.eval(code, Some(&globals), None).unwrap();
+                PyTuple::new(py, [value]).unwrap()
+            };
+
+            // The default repr carries the address, so it cannot be a key
+            let err = derive_key(&func, &object(c"Plain()"), &PyDict::new(py)).unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+            assert!(err.to_string().contains("__rustflight_key__"));
+            let err = derive_key(&func, &object(c"nested"), &PyDict::new(py)).unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+
+            assert_eq!(
+                key(&func, &object(c"Keyed(1)"), &[]),
+                key(&func, &object(c"Keyed(1)"), &[])
+            );
+            assert_ne!(
+                key(&func, &object(c"Keyed(1)"), &[]),
+                key(&func, &object(c"Keyed(2)"), &[])
+            );
+            // Not the same key as the bare value it stands for
+            assert_ne!(
+                key(&func, &object(c"Keyed(1)"), &[]),
+                key(&func, &object(c"1"), &[])
+            );
+        });
+    }
+}
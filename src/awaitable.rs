@@ -0,0 +1,27 @@
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+
+static ISCOROUTINE: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+
+// Runs a coroutine returned by an `async def` leader to completion, so the
+// cache holds its result rather than the coroutine. With an `event_loop` the
+// coroutine is scheduled there, which must be running in another thread;
+// otherwise it runs on a fresh loop in the calling thread.
+pub(crate) fn await_result(
+    py: Python<'_>,
+    event_loop: Option<&Py<PyAny>>,
+    value: Py<PyAny>,
+) -> PyResult<Py<PyAny>> {
+    let iscoroutine = ISCOROUTINE.import(py, "asyncio", "iscoroutine")?;
+    if !iscoroutine.call1((&value,))?.is_truthy()? {
+        return Ok(value);
+    }
+    let asyncio = py.import("asyncio")?;
+    let result = match event_loop {
+        Some(event_loop) => asyncio
+            .call_method1("run_coroutine_threadsafe", (value, event_loop))?
+            .call_method0("result")?,
+        None => asyncio.call_method1("run", (value,))?,
+    };
+    Ok(result.unbind())
+}
@@ -0,0 +1,432 @@
+use crate::config::{CacheConfig, WakeStrategy};
+use crate::py_waiter::{CallOptions, PyCache};
+use crate::simulate::get_option;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString, PyTuple};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_KEYS: usize = 1000;
+const DEFAULT_ITERATIONS: usize = 100_000;
+const DEFAULT_THRESHOLD: f64 = 0.1;
+
+// Metrics compared against a baseline, where higher is worse
+const LATENCY_METRICS: [&str; 4] = ["hit_mean_ns", "hit_p50_ns", "hit_p99_ns", "miss_mean_ns"];
+
+struct Profile<'py> {
+    name: Option<String>,
+    keys: usize,
+    iterations: usize,
+    baseline: Option<Bound<'py, PyDict>>,
+    threshold: f64,
+}
+
+impl<'py> Profile<'py> {
+    fn from_dict(profile: Option<&Bound<'py, PyDict>>) -> PyResult<Self> {
+        let Some(profile) = profile else {
+            return Ok(Self {
+                name: None,
+                keys: DEFAULT_KEYS,
+                iterations: DEFAULT_ITERATIONS,
+                baseline: None,
+                threshold: DEFAULT_THRESHOLD,
+            });
+        };
+        Ok(Self {
+            name: get_option(profile, "name")?,
+            keys: get_option(profile, "keys")?.unwrap_or(DEFAULT_KEYS).max(1),
+            iterations: get_option(profile, "iterations")?.unwrap_or(DEFAULT_ITERATIONS),
+            baseline: get_option(profile, "baseline")?,
+            threshold: get_option(profile, "threshold")?.unwrap_or(DEFAULT_THRESHOLD),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Regression {
+    metric: &'static str,
+    baseline: f64,
+    current: f64,
+}
+
+fn mean(samples: &[u64]) -> f64 {
+    match samples.len() {
+        0 => 0.0,
+        len => samples.iter().sum::<u64>() as f64 / len as f64,
+    }
+}
+
+fn percentile(sorted: &[u64], quantile: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        len => sorted[((len - 1) as f64 * quantile).round() as usize] as f64,
+    }
+}
+
+fn latency_metrics(results: &Bound<'_, PyDict>) -> PyResult<Vec<(&'static str, f64)>> {
+    let mut metrics = Vec::new();
+    for metric in LATENCY_METRICS {
+        if let Some(value) = get_option(results, metric)? {
+            metrics.push((metric, value));
+        }
+    }
+    Ok(metrics)
+}
+
+fn regressions(
+    current: &[(&'static str, f64)],
+    baseline: &[(&'static str, f64)],
+    threshold: f64,
+) -> Vec<Regression> {
+    current
+        .iter()
+        .filter_map(|&(metric, current)| {
+            let &(_, baseline) = baseline.iter().find(|(name, _)| *name == metric)?;
+            (current > baseline * (1.0 + threshold)).then_some(Regression {
+                metric,
+                baseline,
+                current,
+            })
+        })
+        .collect()
+}
+
+fn regression_list<'py>(
+    py: Python<'py>,
+    regressions: &[Regression],
+) -> PyResult<Bound<'py, PyList>> {
+    let list = PyList::empty(py);
+    for regression in regressions {
+        let item = PyDict::new(py);
+        item.set_item("metric", regression.metric)?;
+        item.set_item("baseline", regression.baseline)?;
+        item.set_item("current", regression.current)?;
+        list.append(item)?;
+    }
+    Ok(list)
+}
+
+// Times the hit path of a fresh cache, plus the miss path while warming it,
+// so CI can track both without a separate benchmark binary.
+#[pyfunction]
+#[pyo3(signature = (profile=None))]
+pub fn run<'py>(
+    py: Python<'py>,
+    profile: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profile = Profile::from_dict(profile)?;
+    let cache = PyCache::with_config(CacheConfig::default());
+    let func = py.import("builtins")?.getattr("str")?.unbind();
+    let kwargs = PyDict::new(py).into_any().unbind();
+    let keys = (0..profile.keys)
+        .map(|index| {
+            let args = PyTuple::new(py, [index])?.into_any().unbind();
+            Ok((format!("bench:{}", index), args))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let call = |key: &str, args: &Py<PyAny>| -> PyResult<u64> {
+        let (func, args, kwargs) = (func.clone_ref(py), args.clone_ref(py), kwargs.clone_ref(py));
+        let started = Instant::now();
+        cache.call(py, func, args, kwargs, key, CallOptions::default())?;
+        Ok(started.elapsed().as_nanos() as u64)
+    };
+
+    let misses = keys
+        .iter()
+        .map(|(key, args)| call(key, args))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let started = Instant::now();
+    let mut hits = keys
+        .iter()
+        .cycle()
+        .take(profile.iterations)
+        .map(|(key, args)| call(key, args))
+        .collect::<PyResult<Vec<_>>>()?;
+    let elapsed = started.elapsed().as_secs_f64();
+    hits.sort_unstable();
+
+    let results = PyDict::new(py);
+    results.set_item("name", profile.name.as_deref())?;
+    results.set_item("keys", profile.keys)?;
+    results.set_item("iterations", profile.iterations)?;
+    results.set_item("hit_mean_ns", mean(&hits))?;
+    results.set_item("hit_p50_ns", percentile(&hits, 0.5))?;
+    results.set_item("hit_p99_ns", percentile(&hits, 0.99))?;
+    results.set_item("miss_mean_ns", mean(&misses))?;
+    let ops_per_sec = if elapsed > 0.0 {
+        hits.len() as f64 / elapsed
+    } else {
+        0.0
+    };
+    results.set_item("ops_per_sec", ops_per_sec)?;
+
+    if let Some(baseline) = &profile.baseline {
+        let found = regressions(
+            &latency_metrics(&results)?,
+            &latency_metrics(baseline)?,
+            profile.threshold,
+        );
+        results.set_item("passed", found.is_empty())?;
+        results.set_item("regressions", regression_list(py, &found)?)?;
+    }
+    Ok(results)
+}
+
+// Latency metrics of `results` that exceed `baseline` by more than `threshold`
+#[pyfunction]
+#[pyo3(signature = (results, baseline, threshold=DEFAULT_THRESHOLD))]
+pub fn compare<'py>(
+    py: Python<'py>,
+    results: &Bound<'py, PyDict>,
+    baseline: &Bound<'py, PyDict>,
+    threshold: f64,
+) -> PyResult<Bound<'py, PyList>> {
+    let found = regressions(
+        &latency_metrics(results)?,
+        &latency_metrics(baseline)?,
+        threshold,
+    );
+    regression_list(py, &found)
+}
+
+// Nanoseconds from completing an external flight until each of `waiters`
+// threads blocked on it through `PyCache::call` has its value back, sorted.
+// Waiters also recheck their entry every SIGNAL_CHECK_INTERVAL, so a staged
+// wakeup that stalls is caught there, and that shows up in the tail.
+fn wakeups(py: Python<'_>, waiters: usize, wake: WakeStrategy) -> PyResult<Vec<u64>> {
+    let cache = PyCache::with_config(CacheConfig {
+        wake,
+        ..Default::default()
+    });
+    cache.start(py, "bench")?;
+    let func = py.import("builtins")?.getattr("str")?.unbind();
+    let resolved = OnceLock::new();
+    let coalesced = |py| -> PyResult<usize> {
+        Ok(get_option(&cache.stats(py)?, "coalesced_waits")?.unwrap_or(0))
+    };
+    let mut latencies = thread::scope(|scope| {
+        let handles: Vec<_> = (0..waiters)
+            .map(|_| {
+                scope.spawn(|| {
+                    Python::with_gil(|py| {
+                        let args = PyTuple::empty(py).into_any().unbind();
+                        let kwargs = PyDict::new(py).into_any().unbind();
+                        let options = CallOptions::default();
+                        cache.call(py, func.clone_ref(py), args, kwargs, "bench", options)?;
+                        let resolved: &Instant = resolved.get().expect("Woken before completion");
+                        Ok(resolved.elapsed().as_nanos() as u64)
+                    })
+                })
+            })
+            .collect();
+        while coalesced(py)? < waiters {
+            py.allow_threads(|| thread::sleep(Duration::from_millis(1)));
+        }
+        let value = PyString::new(py, "bench").into_any().unbind();
+        resolved.set(Instant::now()).unwrap();
+        cache.complete(py, "bench", value)?;
+        py.allow_threads(|| {
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<PyResult<Vec<_>>>()
+        })
+    })?;
+    latencies.sort_unstable();
+    Ok(latencies)
+}
+
+// Compares how long waiters of one flight take to get its value under each
+// wake strategy, GIL included; the spread between p50 and p99 shows lock
+// contention
+#[pyfunction]
+#[pyo3(signature = (waiters=1000, batch=64))]
+pub fn wake_strategies(
+    py: Python<'_>,
+    waiters: usize,
+    batch: usize,
+) -> PyResult<Bound<'_, PyDict>> {
+    let strategies = [
+        WakeStrategy::All,
+        WakeStrategy::Chain,
+        WakeStrategy::Batch(batch.max(1)),
+    ];
+    let results = PyDict::new(py);
+    results.set_item("waiters", waiters)?;
+    results.set_item("batch", batch.max(1))?;
+    for strategy in strategies {
+        let latencies = wakeups(py, waiters, strategy)?;
+        let metrics = PyDict::new(py);
+        let p50 = percentile(&latencies, 0.5);
+        let p99 = percentile(&latencies, 0.99);
+        metrics.set_item("mean_ns", mean(&latencies))?;
+        metrics.set_item("p50_ns", p50)?;
+        metrics.set_item("p99_ns", p99)?;
+        metrics.set_item("spread_ns", p99 - p50)?;
+        results.set_item(strategy.as_str(), metrics)?;
+    }
+    Ok(results)
+}
+
+// Nanoseconds per call of `threads` threads walking the same cold keys at
+// once, so that most calls either lead a flight or wait on one, sorted
+fn cold_calls(
+    py: Python<'_>,
+    cache: &PyCache,
+    threads: usize,
+    keys: &[(String, Py<PyAny>)],
+) -> PyResult<Vec<u64>> {
+    let func = py.import("builtins")?.getattr("str")?.unbind();
+    let mut latencies = py
+        .allow_threads(|| {
+            thread::scope(|scope| {
+                let handles: Vec<_> = (0..threads)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            keys.iter()
+                                .map(|(key, args)| {
+                                    Python::with_gil(|py| {
+                                        let args = args.clone_ref(py);
+                                        let kwargs = PyDict::new(py).into_any().unbind();
+                                        let started = Instant::now();
+                                        let options = CallOptions::default();
+                                        cache.call(
+                                            py,
+                                            func.clone_ref(py),
+                                            args,
+                                            kwargs,
+                                            key,
+                                            options,
+                                        )?;
+                                        Ok(started.elapsed().as_nanos() as u64)
+                                    })
+                                })
+                                .collect::<PyResult<Vec<_>>>()
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect::<PyResult<Vec<_>>>()
+            })
+        })?
+        .concat();
+    latencies.sort_unstable();
+    Ok(latencies)
+}
+
+// Compares entries carrying a condition variable each with entries sharing
+// `stripes` of them, through the cache's own call path
+#[pyfunction]
+#[pyo3(signature = (threads=8, keys=1000, stripes=64))]
+pub fn lock_stripes(
+    py: Python<'_>,
+    threads: usize,
+    keys: usize,
+    stripes: usize,
+) -> PyResult<Bound<'_, PyDict>> {
+    let keys = (0..keys)
+        .map(|index| {
+            let args = PyTuple::new(py, [index])?.into_any().unbind();
+            Ok((format!("bench:{}", index), args))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let results = PyDict::new(py);
+    results.set_item("threads", threads)?;
+    results.set_item("keys", keys.len())?;
+    results.set_item("stripes", stripes.max(1))?;
+    for (mode, lock_stripes) in [("per_entry", None), ("striped", Some(stripes.max(1)))] {
+        let cache = PyCache::with_config(CacheConfig {
+            lock_stripes,
+            ..Default::default()
+        });
+        let started = Instant::now();
+        let latencies = cold_calls(py, &cache, threads, &keys)?;
+        let elapsed = started.elapsed().as_secs_f64();
+        let metrics = PyDict::new(py);
+        metrics.set_item("mean_ns", mean(&latencies))?;
+        metrics.set_item("p50_ns", percentile(&latencies, 0.5))?;
+        metrics.set_item("p99_ns", percentile(&latencies, 0.99))?;
+        let ops_per_sec = if elapsed > 0.0 {
+            latencies.len() as f64 / elapsed
+        } else {
+            0.0
+        };
+        metrics.set_item("ops_per_sec", ops_per_sec)?;
+        results.set_item(mode, metrics)?;
+    }
+    Ok(results)
+}
+
+pub(crate) fn register(parent: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = parent.py();
+    let bench = PyModule::new(py, "bench")?;
+    bench.add_function(wrap_pyfunction!(run, &bench)?)?;
+    bench.add_function(wrap_pyfunction!(compare, &bench)?)?;
+    bench.add_function(wrap_pyfunction!(wake_strategies, &bench)?)?;
+    bench.add_function(wrap_pyfunction!(lock_stripes, &bench)?)?;
+    parent.add_submodule(&bench)?;
+    // Makes `import rustflight.bench` work, not just attribute access
+    py.import("sys")?
+        .getattr("modules")?
+        .set_item("rustflight.bench", &bench)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_regressions() {
+        let samples = [10, 20, 30, 40, 1000];
+        assert_eq!(mean(&samples), 220.0);
+        assert_eq!(percentile(&samples, 0.5), 30.0);
+        assert_eq!(percentile(&samples, 0.99), 1000.0);
+        assert_eq!(percentile(&[], 0.5), 0.0);
+
+        let baseline = [("hit_mean_ns", 100.0), ("hit_p99_ns", 400.0)];
+        let current = [
+            ("hit_mean_ns", 105.0),
+            ("hit_p99_ns", 500.0),
+            ("miss_mean_ns", 9000.0),
+        ];
+        assert_eq!(
+            regressions(&current, &baseline, 0.1),
+            [Regression {
+                metric: "hit_p99_ns",
+                baseline: 400.0,
+                current: 500.0
+            }]
+        );
+        assert!(regressions(&current, &baseline, 0.5).is_empty());
+    }
+
+    #[test]
+    fn test_lock_stripes() {
+        Python::with_gil(|py| {
+            let results = lock_stripes(py, 4, 50, 8).unwrap();
+            for mode in ["per_entry", "striped"] {
+                let metrics: Bound<'_, PyDict> = get_option(&results, mode).unwrap().unwrap();
+                let ops_per_sec: f64 = get_option(&metrics, "ops_per_sec").unwrap().unwrap();
+                assert!(ops_per_sec > 0.0);
+            }
+        })
+    }
+
+    #[test]
+    fn test_wake_strategies() {
+        Python::with_gil(|py| {
+            let results = wake_strategies(py, 8, 2).unwrap();
+            for strategy in ["all", "chain", "batch"] {
+                let metrics: Bound<'_, PyDict> = get_option(&results, strategy).unwrap().unwrap();
+                let p99: f64 = get_option(&metrics, "p99_ns").unwrap().unwrap();
+                assert!(p99 > 0.0);
+            }
+        })
+    }
+}
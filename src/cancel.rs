@@ -0,0 +1,20 @@
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[pyclass(frozen)]
+#[derive(Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+}
+
+#[pymethods]
+impl CancelToken {
+    #[getter]
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
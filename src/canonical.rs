@@ -0,0 +1,118 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use std::borrow::Cow;
+
+enum Step {
+    Lowercase,
+    Nfc,
+    Trim,
+    Custom(Py<PyAny>),
+}
+
+impl Step {
+    fn parse(spec: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if spec.is_callable() {
+            return Ok(Step::Custom(spec.clone().unbind()));
+        }
+        match spec.extract::<String>()?.as_str() {
+            "lowercase" => Ok(Step::Lowercase),
+            "nfc" => Ok(Step::Nfc),
+            "trim" => Ok(Step::Trim),
+            name => Err(PyValueError::new_err(format!(
+                "Unknown canonicalizer '{}', expected 'lowercase', 'nfc', 'trim' or a callable",
+                name
+            ))),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Step::Lowercase => "lowercase",
+            Step::Nfc => "nfc",
+            Step::Trim => "trim",
+            Step::Custom(_) => "custom",
+        }
+    }
+}
+
+// Rewrites keys before they reach the map so that different spellings of
+// the same key share one entry. Steps run in the order they were given.
+pub(crate) struct Canonicalizer {
+    steps: Vec<Step>,
+}
+
+impl Canonicalizer {
+    // Accepts a single built-in name or callable, or a sequence of them
+    pub(crate) fn parse(spec: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let steps = if spec.is_instance_of::<PyString>() || spec.is_callable() {
+            vec![Step::parse(spec)?]
+        } else {
+            spec.try_iter()?
+                .map(|spec| Step::parse(&spec?))
+                .collect::<PyResult<_>>()?
+        };
+        Ok(Self { steps })
+    }
+
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        self.steps.iter().map(Step::as_str).collect()
+    }
+
+    pub(crate) fn apply<'a>(&self, py: Python<'_>, key: &'a str) -> PyResult<Cow<'a, str>> {
+        let mut key = Cow::Borrowed(key);
+        for step in &self.steps {
+            key = match step {
+                Step::Lowercase => lowercase(key),
+                Step::Trim => match key {
+                    Cow::Borrowed(key) => Cow::Borrowed(key.trim()),
+                    Cow::Owned(key) => Cow::Owned(key.trim().to_string()),
+                },
+                // ASCII is already in normal form
+                Step::Nfc if key.is_ascii() => key,
+                Step::Nfc => Cow::Owned(
+                    py.import("unicodedata")?
+                        .call_method1("normalize", ("NFC", key.as_ref()))?
+                        .extract()?,
+                ),
+                Step::Custom(canonicalize) => {
+                    Cow::Owned(canonicalize.call1(py, (key.as_ref(),))?.extract(py)?)
+                }
+            };
+        }
+        Ok(key)
+    }
+}
+
+fn lowercase(key: Cow<'_, str>) -> Cow<'_, str> {
+    if !key.is_ascii() {
+        return Cow::Owned(key.to_lowercase());
+    }
+    if key.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        return Cow::Owned(key.to_ascii_lowercase());
+    }
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_canonicalizer() {
+        let canonicalizer = Canonicalizer {
+            steps: vec![Step::Trim, Step::Lowercase],
+        };
+        Python::with_gil(|py| {
+            let key = canonicalizer.apply(py, " User@Example.com\n").unwrap();
+            assert_eq!(key, "user@example.com");
+
+            let key = canonicalizer.apply(py, "user:42").unwrap();
+            assert!(matches!(key, Cow::Borrowed("user:42")));
+
+            let key = canonicalizer.apply(py, "ÄRGER").unwrap();
+            assert_eq!(key, "ärger");
+        });
+        assert_eq!(canonicalizer.names(), ["trim", "lowercase"]);
+    }
+}
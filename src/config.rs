@@ -0,0 +1,417 @@
+use crate::canonical::Canonicalizer;
+use crate::fallback::FallbackPolicy;
+use crate::mismatch::MismatchPolicy;
+use crate::threads::ThreadSettings;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::Condvar;
+use std::time::{Duration, Instant};
+
+// Decides what happens when a manual write lands on a key whose computation
+// is still in flight.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum WritePolicy {
+    // The manual write resolves the flight and the leader's result is dropped
+    First,
+    // The leader's result replaces the manual write, which is dropped
+    Last,
+}
+
+impl WritePolicy {
+    pub(crate) fn parse(policy: &str) -> PyResult<Self> {
+        match policy {
+            "first" => Ok(WritePolicy::First),
+            "last" => Ok(WritePolicy::Last),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown write policy '{}', expected 'first' or 'last'",
+                policy
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WritePolicy::First => "first",
+            WritePolicy::Last => "last",
+        }
+    }
+}
+
+// What happens to the waiters of a flight whose leader raised.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum FailurePolicy {
+    // Re-raise the leader's error in every waiter
+    Broadcast,
+    // Let one waiter retry as the new leader, up to `max_handoffs` times
+    Promote,
+}
+
+impl FailurePolicy {
+    pub(crate) fn parse(policy: &str) -> PyResult<Self> {
+        match policy {
+            "broadcast" => Ok(FailurePolicy::Broadcast),
+            "promote" => Ok(FailurePolicy::Promote),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown failure policy '{}', expected 'broadcast' or 'promote'",
+                policy
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            FailurePolicy::Broadcast => "broadcast",
+            FailurePolicy::Promote => "promote",
+        }
+    }
+}
+
+// What a waiter does once `wait_timeout` runs out on a flight.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum TimeoutPolicy {
+    // Raise TimeoutError
+    Raise,
+    // Start a second computation as the new leader
+    Lead,
+    // Return `timeout_fallback`
+    Fallback,
+}
+
+impl TimeoutPolicy {
+    pub(crate) fn parse(policy: &str) -> PyResult<Self> {
+        match policy {
+            "raise" => Ok(TimeoutPolicy::Raise),
+            "lead" => Ok(TimeoutPolicy::Lead),
+            "fallback" => Ok(TimeoutPolicy::Fallback),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown timeout policy '{}', expected 'raise', 'lead' or 'fallback'",
+                policy
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            TimeoutPolicy::Raise => "raise",
+            TimeoutPolicy::Lead => "lead",
+            TimeoutPolicy::Fallback => "fallback",
+        }
+    }
+}
+
+// How the waiters of a flight are woken once it resolves. Staged strategies
+// wake a few waiters and let each pass the wakeup on when it leaves, so they
+// do not all contend for the entry lock at once.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum WakeStrategy {
+    All,
+    Chain,
+    Batch(usize),
+}
+
+impl WakeStrategy {
+    pub(crate) fn parse(strategy: &str, batch: usize) -> PyResult<Self> {
+        match strategy {
+            "all" => Ok(WakeStrategy::All),
+            "chain" => Ok(WakeStrategy::Chain),
+            "batch" => Ok(WakeStrategy::Batch(batch.max(1))),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown wake strategy '{}', expected 'all', 'chain' or 'batch'",
+                strategy
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WakeStrategy::All => "all",
+            WakeStrategy::Chain => "chain",
+            WakeStrategy::Batch(_) => "batch",
+        }
+    }
+
+    // Called by whoever resolves the flight
+    pub(crate) fn wake(&self, cvar: &Condvar) {
+        match self {
+            WakeStrategy::All => cvar.notify_all(),
+            WakeStrategy::Chain => cvar.notify_one(),
+            WakeStrategy::Batch(batch) => (0..*batch).for_each(|_| cvar.notify_one()),
+        }
+    }
+
+    // Called by each waiter that observed the resolved flight
+    pub(crate) fn pass_on(&self, cvar: &Condvar) {
+        if *self != WakeStrategy::All {
+            cvar.notify_one();
+        }
+    }
+}
+
+pub(crate) struct CacheConfig {
+    // None waits for the leader indefinitely
+    pub(crate) wait_timeout: Option<Duration>,
+    pub(crate) timeout_policy: TimeoutPolicy,
+    pub(crate) failure_policy: FailurePolicy,
+    pub(crate) max_handoffs: u32,
+    pub(crate) timeout_fallback: Option<Py<PyAny>>,
+    pub(crate) leader_elector: Option<Py<PyAny>>,
+    pub(crate) shards: usize,
+    pub(crate) removal_log: Option<usize>,
+    // Entries share this many condition variables instead of one each
+    pub(crate) lock_stripes: Option<usize>,
+    // Threads running `py_call_nowait` calls off the caller's thread
+    pub(crate) call_workers: usize,
+    pub(crate) compute_timeout: Option<Duration>,
+    pub(crate) ttl: Option<Duration>,
+    pub(crate) negative_ttl: Option<Duration>,
+    pub(crate) negative_sentinel: Option<Py<PyAny>>,
+    pub(crate) event_loop: Option<Py<PyAny>>,
+    pub(crate) track_popularity: bool,
+    pub(crate) wake: WakeStrategy,
+    pub(crate) max_size: Option<usize>,
+    pub(crate) store_results: bool,
+    pub(crate) trace_capacity: Option<usize>,
+    pub(crate) sweep_interval: Duration,
+    pub(crate) cancel_abandoned: bool,
+    pub(crate) retries: u32,
+    pub(crate) retry_predicate: Option<Py<PyAny>>,
+    pub(crate) on_error: Option<Py<PyAny>>,
+    pub(crate) post_process: Option<Py<PyAny>>,
+    pub(crate) freeze: bool,
+    pub(crate) check_access: Option<Py<PyAny>>,
+    pub(crate) on_quota_exceeded: Option<Py<PyAny>>,
+    pub(crate) on_func_mismatch: Option<MismatchPolicy>,
+    pub(crate) write_policy: WritePolicy,
+    pub(crate) max_inflight_alarm: Option<Duration>,
+    pub(crate) on_inflight_alarm: Option<Py<PyAny>>,
+    pub(crate) soft_memory: Option<usize>,
+    pub(crate) max_memory: Option<usize>,
+    pub(crate) keep_history: usize,
+    pub(crate) delta_refresh: bool,
+    pub(crate) refresh_comparator: Option<Py<PyAny>>,
+    pub(crate) on_hit: Option<Py<PyAny>>,
+    pub(crate) on_evict: Option<Py<PyAny>>,
+    pub(crate) on_miss: Option<Py<PyAny>>,
+    pub(crate) on_leader_start: Option<Py<PyAny>>,
+    pub(crate) on_leader_done: Option<Py<PyAny>>,
+    pub(crate) on_timeout: Option<Py<PyAny>>,
+    pub(crate) hook_queue_size: usize,
+    pub(crate) record_provenance: bool,
+    pub(crate) canonicalize: Option<Canonicalizer>,
+    pub(crate) fallback: Option<Py<FallbackPolicy>>,
+    pub(crate) on_hook_error: Option<Py<PyAny>>,
+    pub(crate) disable_hook_after: Option<u32>,
+    pub(crate) threads: ThreadSettings,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            wait_timeout: None,
+            timeout_policy: TimeoutPolicy::Lead,
+            failure_policy: FailurePolicy::Broadcast,
+            max_handoffs: 1,
+            timeout_fallback: None,
+            leader_elector: None,
+            shards: 16,
+            removal_log: None,
+            lock_stripes: None,
+            call_workers: 16,
+            compute_timeout: None,
+            ttl: None,
+            negative_ttl: None,
+            negative_sentinel: None,
+            event_loop: None,
+            track_popularity: false,
+            wake: WakeStrategy::All,
+            max_size: None,
+            store_results: true,
+            trace_capacity: None,
+            sweep_interval: Duration::from_secs(1),
+            cancel_abandoned: false,
+            retries: 0,
+            retry_predicate: None,
+            on_error: None,
+            post_process: None,
+            freeze: false,
+            check_access: None,
+            on_quota_exceeded: None,
+            on_func_mismatch: None,
+            write_policy: WritePolicy::Last,
+            max_inflight_alarm: None,
+            on_inflight_alarm: None,
+            soft_memory: None,
+            max_memory: None,
+            keep_history: 0,
+            delta_refresh: false,
+            refresh_comparator: None,
+            on_hit: None,
+            on_evict: None,
+            on_miss: None,
+            on_leader_start: None,
+            on_leader_done: None,
+            on_timeout: None,
+            hook_queue_size: 1024,
+            record_provenance: false,
+            canonicalize: None,
+            fallback: None,
+            on_hook_error: None,
+            disable_hook_after: None,
+            threads: ThreadSettings::default(),
+        }
+    }
+}
+
+// Every duration taken from Python is float seconds. Infinity means no limit.
+pub(crate) fn timeout_from_secs(secs: f64) -> PyResult<Option<Duration>> {
+    if secs.is_nan() || secs < 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "Timeout must be a non-negative number of seconds, got {}",
+            secs
+        )));
+    }
+    match secs.is_infinite() {
+        true => Ok(None),
+        false => Ok(Some(Duration::from_secs_f64(secs))),
+    }
+}
+
+// For optional arguments, where None means no limit as well
+pub(crate) fn optional_secs(secs: Option<f64>) -> PyResult<Option<Duration>> {
+    Ok(secs.map(timeout_from_secs).transpose()?.flatten())
+}
+
+// For intervals and other durations that must be finite
+pub(crate) fn duration_from_secs(secs: f64) -> PyResult<Duration> {
+    timeout_from_secs(secs)?.ok_or_else(|| {
+        PyValueError::new_err("Duration must be a finite number of seconds, got inf")
+    })
+}
+
+fn as_secs(duration: Option<Duration>) -> Option<f64> {
+    duration.map(|duration| duration.as_secs_f64())
+}
+
+impl CacheConfig {
+    // When a value stored now goes stale, if the cache has a `ttl`
+    pub(crate) fn ttl_expiry(&self) -> Option<Instant> {
+        self.ttl.map(|ttl| Instant::now() + ttl)
+    }
+
+    // Like `ttl_expiry`, but "not found" results (None unless a sentinel is
+    // configured) use `negative_ttl`. Calls into Python to compare.
+    pub(crate) fn result_expiry(&self, py: Python<'_>, value: &Py<PyAny>) -> Option<Instant> {
+        let Some(negative_ttl) = self.negative_ttl else {
+            return self.ttl_expiry();
+        };
+        let value = value.bind(py);
+        let negative = match &self.negative_sentinel {
+            Some(sentinel) => value.is(sentinel) || value.eq(sentinel).unwrap_or(false),
+            None => value.is_none(),
+        };
+        match negative {
+            true => Some(Instant::now() + negative_ttl),
+            false => self.ttl_expiry(),
+        }
+    }
+
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let config = PyDict::new(py);
+        config.set_item("wait_timeout", as_secs(self.wait_timeout))?;
+        config.set_item("timeout_policy", self.timeout_policy.as_str())?;
+        config.set_item("on_leader_failure", self.failure_policy.as_str())?;
+        config.set_item("max_handoffs", self.max_handoffs)?;
+        config.set_item("leader_elector", self.leader_elector.is_some())?;
+        config.set_item("shards", self.shards)?;
+        config.set_item("removal_log", self.removal_log)?;
+        config.set_item("lock_stripes", self.lock_stripes)?;
+        config.set_item("call_workers", self.call_workers)?;
+        config.set_item("compute_timeout", as_secs(self.compute_timeout))?;
+        config.set_item("ttl", as_secs(self.ttl))?;
+        config.set_item("negative_ttl", as_secs(self.negative_ttl))?;
+        config.set_item("event_loop", self.event_loop.is_some())?;
+        config.set_item("track_popularity", self.track_popularity)?;
+        config.set_item("max_size", self.max_size)?;
+        config.set_item("store_results", self.store_results)?;
+        config.set_item("wake", self.wake.as_str())?;
+        if let WakeStrategy::Batch(batch) = self.wake {
+            config.set_item("wake_batch", batch)?;
+        }
+        config.set_item("trace_capacity", self.trace_capacity)?;
+        config.set_item("sweep_interval", self.sweep_interval.as_secs_f64())?;
+        config.set_item("cancel_abandoned", self.cancel_abandoned)?;
+        config.set_item("retries", self.retries)?;
+        config.set_item("freeze", self.freeze)?;
+        config.set_item("write_policy", self.write_policy.as_str())?;
+        config.set_item("max_inflight_alarm", as_secs(self.max_inflight_alarm))?;
+        config.set_item("soft_memory", self.soft_memory)?;
+        config.set_item("max_memory", self.max_memory)?;
+        config.set_item("keep_history", self.keep_history)?;
+        config.set_item("delta_refresh", self.delta_refresh)?;
+        config.set_item("hook_queue_size", self.hook_queue_size)?;
+        config.set_item("disable_hook_after", self.disable_hook_after)?;
+        config.set_item("record_provenance", self.record_provenance)?;
+        config.set_item("thread_nice", self.threads.nice)?;
+        config.set_item("thread_affinity", self.threads.affinity.clone())?;
+        config.set_item(
+            "canonicalize",
+            self.canonicalize.as_ref().map(Canonicalizer::names),
+        )?;
+        config.set_item(
+            "on_func_mismatch",
+            self.on_func_mismatch.map(|policy| policy.as_str()),
+        )?;
+        config.set_item(
+            "fallback_max_wait",
+            self.fallback
+                .as_ref()
+                .map(|policy| policy.get().max_wait().as_secs_f64()),
+        )?;
+        let hooks = [
+            ("retry_predicate", &self.retry_predicate),
+            ("on_error", &self.on_error),
+            ("post_process", &self.post_process),
+            ("check_access", &self.check_access),
+            ("on_quota_exceeded", &self.on_quota_exceeded),
+            ("on_inflight_alarm", &self.on_inflight_alarm),
+            ("refresh_comparator", &self.refresh_comparator),
+            ("on_hit", &self.on_hit),
+            ("on_evict", &self.on_evict),
+            ("on_miss", &self.on_miss),
+            ("on_leader_start", &self.on_leader_start),
+            ("on_leader_done", &self.on_leader_done),
+            ("on_timeout", &self.on_timeout),
+            ("on_hook_error", &self.on_hook_error),
+        ];
+        for (name, hook) in hooks {
+            config.set_item(name, hook.is_some())?;
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_float_seconds() {
+        pyo3::prepare_freethreaded_python();
+        assert_eq!(
+            timeout_from_secs(0.25).unwrap(),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(timeout_from_secs(f64::INFINITY).unwrap(), None);
+        assert!(timeout_from_secs(-1.0).is_err());
+        assert!(timeout_from_secs(f64::NAN).is_err());
+
+        assert_eq!(optional_secs(None).unwrap(), None);
+        assert_eq!(optional_secs(Some(f64::INFINITY)).unwrap(), None);
+        assert_eq!(
+            duration_from_secs(1.5).unwrap(),
+            Duration::from_millis(1500)
+        );
+        assert!(duration_from_secs(f64::INFINITY).is_err());
+    }
+}
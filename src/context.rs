@@ -0,0 +1,67 @@
+use crate::cancel::CancelToken;
+use pyo3::prelude::*;
+use std::time::Instant;
+
+#[pyclass(frozen)]
+pub struct FlightContext {
+    #[pyo3(get)]
+    key: String,
+    #[pyo3(get)]
+    attempt: u32,
+    #[pyo3(get)]
+    cancel_token: Py<CancelToken>,
+    #[pyo3(get)]
+    last_exception: Option<Py<PyAny>>,
+    #[pyo3(get)]
+    meta: Option<Py<PyAny>>,
+    deadline: Option<Instant>,
+}
+
+#[pymethods]
+impl FlightContext {
+    // Seconds left before waiters on this flight give up, infinite if they
+    // never do
+    #[getter]
+    fn remaining(&self) -> f64 {
+        match self.deadline {
+            Some(deadline) => deadline
+                .saturating_duration_since(Instant::now())
+                .as_secs_f64(),
+            None => f64::INFINITY,
+        }
+    }
+
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.cancel_token.get().cancelled()
+    }
+}
+
+impl FlightContext {
+    pub(crate) fn new(
+        key: String,
+        attempt: u32,
+        cancel_token: Py<CancelToken>,
+        deadline: Option<Instant>,
+        last_exception: Option<Py<PyAny>>,
+        meta: Option<Py<PyAny>>,
+    ) -> Self {
+        Self {
+            key,
+            attempt,
+            cancel_token,
+            last_exception,
+            meta,
+            deadline,
+        }
+    }
+}
+
+pub(crate) fn accepts_flight_ctx(func: &Bound<'_, PyAny>) -> bool {
+    func.py()
+        .import("inspect")
+        .and_then(|inspect| inspect.call_method1("signature", (func,)))
+        .and_then(|signature| signature.getattr("parameters"))
+        .and_then(|parameters| parameters.contains("flight_ctx"))
+        .unwrap_or(false)
+}
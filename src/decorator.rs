@@ -0,0 +1,193 @@
+use crate::auto_key::derive_key;
+use crate::py_waiter::{CallOptions, PyCache};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString, PyTuple};
+
+enum KeySpec {
+    // Derived from the function and its arguments
+    Derived,
+    // A `str.format` template filled with the call's arguments
+    Template(Py<PyString>),
+    // Called with the same arguments as the function, returns the key
+    Builder(Py<PyAny>),
+}
+
+impl KeySpec {
+    fn new(key: Option<Bound<'_, PyAny>>) -> PyResult<Self> {
+        match key {
+            None => Ok(KeySpec::Derived),
+            Some(key) if key.is_callable() => Ok(KeySpec::Builder(key.unbind())),
+            Some(key) => Ok(KeySpec::Template(key.downcast_into::<PyString>()?.unbind())),
+        }
+    }
+
+    fn key(
+        &self,
+        func: &Bound<'_, PyAny>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: &Bound<'_, PyDict>,
+    ) -> PyResult<String> {
+        let py = func.py();
+        match self {
+            KeySpec::Derived => derive_key(func, args, kwargs),
+            KeySpec::Template(template) => template
+                .bind(py)
+                .call_method("format", args, Some(kwargs))?
+                .extract(),
+            KeySpec::Builder(builder) => builder.bind(py).call(args, Some(kwargs))?.extract(),
+        }
+    }
+
+    fn clone_ref(&self, py: Python<'_>) -> Self {
+        match self {
+            KeySpec::Derived => KeySpec::Derived,
+            KeySpec::Template(template) => KeySpec::Template(template.clone_ref(py)),
+            KeySpec::Builder(builder) => KeySpec::Builder(builder.clone_ref(py)),
+        }
+    }
+}
+
+// Returned by `PyCache.flight(key=...)`; applying it to a function wraps it
+#[pyclass(frozen)]
+pub struct FlightDecorator {
+    cache: Py<PyCache>,
+    key: KeySpec,
+    freeze: Option<bool>,
+}
+
+impl FlightDecorator {
+    pub(crate) fn new(
+        cache: Py<PyCache>,
+        key: Option<Bound<'_, PyAny>>,
+        freeze: Option<bool>,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            cache,
+            key: KeySpec::new(key)?,
+            freeze,
+        })
+    }
+}
+
+#[pymethods]
+impl FlightDecorator {
+    fn __call__(&self, py: Python<'_>, func: Bound<'_, PyAny>) -> PyResult<Py<FlightFunction>> {
+        let wrapper = Py::new(
+            py,
+            FlightFunction {
+                cache: self.cache.clone_ref(py),
+                key: self.key.clone_ref(py),
+                func: func.clone().unbind(),
+                freeze: self.freeze,
+            },
+        )?;
+        py.import("functools")?
+            .call_method1("update_wrapper", (&wrapper, func))?;
+        Ok(wrapper)
+    }
+}
+
+// A function whose every call goes through the cache
+#[pyclass(frozen, dict)]
+pub struct FlightFunction {
+    cache: Py<PyCache>,
+    key: KeySpec,
+    func: Py<PyAny>,
+    freeze: Option<bool>,
+}
+
+#[pymethods]
+impl FlightFunction {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        args: Bound<'_, PyTuple>,
+        kwargs: Option<Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let kwargs = kwargs.unwrap_or_else(|| PyDict::new(py));
+        let key = self.key.key(self.func.bind(py), &args, &kwargs)?;
+        PyCache::call_bounded(
+            &self.cache,
+            py,
+            self.func.clone_ref(py),
+            args.into_any().unbind(),
+            kwargs.into_any().unbind(),
+            &key,
+            CallOptions {
+                freeze: self.freeze,
+                ..Default::default()
+            },
+        )
+    }
+
+    // Binds like a plain function when used on a method
+    fn __get__(
+        slf: &Bound<'_, Self>,
+        instance: Option<Bound<'_, PyAny>>,
+        _owner: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+        match instance.filter(|instance| !instance.is_none()) {
+            Some(instance) => Ok(py
+                .import("types")?
+                .getattr("MethodType")?
+                .call1((slf, instance))?
+                .unbind()),
+            None => Ok(slf.clone().into_any().unbind()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::CacheConfig;
+    use pyo3::ffi::c_str;
+
+    #[test]
+    fn test_decorator() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let cache = Py::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let decorator = |template: &str| {
+                let template = PyString::new(py, template).into_any();
+                FlightDecorator::new(cache.clone_ref(py), Some(template), None).unwrap()
+            };
+            let globals = PyDict::new(py);
+            globals.set_item("flight", decorator("user:{0}")).unwrap();
+            globals
+                .set_item("method_flight", decorator("repo:{1}"))
+                .unwrap();
+            py.run(
+                c_str!(
+                    "calls = []
+
+@flight
+def load(user):
+    calls.append(user)
+    return user * 2
+
+class Repo:
+    @method_flight
+    def get(self, user):
+        return -user
+
+results = (load(1), load(1), Repo().get(3), load.__name__)"
+                ),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+
+            let results = globals.get_item("results").unwrap().unwrap();
+            let (first, second, method, name): (i64, i64, i64, String) = results.extract().unwrap();
+            assert_eq!((first, second, method), (2, 2, -3));
+            assert_eq!(name, "load");
+            let calls = globals.get_item("calls").unwrap().unwrap();
+            assert_eq!(calls.len().unwrap(), 1);
+            assert!(cache.get().lookup(py, "user:1").unwrap().is_some());
+            assert!(cache.get().lookup(py, "repo:3").unwrap().is_some());
+        });
+    }
+}
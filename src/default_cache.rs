@@ -0,0 +1,115 @@
+use crate::config::CacheConfig;
+use crate::py_waiter::{CallOptions, PyCache};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyTuple};
+use std::time::Duration;
+
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+static DEFAULT_CACHE: GILOnceCell<Py<PyCache>> = GILOnceCell::new();
+
+// Process-wide cache behind the module-level functions, created on first use
+#[pyfunction]
+pub fn default_cache(py: Python<'_>) -> PyResult<&Py<PyCache>> {
+    DEFAULT_CACHE.get_or_try_init(py, || {
+        Py::new(
+            py,
+            PyCache::with_config(CacheConfig {
+                wait_timeout: Some(DEFAULT_WAIT_TIMEOUT),
+                ..Default::default()
+            }),
+        )
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (func, *args, key, **kwargs))]
+pub fn call(
+    py: Python<'_>,
+    func: Py<PyAny>,
+    args: Bound<'_, PyTuple>,
+    key: &str,
+    kwargs: Option<Bound<'_, PyDict>>,
+) -> PyResult<Py<PyAny>> {
+    let kwargs = kwargs.unwrap_or_else(|| PyDict::new(py));
+    default_cache(py)?.get().call(
+        py,
+        func,
+        args.into_any().unbind(),
+        kwargs.into_any().unbind(),
+        key,
+        CallOptions::default(),
+    )
+}
+
+#[pyfunction]
+pub fn get(py: Python<'_>, key: &str) -> PyResult<Option<Py<PyAny>>> {
+    default_cache(py)?.get().lookup(py, key)
+}
+
+#[pyfunction]
+#[pyo3(signature = (key, value, *, refresher=None))]
+pub fn set(
+    py: Python<'_>,
+    key: &str,
+    value: Py<PyAny>,
+    refresher: Option<Py<PyAny>>,
+) -> PyResult<bool> {
+    default_cache(py)?.get().store(py, key, value, refresher)
+}
+
+#[pyfunction]
+pub fn invalidate(py: Python<'_>, key: &str) -> PyResult<()> {
+    let cache = default_cache(py)?.get();
+    let key = cache.canonical_key(py, key)?;
+    cache.leases().check(&key, None)?;
+    cache.remove(&key);
+    Ok(())
+}
+
+#[pyfunction]
+pub fn stats(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    default_cache(py)?.get().stats(py)
+}
+
+#[pyfunction]
+pub fn reset_stats(py: Python<'_>) -> PyResult<()> {
+    default_cache(py)?.get().reset_stats();
+    Ok(())
+}
+
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(default_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(call, m)?)?;
+    m.add_function(wrap_pyfunction!(get, m)?)?;
+    m.add_function(wrap_pyfunction!(set, m)?)?;
+    m.add_function(wrap_pyfunction!(invalidate, m)?)?;
+    m.add_function(wrap_pyfunction!(stats, m)?)?;
+    m.add_function(wrap_pyfunction!(reset_stats, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::{ffi::c_str, types::IntoPyDict};
+
+    #[test]
+    fn test_module_call() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda x, y: x * y"), None, None).unwrap();
+            let args = PyTuple::new(py, [6]).unwrap();
+            let kwargs = [("y", 7)].into_py_dict(py).unwrap();
+            let value = call(py, func.unbind(), args, "module:test", Some(kwargs)).unwrap();
+            assert_eq!(value.extract::<i32>(py).unwrap(), 42);
+
+            // Every module-level function shares the one default cache
+            let cached = get(py, "module:test").unwrap().unwrap();
+            assert!(cached.is(&value));
+            invalidate(py, "module:test").unwrap();
+            assert!(get(py, "module:test").unwrap().is_none());
+        });
+    }
+}
@@ -0,0 +1,254 @@
+use crate::py_log;
+use crate::py_waiter::call_hook;
+use crate::supervisor::{Shutdown, Supervisor};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use pyo3::BoundObject;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Calls taken off the queue per GIL acquisition
+const MAX_BATCH: usize = 64;
+// How often an idle worker checks whether the cache was closed
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type Worker = Box<dyn Fn(&Shutdown) + Send>;
+
+struct HookCall {
+    name: &'static str,
+    hook: Py<PyAny>,
+    args: Py<PyTuple>,
+    meta: Option<Py<PyAny>>,
+}
+
+#[derive(Default)]
+struct HookHealth {
+    consecutive_failures: HashMap<&'static str, u32>,
+    disabled: BTreeSet<&'static str>,
+}
+
+impl HookHealth {
+    // Returns whether this failure disabled the hook
+    fn failed(&mut self, name: &'static str, disable_after: Option<u32>) -> bool {
+        let failures = self.consecutive_failures.entry(name).or_insert(0);
+        *failures += 1;
+        disable_after.is_some_and(|limit| *failures >= limit) && self.disabled.insert(name)
+    }
+
+    fn succeeded(&mut self, name: &'static str) {
+        self.consecutive_failures.remove(name);
+    }
+}
+
+#[derive(Default)]
+struct HookStats {
+    queued: AtomicUsize,
+    dispatched: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+    health: Mutex<HookHealth>,
+}
+
+// Notification hooks run on a dedicated thread so user code never sits on
+// the caller's path; when the queue is full new calls are dropped and counted.
+pub(crate) struct HookDispatcher {
+    sender: SyncSender<HookCall>,
+    capacity: usize,
+    stats: Arc<HookStats>,
+    supervisor: Arc<Supervisor>,
+    // Taken and spawned with the first queued call, so caches without
+    // hooks never start the thread
+    worker: Mutex<Option<Worker>>,
+}
+
+impl HookDispatcher {
+    // Hook exceptions never reach the cache operation that triggered them;
+    // they are counted and handed to `on_hook_error` instead.
+    pub(crate) fn start(
+        supervisor: &Arc<Supervisor>,
+        capacity: usize,
+        on_hook_error: Option<Py<PyAny>>,
+        disable_after: Option<u32>,
+    ) -> Self {
+        let (sender, receiver) = sync_channel::<HookCall>(capacity);
+        let receiver = Mutex::new(receiver);
+        let stats = Arc::new(HookStats::default());
+        let worker_stats = stats.clone();
+        let worker = move |shutdown: &Shutdown| loop {
+            let receiver = receiver.lock().expect("Unable to lock hook queue!");
+            let Some(batch) = next_batch(&receiver, shutdown) else {
+                return;
+            };
+            drop(receiver);
+            worker_stats
+                .queued
+                .fetch_sub(batch.len(), Ordering::Relaxed);
+            Python::with_gil(|py| {
+                for call in batch {
+                    let result = call_hook(py, &call.hook, call.args.bind(py), call.meta.as_ref());
+                    let mut health = worker_stats
+                        .health
+                        .lock()
+                        .expect("Unable to lock hook health!");
+                    let Err(err) = result else {
+                        health.succeeded(call.name);
+                        worker_stats.dispatched.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    };
+                    worker_stats.failed.fetch_add(1, Ordering::Relaxed);
+                    let disabled = health.failed(call.name, disable_after);
+                    drop(health);
+                    if disabled {
+                        py_log::log(
+                            py_log::WARNING,
+                            &format!(
+                                "Hook '{}' disabled after {} consecutive failures",
+                                call.name,
+                                disable_after.unwrap_or_default()
+                            ),
+                        );
+                    }
+                    if let Some(on_hook_error) = &on_hook_error {
+                        let _ = on_hook_error.call1(py, (call.name, err.value(py)));
+                    }
+                }
+            });
+        };
+        Self {
+            sender,
+            capacity,
+            stats,
+            supervisor: supervisor.clone(),
+            worker: Mutex::new(Some(Box::new(worker))),
+        }
+    }
+
+    pub(crate) fn dispatch<'py, A>(
+        &self,
+        py: Python<'py>,
+        name: &'static str,
+        hook: &Py<PyAny>,
+        args: A,
+        meta: Option<&Py<PyAny>>,
+    ) where
+        A: IntoPyObject<'py, Target = PyTuple>,
+    {
+        let health = self
+            .stats
+            .health
+            .lock()
+            .expect("Unable to lock hook health!");
+        if health.disabled.contains(name) {
+            return;
+        }
+        drop(health);
+        let Ok(args) = args.into_pyobject(py) else {
+            self.stats.failed.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let call = HookCall {
+            name,
+            hook: hook.clone_ref(py),
+            args: args.into_bound().unbind(),
+            meta: meta.map(|meta| meta.clone_ref(py)),
+        };
+        let worker = self
+            .worker
+            .lock()
+            .expect("Unable to lock hook worker!")
+            .take();
+        if let Some(worker) = worker {
+            self.supervisor.spawn("hooks", worker);
+        }
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        if self.sender.try_send(call).is_err() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+            self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Failed hook calls against all hook calls that ran
+    pub(crate) fn calls(&self) -> (u64, u64) {
+        let failed = self.stats.failed.load(Ordering::Relaxed);
+        (
+            failed,
+            failed + self.stats.dispatched.load(Ordering::Relaxed),
+        )
+    }
+
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = PyDict::new(py);
+        stats.set_item("capacity", self.capacity)?;
+        stats.set_item("queued", self.stats.queued.load(Ordering::Relaxed))?;
+        stats.set_item("dispatched", self.stats.dispatched.load(Ordering::Relaxed))?;
+        stats.set_item("dropped", self.stats.dropped.load(Ordering::Relaxed))?;
+        stats.set_item("failed", self.stats.failed.load(Ordering::Relaxed))?;
+        let health = self
+            .stats
+            .health
+            .lock()
+            .expect("Unable to lock hook health!");
+        stats.set_item("disabled", health.disabled.iter().collect::<Vec<_>>())?;
+        Ok(stats)
+    }
+}
+
+// Blocks for the first call, then takes whatever else is already queued.
+// Once a shutdown was requested the queue is drained before giving up.
+fn next_batch<T>(receiver: &Receiver<T>, shutdown: &Shutdown) -> Option<Vec<T>> {
+    let first = loop {
+        match receiver.recv_timeout(STOP_POLL_INTERVAL) {
+            Ok(first) => break first,
+            Err(RecvTimeoutError::Timeout) if !shutdown.requested() => continue,
+            Err(_) => return None,
+        }
+    };
+    let mut batch = vec![first];
+    batch.extend(receiver.try_iter().take(MAX_BATCH - 1));
+    Some(batch)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_batch() {
+        let (sender, receiver) = sync_channel(100);
+        for value in 0..70 {
+            sender.send(value).unwrap();
+        }
+        let shutdown = Shutdown::default();
+        assert_eq!(next_batch(&receiver, &shutdown).unwrap().len(), MAX_BATCH);
+        assert_eq!(
+            next_batch(&receiver, &shutdown).unwrap(),
+            [64, 65, 66, 67, 68, 69]
+        );
+        drop(sender);
+        assert!(next_batch(&receiver, &shutdown).is_none());
+
+        // A requested shutdown still hands out what is queued, then stops
+        let (sender, receiver) = sync_channel(100);
+        sender.send(1).unwrap();
+        shutdown.request();
+        assert_eq!(next_batch(&receiver, &shutdown).unwrap(), [1]);
+        assert!(next_batch(&receiver, &shutdown).is_none());
+    }
+
+    #[test]
+    fn test_hook_health() {
+        let mut health = HookHealth::default();
+        assert!(!health.failed("on_hit", Some(3)));
+        assert!(!health.failed("on_hit", Some(3)));
+        health.succeeded("on_hit");
+        assert!(!health.failed("on_hit", Some(3)));
+        assert!(!health.failed("on_hit", Some(3)));
+        assert!(health.failed("on_hit", Some(3)));
+        assert!(!health.failed("on_hit", Some(3)));
+        assert!(!health.failed("on_evict", None));
+        assert_eq!(health.disabled.iter().collect::<Vec<_>>(), [&"on_hit"]);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::{duration_from_secs, optional_secs};
+use crate::trace::key_hash;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// Deletes the leader key only if it still holds our token, so a lock that
+// expired and was taken over is left alone
+const REDIS_RELEASE: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+                             return redis.call('del', KEYS[1]) else return 0 end";
+
+// Leader electors decide, across processes, who computes a key after a
+// local miss. Any object with `acquire(key, timeout) -> bool` and
+// `release(key)` works; `timeout` is in seconds, None to wait indefinitely.
+
+// Polls `attempt` until it succeeds or `timeout` runs out, without holding
+// the GIL while sleeping
+fn poll(
+    py: Python<'_>,
+    timeout: Option<f64>,
+    interval: Duration,
+    mut attempt: impl FnMut() -> PyResult<bool>,
+) -> PyResult<bool> {
+    let deadline =
+        timeout.map(|timeout| Instant::now() + Duration::from_secs_f64(timeout.max(0.0)));
+    loop {
+        if attempt()? {
+            return Ok(true);
+        }
+        let wait = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) if !left.is_zero() => left.min(interval),
+                _ => return Ok(false),
+            },
+            None => interval,
+        };
+        py.allow_threads(|| thread::sleep(wait));
+    }
+}
+
+// Every process leads its own misses, as without an elector
+#[pyclass(frozen)]
+#[derive(Default)]
+pub struct LocalElector;
+
+#[pymethods]
+impl LocalElector {
+    #[new]
+    fn new() -> Self {
+        Self
+    }
+
+    #[pyo3(signature = (_key, _timeout=None))]
+    fn acquire(&self, _key: &str, _timeout: Option<f64>) -> bool {
+        true
+    }
+
+    fn release(&self, _key: &str) {}
+}
+
+// Leadership is a lock file in `directory`, created exclusively. Locks older
+// than `stale_after` seconds are assumed to belong to a dead process.
+#[pyclass(frozen)]
+pub struct FileLockElector {
+    directory: PathBuf,
+    stale_after: Option<Duration>,
+    interval: Duration,
+}
+
+impl FileLockElector {
+    fn lock_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}.lock", key_hash(key)))
+    }
+
+    fn try_lock(&self, path: &PathBuf) -> PyResult<bool> {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                // The pid only helps whoever inspects a leftover lock
+                let _ = write!(file, "{}", std::process::id());
+                Ok(true)
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                if self.is_stale(path) {
+                    let _ = fs::remove_file(path);
+                }
+                Ok(false)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn is_stale(&self, path: &PathBuf) -> bool {
+        let Some(stale_after) = self.stale_after else {
+            return false;
+        };
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > stale_after)
+    }
+}
+
+#[pymethods]
+impl FileLockElector {
+    #[new]
+    #[pyo3(signature = (directory, *, stale_after=None, poll_interval=0.05))]
+    fn new(directory: PathBuf, stale_after: Option<f64>, poll_interval: f64) -> PyResult<Self> {
+        let stale_after = optional_secs(stale_after)?;
+        let interval = duration_from_secs(poll_interval)?.max(MIN_POLL_INTERVAL);
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            stale_after,
+            interval,
+        })
+    }
+
+    #[pyo3(signature = (key, timeout=None))]
+    fn acquire(&self, py: Python<'_>, key: &str, timeout: Option<f64>) -> PyResult<bool> {
+        let path = self.lock_path(key);
+        poll(py, timeout, self.interval, || self.try_lock(&path))
+    }
+
+    fn release(&self, key: &str) -> PyResult<()> {
+        match fs::remove_file(self.lock_path(key)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Leadership is a Redis key set with NX and a `ttl` in seconds, so a
+// crashed leader cannot hold a key for longer than that. `client` is a
+// redis-py compatible client.
+#[pyclass(frozen)]
+pub struct RedisElector {
+    client: Py<PyAny>,
+    prefix: String,
+    ttl: Duration,
+    interval: Duration,
+    tokens: Mutex<HashMap<String, String>>,
+    next_token: AtomicU64,
+}
+
+#[pymethods]
+impl RedisElector {
+    #[new]
+    #[pyo3(signature = (client, *, prefix="rustflight:leader:".to_string(), ttl=30.0, poll_interval=0.05))]
+    fn new(client: Py<PyAny>, prefix: String, ttl: f64, poll_interval: f64) -> PyResult<Self> {
+        Ok(Self {
+            client,
+            prefix,
+            ttl: duration_from_secs(ttl)?.max(Duration::from_millis(1)),
+            interval: duration_from_secs(poll_interval)?.max(MIN_POLL_INTERVAL),
+            tokens: Mutex::new(HashMap::new()),
+            next_token: AtomicU64::new(0),
+        })
+    }
+
+    #[pyo3(signature = (key, timeout=None))]
+    fn acquire(&self, py: Python<'_>, key: &str, timeout: Option<f64>) -> PyResult<bool> {
+        let name = format!("{}{}", self.prefix, key);
+        let token = format!(
+            "{}:{}",
+            std::process::id(),
+            self.next_token.fetch_add(1, Ordering::Relaxed)
+        );
+        let client = self.client.bind(py);
+        let acquired = poll(py, timeout, self.interval, || {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("nx", true)?;
+            kwargs.set_item("px", self.ttl.as_millis() as u64)?;
+            client
+                .call_method("set", (&name, &token), Some(&kwargs))?
+                .is_truthy()
+        })?;
+        if acquired {
+            let mut tokens = self.tokens.lock().expect("Unable to lock elector!");
+            tokens.insert(key.to_string(), token);
+        }
+        Ok(acquired)
+    }
+
+    fn release(&self, py: Python<'_>, key: &str) -> PyResult<()> {
+        let token = self
+            .tokens
+            .lock()
+            .expect("Unable to lock elector!")
+            .remove(key);
+        let Some(token) = token else {
+            return Ok(());
+        };
+        let name = format!("{}{}", self.prefix, key);
+        self.client
+            .call_method1(py, "eval", (REDIS_RELEASE, 1, name, token))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_lock_elector() {
+        let directory = std::env::temp_dir().join(format!("rustflight-{}", std::process::id()));
+        let elector = FileLockElector::new(directory.clone(), None, 0.001).unwrap();
+        Python::with_gil(|py| {
+            assert!(elector.acquire(py, "user:1", Some(0.0)).unwrap());
+            assert!(!elector.acquire(py, "user:1", Some(0.01)).unwrap());
+            assert!(elector.acquire(py, "user:2", Some(0.0)).unwrap());
+            elector.release("user:1").unwrap();
+            assert!(elector.acquire(py, "user:1", Some(0.0)).unwrap());
+        });
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
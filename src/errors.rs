@@ -0,0 +1,9 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+
+create_exception!(rustflight, QuotaExceeded, PyException);
+create_exception!(rustflight, FunctionMismatch, PyException);
+create_exception!(rustflight, ComputeTimeout, PyException);
+create_exception!(rustflight, CacheDegraded, PyException);
+create_exception!(rustflight, LeaseHeld, PyException);
+create_exception!(rustflight, CacheClosed, PyException);
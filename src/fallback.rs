@@ -0,0 +1,122 @@
+use crate::config::duration_from_secs;
+use pyo3::prelude::*;
+use std::time::Duration;
+
+// Bounds how long a caller waits for a value: fresh if ready, otherwise the
+// stale value if one is stored, otherwise `default(key)`. The computation
+// keeps running in the background and fills the cache for later callers.
+#[pyclass(frozen)]
+pub struct FallbackPolicy {
+    #[pyo3(get)]
+    max_wait: f64,
+    #[pyo3(get)]
+    serve_stale: bool,
+    #[pyo3(get)]
+    default: Option<Py<PyAny>>,
+}
+
+#[pymethods]
+impl FallbackPolicy {
+    #[new]
+    #[pyo3(signature = (max_wait, *, serve_stale=true, default=None))]
+    fn new(max_wait: f64, serve_stale: bool, default: Option<Py<PyAny>>) -> PyResult<Self> {
+        duration_from_secs(max_wait)?;
+        Ok(Self {
+            max_wait,
+            serve_stale,
+            default,
+        })
+    }
+}
+
+impl FallbackPolicy {
+    pub(crate) fn max_wait(&self) -> Duration {
+        Duration::from_secs_f64(self.max_wait)
+    }
+
+    pub(crate) fn serve_stale(&self) -> bool {
+        self.serve_stale
+    }
+
+    pub(crate) fn default_provider(&self) -> Option<&Py<PyAny>> {
+        self.default.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::CacheConfig;
+    use crate::py_waiter::{CallOptions, PyCache};
+    use pyo3::ffi::c_str;
+    use pyo3::types::{PyDict, PyTuple};
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn test_fallback() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let default = py
+                .eval(c_str!("lambda key: 'default'"), None, None)
+                .unwrap();
+            let policy = FallbackPolicy::new(0.02, true, Some(default.unbind())).unwrap();
+            let cache = PyCache::with_config(CacheConfig {
+                fallback: Some(Py::new(py, policy).unwrap()),
+                call_workers: 1,
+                ..Default::default()
+            });
+            let cache = Py::new(py, cache).unwrap();
+            let globals = PyDict::new(py);
+            py.run(
+                c_str!("import time\ndef f():\n    time.sleep(0.1)\n    return 'fresh'"),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            let slow = globals.get_item("f").unwrap().unwrap().unbind();
+            let call = |key: &str| -> String {
+                PyCache::call_bounded(
+                    &cache,
+                    py,
+                    slow.clone_ref(py),
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    key,
+                    CallOptions::default(),
+                )
+                .unwrap()
+                .extract(py)
+                .unwrap()
+            };
+
+            // An expired value beats the default
+            globals.set_item("cache", &cache).unwrap();
+            py.run(
+                c_str!("cache.set('stale', 'stale', ttl=0.0)"),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            assert_eq!(call("stale"), "stale");
+            assert_eq!(call("missing"), "default");
+
+            // The computation carries on for later callers
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while cache.get().lookup(py, "missing").unwrap().is_none() {
+                assert!(Instant::now() < deadline, "fallback computation was lost");
+                py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+            }
+            assert_eq!(call("missing"), "fresh");
+            // Both calls shared the one pool thread
+            let stats = cache.get().stats(py).unwrap();
+            let threads: usize = stats
+                .get_item("call_threads")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(threads, 1);
+        });
+    }
+}
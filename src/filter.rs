@@ -0,0 +1,59 @@
+use crate::config::duration_from_secs;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::time::Duration;
+
+#[derive(Default)]
+pub(crate) struct EntryFilter {
+    prefix: Option<String>,
+    min_age: Option<Duration>,
+    max_age: Option<Duration>,
+    ready: Option<bool>,
+}
+
+impl EntryFilter {
+    pub(crate) fn from_spec(spec: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut filter = Self::default();
+        let Some(spec) = spec else {
+            return Ok(filter);
+        };
+
+        for (name, value) in spec.iter() {
+            let name: String = name.extract()?;
+            match name.as_str() {
+                "prefix" => filter.prefix = Some(value.extract()?),
+                "min_age" => filter.min_age = Some(duration_from_secs(value.extract()?)?),
+                "max_age" => filter.max_age = Some(duration_from_secs(value.extract()?)?),
+                "state" => {
+                    filter.ready = match value.extract::<String>()?.as_str() {
+                        "ready" => Some(true),
+                        "pending" => Some(false),
+                        state => {
+                            return Err(PyValueError::new_err(format!(
+                                "Unknown state '{}', expected 'ready' or 'pending'",
+                                state
+                            )))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                    "Unknown filter '{}', expected one of 'prefix', 'min_age', 'max_age', 'state'",
+                    name
+                )))
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    pub(crate) fn matches(&self, key: &str, ready: bool, age: Duration) -> bool {
+        self.prefix
+            .as_ref()
+            .is_none_or(|prefix| key.starts_with(prefix))
+            && self.min_age.is_none_or(|min_age| age >= min_age)
+            && self.max_age.is_none_or(|max_age| age <= max_age)
+            && self.ready.is_none_or(|expected| ready == expected)
+    }
+}
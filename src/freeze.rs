@@ -0,0 +1,76 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyFrozenSet, PyList, PySet, PyTuple};
+
+pub(crate) const MAX_FREEZE_DEPTH: usize = 32;
+
+// Converts lists, dicts and sets into their immutable counterparts. Nesting
+// deeper than `depth` is left untouched.
+pub(crate) fn freeze<'py>(value: &Bound<'py, PyAny>, depth: usize) -> PyResult<Bound<'py, PyAny>> {
+    if depth == 0 {
+        return Ok(value.clone());
+    }
+    let py = value.py();
+
+    if let Ok(list) = value.downcast::<PyList>() {
+        let items = list
+            .iter()
+            .map(|item| freeze(&item, depth - 1))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(PyTuple::new(py, items)?.into_any());
+    }
+    if let Ok(tuple) = value.downcast::<PyTuple>() {
+        let items = tuple
+            .iter()
+            .map(|item| freeze(&item, depth - 1))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(PyTuple::new(py, items)?.into_any());
+    }
+    if let Ok(dict) = value.downcast::<PyDict>() {
+        let frozen = PyDict::new(py);
+        for (key, item) in dict.iter() {
+            frozen.set_item(key, freeze(&item, depth - 1)?)?;
+        }
+        return py
+            .import("types")?
+            .getattr("MappingProxyType")?
+            .call1((frozen,));
+    }
+    if let Ok(set) = value.downcast::<PySet>() {
+        let items = set
+            .iter()
+            .map(|item| freeze(&item, depth - 1))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(PyFrozenSet::new(py, items)?.into_any());
+    }
+    Ok(value.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::ffi::c_str;
+
+    #[test]
+    fn test_freeze() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let value = py
+                .eval(c_str!("{'a': [1, {2}], 'b': ([3],)}"), None, None)
+                .unwrap();
+            let frozen = freeze(&value, MAX_FREEZE_DEPTH).unwrap();
+            let expected = py
+                .eval(
+                    c_str!("{'a': (1, frozenset({2})), 'b': ((3,),)}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert!(frozen.eq(&expected).unwrap());
+            assert!(frozen.set_item("c", 4).is_err());
+
+            // Past the depth limit values stay as they are
+            let shallow = freeze(&value, 1).unwrap();
+            assert!(shallow.get_item("a").unwrap().downcast::<PyList>().is_ok());
+        });
+    }
+}
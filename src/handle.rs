@@ -0,0 +1,177 @@
+use crate::config::optional_secs;
+use crate::errors::ComputeTimeout;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::PyType;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+static CANCELLED_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+#[derive(Default)]
+struct HandleState {
+    outcome: Option<PyResult<Py<PyAny>>>,
+    cancelled: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Completion {
+    state: Mutex<HandleState>,
+    done: Condvar,
+}
+
+impl Completion {
+    pub(crate) fn complete(&self, outcome: PyResult<Py<PyAny>>) {
+        let mut state = self.state.lock().expect("Unable to lock handle!");
+        state.outcome = Some(outcome);
+        self.done.notify_all();
+    }
+
+    // Returns false if the outcome was already in
+    pub(crate) fn cancel(&self) -> bool {
+        let mut state = self.state.lock().expect("Unable to lock handle!");
+        if state.outcome.is_some() {
+            return false;
+        }
+        state.cancelled = true;
+        self.done.notify_all();
+        true
+    }
+
+    // Blocks until the flight itself is over, even if the handle was
+    // cancelled; false if `deadline` passed first
+    pub(crate) fn wait_finished(&self, deadline: Option<Instant>) -> bool {
+        let state = self.state.lock().expect("Unable to lock handle!");
+        let running = |state: &mut HandleState| state.outcome.is_none();
+        match deadline {
+            Some(deadline) => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                let (state, _) = self
+                    .done
+                    .wait_timeout_while(state, wait, running)
+                    .expect("Unable to lock handle!");
+                state.outcome.is_some()
+            }
+            None => {
+                drop(self.done.wait_while(state, running));
+                true
+            }
+        }
+    }
+}
+
+// Returned by `py_call_nowait`; the flight runs on the cache's call pool
+// and the handle collects its outcome, much like a
+// concurrent.futures.Future.
+#[pyclass(frozen)]
+pub struct FlightHandle {
+    #[pyo3(get)]
+    key: String,
+    completion: Arc<Completion>,
+}
+
+impl FlightHandle {
+    pub(crate) fn new(key: String, completion: Arc<Completion>) -> Self {
+        Self { key, completion }
+    }
+}
+
+#[pymethods]
+impl FlightHandle {
+    fn done(&self) -> bool {
+        let state = self
+            .completion
+            .state
+            .lock()
+            .expect("Unable to lock handle!");
+        state.outcome.is_some() || state.cancelled
+    }
+
+    fn cancelled(&self) -> bool {
+        let state = self
+            .completion
+            .state
+            .lock()
+            .expect("Unable to lock handle!");
+        state.cancelled
+    }
+
+    // Stops waiting on the flight, which keeps running for its other callers.
+    // Returns false if the outcome was already in.
+    fn cancel(&self) -> bool {
+        self.completion.cancel()
+    }
+
+    // `timeout` in seconds, waiting indefinitely without one
+    #[pyo3(signature = (timeout=None))]
+    fn result(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Py<PyAny>> {
+        let timeout = optional_secs(timeout)?;
+        let completion = &self.completion;
+        py.allow_threads(|| {
+            let state = completion.state.lock().expect("Unable to lock handle!");
+            let pending = |state: &mut HandleState| state.outcome.is_none() && !state.cancelled;
+            match timeout {
+                Some(timeout) => {
+                    drop(completion.done.wait_timeout_while(state, timeout, pending));
+                }
+                None => drop(completion.done.wait_while(state, pending)),
+            }
+        });
+        let state = completion.state.lock().expect("Unable to lock handle!");
+        if state.cancelled {
+            drop(state);
+            let cancelled = CANCELLED_ERROR.import(py, "concurrent.futures", "CancelledError")?;
+            return Err(PyErr::from_type(cancelled.clone(), self.key.clone()));
+        }
+        match &state.outcome {
+            Some(Ok(value)) => Ok(value.clone_ref(py)),
+            Some(Err(err)) => Err(err.clone_ref(py)),
+            None => Err(ComputeTimeout::new_err(format!(
+                "No result for cache entry '{}' within {:?}",
+                self.key,
+                timeout.unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::exceptions::PyValueError;
+
+    #[test]
+    fn test_handle() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let handle = FlightHandle::new("test".to_string(), Arc::default());
+            assert!(!handle.done());
+            let err = handle.result(py, Some(0.01)).unwrap_err();
+            assert!(err.is_instance_of::<ComputeTimeout>(py));
+
+            let value = 42i64.into_pyobject(py).unwrap().into_any().unbind();
+            handle.completion.complete(Ok(value));
+            assert!(handle.done());
+            let value = handle.result(py, None).unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 42);
+            // Too late to cancel once the outcome is in
+            assert!(!handle.cancel());
+
+            let failed = FlightHandle::new("failed".to_string(), Arc::default());
+            failed
+                .completion
+                .complete(Err(PyValueError::new_err("boom")));
+            let err = failed.result(py, None).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+
+            let cancelled = FlightHandle::new("cancelled".to_string(), Arc::default());
+            assert!(cancelled.cancel());
+            assert!(cancelled.cancelled());
+            let err = cancelled.result(py, None).unwrap_err();
+            let name = err.get_type(py).name().unwrap();
+            assert_eq!(name.to_string(), "CancelledError");
+            // The flight itself is still awaited by whoever joins it
+            assert!(!cancelled.completion.wait_finished(Some(Instant::now())));
+        });
+    }
+}
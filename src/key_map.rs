@@ -0,0 +1,307 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::{LockResult, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// `*` matches any run of characters, `?` any single one
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(b'?') => {
+                p += 1;
+                t += utf8_width(text[t]);
+            }
+            Some(&byte) if byte == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    p = star;
+                    t = tried + utf8_width(text[tried]);
+                    backtrack = Some((star, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
+
+fn utf8_width(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+
+// One shard's keys, stored whole: a lookup is a single hash of the key.
+pub(crate) struct KeyMap<V> {
+    entries: HashMap<Box<str>, V>,
+}
+
+impl<V> Default for KeyMap<V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<V> KeyMap<V> {
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&V> {
+        self.entries.get(key)
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, value: V) -> Option<V> {
+        self.entries.insert(key.into(), value)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<V> {
+        self.entries.remove(key)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.entries.iter().map(|(key, value)| (&**key, value))
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values()
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut keep: F) -> Vec<V>
+    where
+        F: FnMut(&str, &V) -> bool,
+    {
+        let removed: Vec<Box<str>> = self
+            .entries
+            .iter()
+            .filter(|(key, value)| !keep(key, value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        removed
+            .iter()
+            .filter_map(|key| self.entries.remove(key))
+            .collect()
+    }
+
+    // Bytes of key text held by the map
+    pub(crate) fn key_bytes(&self) -> usize {
+        self.entries.keys().map(|key| key.len()).sum()
+    }
+}
+
+// Spreads keys over independently locked shards by hash, so threads working
+// on different keys do not contend. Lookups share a shard, so hits on the same
+// key do not serialize. Whole-map operations lock every shard, always in
+// index order; a thread holding one shard must not take them all.
+pub(crate) struct ShardedKeyMap<V> {
+    shards: Box<[RwLock<KeyMap<V>>]>,
+    hasher: RandomState,
+}
+
+impl<V> ShardedKeyMap<V> {
+    pub(crate) fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| RwLock::new(KeyMap::default()))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub(crate) fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self, key: &str) -> &RwLock<KeyMap<V>> {
+        &self.shards[self.hasher.hash_one(key) as usize % self.shards.len()]
+    }
+
+    // The shard holding `key`, for lookups only
+    pub(crate) fn read(&self, key: &str) -> LockResult<RwLockReadGuard<'_, KeyMap<V>>> {
+        self.shard(key).read()
+    }
+
+    // The shard holding `key`
+    pub(crate) fn lock(&self, key: &str) -> LockResult<RwLockWriteGuard<'_, KeyMap<V>>> {
+        self.shard(key).write()
+    }
+
+    pub(crate) fn lock_all(&self) -> LockResult<AllShards<'_, V>> {
+        let mut poisoned = false;
+        let guards = self
+            .shards
+            .iter()
+            .map(|shard| {
+                shard.write().unwrap_or_else(|err| {
+                    poisoned = true;
+                    err.into_inner()
+                })
+            })
+            .collect();
+        let all = AllShards {
+            guards,
+            hasher: &self.hasher,
+        };
+        match poisoned {
+            true => Err(PoisonError::new(all)),
+            false => Ok(all),
+        }
+    }
+}
+
+// Every shard of a `ShardedKeyMap`, locked
+pub(crate) struct AllShards<'a, V> {
+    guards: Vec<RwLockWriteGuard<'a, KeyMap<V>>>,
+    hasher: &'a RandomState,
+}
+
+impl<V> AllShards<'_, V> {
+    fn shard(&self, key: &str) -> usize {
+        self.hasher.hash_one(key) as usize % self.guards.len()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.guards.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&V> {
+        self.guards[self.shard(key)].get(key)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<V> {
+        let index = self.shard(key);
+        self.guards[index].remove(key)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.guards.iter().flat_map(|shard| shard.iter())
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.guards.iter().flat_map(|shard| shard.values())
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut keep: F) -> Vec<V>
+    where
+        F: FnMut(&str, &V) -> bool,
+    {
+        let mut removed = Vec::new();
+        for shard in &mut self.guards {
+            removed.extend(shard.retain(&mut keep));
+        }
+        removed
+    }
+
+    pub(crate) fn key_bytes(&self) -> usize {
+        self.guards.iter().map(|shard| shard.key_bytes()).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_map() {
+        let mut map = KeyMap::default();
+        map.insert("user:42:profile", 1);
+        map.insert("user:42:settings", 2);
+        map.insert("plain", 3);
+        assert_eq!(map.insert("user:42:profile", 4), Some(1));
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.get("user:42:profile"), Some(&4));
+        assert_eq!(map.get("plain"), Some(&3));
+        assert_eq!(map.get("user:42:"), None);
+
+        let mut keys: Vec<String> = map.iter().map(|(key, _)| key.to_string()).collect();
+        keys.sort();
+        assert_eq!(keys, ["plain", "user:42:profile", "user:42:settings"]);
+        assert_eq!(
+            map.iter()
+                .filter(|(key, _)| key.starts_with("user:4"))
+                .count(),
+            2
+        );
+
+        assert_eq!(map.key_bytes(), 15 + 16 + 5);
+
+        assert_eq!(map.remove("user:42:profile"), Some(4));
+        assert_eq!(map.remove("user:42:profile"), None);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut map = KeyMap::default();
+        for (index, key) in [
+            "user:42:profile",
+            "user:42:settings",
+            "user:420:profile",
+            "user:7:profile",
+        ]
+        .iter()
+        .enumerate()
+        {
+            map.insert(key, index);
+        }
+        let mut remove_prefix = |prefix: &str| map.retain(|key, _| !key.starts_with(prefix)).len();
+        assert_eq!(remove_prefix("user:42:"), 2);
+        assert_eq!(remove_prefix("user:4"), 1);
+        assert_eq!(remove_prefix("order:"), 0);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get("user:7:profile"), Some(&3));
+    }
+
+    #[test]
+    fn test_sharded_key_map() {
+        let map = ShardedKeyMap::new(8);
+        for (index, key) in ["user:1", "user:2", "order:1", "plain"].iter().enumerate() {
+            map.lock(key).unwrap().insert(key, index);
+        }
+        assert_eq!(map.lock("user:2").unwrap().get("user:2"), Some(&1));
+
+        // Readers of a shard do not exclude each other
+        let first = map.read("user:1").unwrap();
+        let second = map.read("user:1").unwrap();
+        assert_eq!(first.get("user:1"), second.get("user:1"));
+        drop((first, second));
+
+        let mut all = map.lock_all().unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all.get("order:1"), Some(&2));
+        assert_eq!(all.retain(|key, _| !key.starts_with("user:")).len(), 2);
+        assert_eq!(all.retain(|key, _| key.starts_with("order")).len(), 1);
+        assert_eq!(all.iter().count(), 1);
+        drop(all);
+        assert_eq!(map.lock("order:1").unwrap().get("order:1"), Some(&2));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("user:*:profile", "user:42:profile"));
+        assert!(glob_match("user:*:profile", "user::profile"));
+        assert!(!glob_match("user:*:profile", "user:42:settings"));
+        assert!(glob_match("user:4?:*", "user:42:profile"));
+        assert!(glob_match("user:?:*", "user:ä:x"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*:a*b", "x:ab:aXb"));
+        assert!(!glob_match("user:?", "user:"));
+    }
+}
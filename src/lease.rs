@@ -0,0 +1,174 @@
+use crate::config::duration_from_secs;
+use crate::errors::LeaseHeld;
+use crate::py_waiter::PyCache;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Exclusive write rights on keys. A lease that is not renewed in time simply
+// stops counting, so a holder that died cannot block a key for good.
+#[derive(Default)]
+pub(crate) struct LeaseTable {
+    leases: Mutex<HashMap<String, (u64, Instant)>>,
+    next_id: AtomicU64,
+}
+
+impl LeaseTable {
+    // Returns the new lease id, or None while someone else holds `key`
+    pub(crate) fn acquire(&self, key: &str, ttl: Duration) -> Option<u64> {
+        let now = Instant::now();
+        let mut leases = self.leases.lock().expect("Unable to lock leases!");
+        if leases
+            .get(key)
+            .is_some_and(|(_, expires_at)| *expires_at > now)
+        {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        leases.insert(key.to_string(), (id, now + ttl));
+        Some(id)
+    }
+
+    // Fails once the lease expired, even if nobody else took the key since
+    pub(crate) fn renew(&self, key: &str, id: u64, ttl: Duration) -> bool {
+        let now = Instant::now();
+        let mut leases = self.leases.lock().expect("Unable to lock leases!");
+        match leases.get_mut(key) {
+            Some((holder, expires_at)) if *holder == id && *expires_at > now => {
+                *expires_at = now + ttl;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn release(&self, key: &str, id: u64) -> bool {
+        let mut leases = self.leases.lock().expect("Unable to lock leases!");
+        let held = leases.get(key).is_some_and(|(holder, _)| *holder == id);
+        if held {
+            leases.remove(key);
+        }
+        held
+    }
+
+    pub(crate) fn holds(&self, key: &str, id: u64) -> bool {
+        let leases = self.leases.lock().expect("Unable to lock leases!");
+        leases
+            .get(key)
+            .is_some_and(|(holder, expires_at)| *holder == id && *expires_at > Instant::now())
+    }
+
+    // Whether `holder` (None for writers without a lease) may write `key`
+    pub(crate) fn allows(&self, key: &str, holder: Option<u64>) -> bool {
+        let leases = self.leases.lock().expect("Unable to lock leases!");
+        match leases.get(key) {
+            Some((id, expires_at)) if *expires_at > Instant::now() => holder == Some(*id),
+            _ => true,
+        }
+    }
+
+    pub(crate) fn check(&self, key: &str, holder: Option<u64>) -> PyResult<()> {
+        if self.allows(key, holder) {
+            return Ok(());
+        }
+        Err(LeaseHeld::new_err(format!(
+            "Cache entry '{}' is leased to another writer",
+            key
+        )))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        let now = Instant::now();
+        let leases = self.leases.lock().expect("Unable to lock leases!");
+        leases
+            .values()
+            .filter(|(_, expires_at)| *expires_at > now)
+            .count()
+    }
+}
+
+// Returned by `PyCache.lease(key, ttl)`. Usable as a context manager that
+// releases the lease on exit.
+#[pyclass(frozen)]
+pub struct Lease {
+    cache: Py<PyCache>,
+    #[pyo3(get)]
+    key: String,
+    id: u64,
+}
+
+impl Lease {
+    pub(crate) fn new(cache: Py<PyCache>, key: String, id: u64) -> Self {
+        Self { cache, key, id }
+    }
+}
+
+#[pymethods]
+impl Lease {
+    #[getter]
+    fn held(&self) -> bool {
+        self.cache.get().leases().holds(&self.key, self.id)
+    }
+
+    // Writes under the lease; fails with LeaseHeld once it has expired and
+    // someone else took the key
+    fn set(&self, py: Python<'_>, value: Py<PyAny>) -> PyResult<bool> {
+        self.cache.get().store_leased(py, &self.key, value, self.id)
+    }
+
+    // `ttl` in seconds, counted from now
+    fn renew(&self, ttl: f64) -> PyResult<bool> {
+        let ttl = duration_from_secs(ttl)?;
+        Ok(self.cache.get().leases().renew(&self.key, self.id, ttl))
+    }
+
+    fn release(&self) -> bool {
+        self.cache.get().leases().release(&self.key, self.id)
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.release();
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lease_table() {
+        let leases = LeaseTable::default();
+        let ttl = Duration::from_secs(60);
+
+        let id = leases.acquire("user:1", ttl).unwrap();
+        assert!(leases.acquire("user:1", ttl).is_none());
+        assert!(leases.allows("user:1", Some(id)));
+        assert!(!leases.allows("user:1", None));
+        assert!(leases.allows("user:2", None));
+        assert!(leases.renew("user:1", id, ttl));
+        assert!(leases.holds("user:1", id));
+
+        assert!(leases.release("user:1", id));
+        assert!(!leases.release("user:1", id));
+        assert!(leases.allows("user:1", None));
+
+        // An expired lease no longer blocks anyone and cannot be renewed
+        let expired = leases.acquire("user:1", Duration::ZERO).unwrap();
+        assert!(leases.allows("user:1", None));
+        assert!(!leases.renew("user:1", expired, ttl));
+        assert!(leases.acquire("user:1", ttl).is_some());
+        assert_eq!(leases.len(), 1);
+    }
+}
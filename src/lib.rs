@@ -1,10 +1,89 @@
+mod auto_key;
+mod awaitable;
+mod bench;
+mod cancel;
+mod canonical;
+mod config;
+mod context;
+mod decorator;
+mod default_cache;
+mod dispatch;
+mod election;
+mod errors;
+mod fallback;
+mod filter;
+mod fork;
+mod freeze;
+mod handle;
+mod key_map;
+mod lease;
+mod memory;
+mod mismatch;
+mod overlay;
+mod pool;
+mod popularity;
+mod py_log;
 mod py_waiter;
+mod refresh;
+mod removals;
+mod schedule;
+mod scope;
+mod simulate;
+mod snapshot;
+mod stats;
+mod stripe;
+mod supervisor;
+mod tenant;
+mod threads;
+mod timed;
+mod trace;
 
+use cancel::CancelToken;
+use context::FlightContext;
+use decorator::{FlightDecorator, FlightFunction};
+use election::{FileLockElector, LocalElector, RedisElector};
+use fallback::FallbackPolicy;
+use handle::FlightHandle;
+use lease::Lease;
+use overlay::CacheOverlay;
 use py_waiter::PyCache;
 use pyo3::prelude::*;
+use scope::FlightScope;
+use tenant::TenantView;
 
 #[pymodule]
 fn rustflight(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyCache>()?;
+    m.add_class::<CancelToken>()?;
+    m.add_class::<FlightContext>()?;
+    m.add_class::<TenantView>()?;
+    m.add_class::<CacheOverlay>()?;
+    m.add_class::<FallbackPolicy>()?;
+    m.add_class::<FlightDecorator>()?;
+    m.add_class::<FlightFunction>()?;
+    m.add_class::<Lease>()?;
+    m.add_class::<FlightHandle>()?;
+    m.add_class::<FlightScope>()?;
+    m.add_class::<LocalElector>()?;
+    m.add_class::<FileLockElector>()?;
+    m.add_class::<RedisElector>()?;
+    m.add("QuotaExceeded", m.py().get_type::<errors::QuotaExceeded>())?;
+    m.add(
+        "ComputeTimeout",
+        m.py().get_type::<errors::ComputeTimeout>(),
+    )?;
+    m.add("CacheDegraded", m.py().get_type::<errors::CacheDegraded>())?;
+    m.add("LeaseHeld", m.py().get_type::<errors::LeaseHeld>())?;
+    m.add("CacheClosed", m.py().get_type::<errors::CacheClosed>())?;
+    m.add(
+        "FunctionMismatch",
+        m.py().get_type::<errors::FunctionMismatch>(),
+    )?;
+    m.add_function(wrap_pyfunction!(simulate::simulate, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot::warmup_order, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot::migrate_snapshot, m)?)?;
+    default_cache::register(m)?;
+    bench::register(m)?;
+    fork::register(m)?;
     Ok(())
 }
@@ -0,0 +1,72 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+// Above the soft limit a background thread trims least recently used
+// entries; only above the hard limit does the caller's thread evict.
+#[derive(Default)]
+pub(crate) struct MemoryBudget {
+    used: AtomicUsize,
+    pub(crate) soft_limit: Option<usize>,
+    pub(crate) hard_limit: Option<usize>,
+    pub(crate) background_evictions: AtomicU64,
+    pub(crate) sync_evictions: AtomicU64,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(soft_limit: Option<usize>, hard_limit: Option<usize>) -> Self {
+        Self {
+            soft_limit,
+            hard_limit,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn add(&self, weight: usize) {
+        self.used.fetch_add(weight, Ordering::Relaxed);
+    }
+
+    pub(crate) fn sub(&self, weight: usize) {
+        self.used.fetch_sub(weight, Ordering::Relaxed);
+    }
+
+    pub(crate) fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn over_soft(&self) -> bool {
+        self.soft_limit.is_some_and(|limit| self.used() > limit)
+    }
+
+    pub(crate) fn over_hard(&self) -> bool {
+        self.hard_limit.is_some_and(|limit| self.used() > limit)
+    }
+
+    pub(crate) fn pressure(&self) -> &'static str {
+        if self.over_hard() {
+            "hard"
+        } else if self.over_soft() {
+            "soft"
+        } else {
+            "none"
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pressure() {
+        let budget = MemoryBudget::new(Some(100), Some(200));
+        budget.add(100);
+        assert_eq!(budget.pressure(), "none");
+        budget.add(50);
+        assert_eq!(budget.pressure(), "soft");
+        budget.add(51);
+        assert_eq!(budget.pressure(), "hard");
+        budget.sub(201);
+        assert_eq!(budget.used(), 0);
+
+        assert_eq!(MemoryBudget::new(None, None).pressure(), "none");
+    }
+}
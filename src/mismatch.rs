@@ -0,0 +1,50 @@
+use crate::trace::key_hash;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum MismatchPolicy {
+    Warn,
+    Raise,
+    Partition,
+}
+
+impl MismatchPolicy {
+    pub(crate) fn parse(policy: &str) -> PyResult<Self> {
+        match policy {
+            "warn" => Ok(MismatchPolicy::Warn),
+            "raise" => Ok(MismatchPolicy::Raise),
+            "partition" => Ok(MismatchPolicy::Partition),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown function mismatch policy '{}', expected 'warn', 'raise' or 'partition'",
+                policy
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            MismatchPolicy::Warn => "warn",
+            MismatchPolicy::Raise => "raise",
+            MismatchPolicy::Partition => "partition",
+        }
+    }
+}
+
+// Module and qualified name rather than id(), so a function keeps its
+// identity across reloads and worker processes.
+pub(crate) fn func_fingerprint(func: &Bound<'_, PyAny>) -> u64 {
+    key_hash(func_identity(func))
+}
+
+pub(crate) fn func_identity(func: &Bound<'_, PyAny>) -> String {
+    let name = |attr: &str| {
+        func.getattr(attr)
+            .and_then(|name| name.extract::<String>())
+            .ok()
+    };
+    match (name("__module__"), name("__qualname__")) {
+        (Some(module), Some(qualname)) => format!("{}.{}", module, qualname),
+        _ => func.repr().map(|repr| repr.to_string()).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::py_waiter::PyCache;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Request-scoped layer over a shared cache: reads fall through to the
+// parent, while anything computed or set here stays local until promoted.
+#[pyclass(frozen)]
+pub struct CacheOverlay {
+    cache: Py<PyCache>,
+    writes: Mutex<HashMap<String, Py<PyAny>>>,
+    promote_on_exit: bool,
+}
+
+impl CacheOverlay {
+    pub(crate) fn new(cache: Py<PyCache>, promote_on_exit: bool) -> Self {
+        Self {
+            cache,
+            writes: Mutex::new(HashMap::new()),
+            promote_on_exit,
+        }
+    }
+
+    fn local(&self, py: Python<'_>, key: &str) -> Option<Py<PyAny>> {
+        let writes = self.writes.lock().expect("Unable to lock overlay!");
+        writes.get(key).map(|value| value.clone_ref(py))
+    }
+}
+
+#[pymethods]
+impl CacheOverlay {
+    fn py_call(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+    ) -> PyResult<Py<PyAny>> {
+        if let Some(value) = self.local(py, &key) {
+            return Ok(value);
+        }
+        let cache = self.cache.get();
+        if let Some(value) = cache.lookup(py, &key)? {
+            return Ok(value);
+        }
+        let args: &Bound<'_, PyTuple> = args.downcast_bound(py)?;
+        let kwargs: &Bound<'_, PyDict> = kwargs.downcast_bound(py)?;
+        let value = py_func.call(py, args, Some(kwargs))?;
+        let value = cache.await_result(py, value)?;
+        let value = cache.post_process(py, value, None, None)?;
+        self.set(key, value.clone_ref(py));
+        Ok(value)
+    }
+
+    fn set(&self, key: String, value: Py<PyAny>) {
+        let mut writes = self.writes.lock().expect("Unable to lock overlay!");
+        writes.insert(key, value);
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        default: Option<Py<PyAny>>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        if let Some(value) = self.local(py, key) {
+            return Ok(Some(value));
+        }
+        Ok(self.cache.get().lookup(py, key)?.or(default))
+    }
+
+    // Returns how many local values made it into the shared cache; keys
+    // being computed there right now are left alone.
+    fn promote(&self, py: Python<'_>) -> PyResult<usize> {
+        let writes = std::mem::take(&mut *self.writes.lock().expect("Unable to lock overlay!"));
+        let cache = self.cache.get();
+        let mut promoted = 0;
+        for (key, value) in writes {
+            if cache.store(py, &key, value, None)? {
+                promoted += 1;
+            }
+        }
+        Ok(promoted)
+    }
+
+    fn discard(&self) -> usize {
+        let mut writes = self.writes.lock().expect("Unable to lock overlay!");
+        let discarded = writes.len();
+        writes.clear();
+        discarded
+    }
+
+    fn __len__(&self) -> usize {
+        self.writes.lock().expect("Unable to lock overlay!").len()
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        exc_type: Option<Bound<'_, PyAny>>,
+        _exc_value: Option<Bound<'_, PyAny>>,
+        _traceback: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if self.promote_on_exit && exc_type.is_none() {
+            self.promote(py)?;
+        } else {
+            self.discard();
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::CacheConfig;
+    use pyo3::ffi::c_str;
+
+    fn int(py: Python<'_>, value: i64) -> Py<PyAny> {
+        value.into_pyobject(py).unwrap().into_any().unbind()
+    }
+
+    #[test]
+    fn test_overlay() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let cache = Py::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let shared = cache.get();
+            assert!(shared.store(py, "shared", int(py, 1), None).unwrap());
+
+            let overlay = CacheOverlay::new(cache.clone_ref(py), false);
+            let value = overlay.get(py, "shared", None).unwrap().unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 1);
+            overlay.set("local".to_string(), int(py, 2));
+            let func = py.eval(c_str!("lambda: 3"), None, None).unwrap();
+            let value = overlay
+                .py_call(
+                    py,
+                    func.unbind(),
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    "computed".to_string(),
+                )
+                .unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 3);
+
+            // Nothing reaches the shared cache before promotion
+            assert_eq!(overlay.__len__(), 2);
+            assert!(shared.lookup(py, "local").unwrap().is_none());
+            assert!(shared.lookup(py, "computed").unwrap().is_none());
+            assert_eq!(overlay.promote(py).unwrap(), 2);
+            assert!(shared.lookup(py, "computed").unwrap().is_some());
+
+            overlay.set("discarded".to_string(), int(py, 4));
+            assert_eq!(overlay.discard(), 1);
+            assert!(shared.lookup(py, "discarded").unwrap().is_none());
+        });
+    }
+}
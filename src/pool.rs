@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct PoolState {
+    jobs: VecDeque<Job>,
+    started: usize,
+    idle: usize,
+    stopping: bool,
+}
+
+#[derive(Default)]
+struct PoolInner {
+    state: Mutex<PoolState>,
+    queued: Condvar,
+}
+
+// Runs calls handed off the caller's thread, e.g. by `py_call_nowait`, on
+// at most `workers` threads; beyond that they queue up. Threads start on
+// demand and exit once the pool is stopped and the queue is empty.
+pub(crate) struct CallPool {
+    workers: usize,
+    inner: Arc<PoolInner>,
+}
+
+impl CallPool {
+    pub(crate) fn new(workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            inner: Arc::default(),
+        }
+    }
+
+    pub(crate) fn submit<F>(&self, job: F) -> io::Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.inner.state.lock().expect("Unable to lock pool!");
+        state.jobs.push_back(Box::new(job));
+        if state.jobs.len() <= state.idle || state.started >= self.workers {
+            self.inner.queued.notify_one();
+            return Ok(());
+        }
+        let inner = self.inner.clone();
+        let spawned = thread::Builder::new()
+            .name("rustflight-call".to_string())
+            .spawn(move || work(&inner));
+        match spawned {
+            Ok(_) => {
+                state.started += 1;
+                Ok(())
+            }
+            // Without a thread of its own the job could wait forever
+            Err(err) if state.started == 0 => {
+                state.jobs.pop_back();
+                Err(err)
+            }
+            Err(_) => Ok(()),
+        }
+    }
+
+    // Queued jobs still run, then the threads exit
+    pub(crate) fn stop(&self) {
+        self.inner
+            .state
+            .lock()
+            .expect("Unable to lock pool!")
+            .stopping = true;
+        self.inner.queued.notify_all();
+    }
+
+    // Threads currently running, busy or idle
+    pub(crate) fn started(&self) -> usize {
+        self.inner
+            .state
+            .lock()
+            .expect("Unable to lock pool!")
+            .started
+    }
+}
+
+impl Drop for CallPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn work(inner: &PoolInner) {
+    let mut state = inner.state.lock().expect("Unable to lock pool!");
+    loop {
+        if let Some(job) = state.jobs.pop_front() {
+            drop(state);
+            // A panicking job must not take a pool thread with it
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            state = inner.state.lock().expect("Unable to lock pool!");
+            continue;
+        }
+        if state.stopping {
+            state.started -= 1;
+            return;
+        }
+        state.idle += 1;
+        state = inner.queued.wait(state).expect("Unable to lock pool!");
+        state.idle -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_call_pool() {
+        let pool = CallPool::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..20 {
+            let (running, most, sender) = (running.clone(), most.clone(), sender.clone());
+            pool.submit(move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(2));
+                running.fetch_sub(1, Ordering::SeqCst);
+                sender.send(()).unwrap();
+            })
+            .unwrap();
+        }
+        for _ in 0..20 {
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        // Jobs queued up behind the two threads instead of getting their own
+        assert_eq!(pool.started(), 2);
+        assert!(most.load(Ordering::SeqCst) <= 2);
+
+        // A panicking job leaves the pool working
+        pool.submit(|| panic!("job failed")).unwrap();
+        let sender = sender.clone();
+        pool.submit(move || sender.send(()).unwrap()).unwrap();
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        pool.stop();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.started() > 0 {
+            assert!(Instant::now() < deadline, "pool threads did not exit");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::simulate::get_option;
+use crate::trace::key_hash;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+const DEPTH: usize = 4;
+pub(crate) const DEFAULT_WIDTH: usize = 4096;
+// Counters are halved after this many accesses per counter in a row, so old
+// traffic fades out the way TinyLFU ages its sketch
+const SAMPLE_FACTOR: u64 = 10;
+
+// Count-min sketch of key accesses. Estimates never undercount within the
+// current window; collisions can only overcount. The hash is stable across
+// processes, so a persisted sketch keeps its meaning after a restart.
+pub(crate) struct PopularitySketch {
+    width: usize,
+    counters: Box<[AtomicU32]>,
+    additions: AtomicU64,
+}
+
+impl PopularitySketch {
+    pub(crate) fn new(width: usize) -> Self {
+        let width = width.max(1);
+        Self {
+            width,
+            counters: (0..width * DEPTH).map(|_| AtomicU32::new(0)).collect(),
+            additions: AtomicU64::new(0),
+        }
+    }
+
+    fn slots(&self, key: &str) -> [usize; DEPTH] {
+        let hash = key_hash(key);
+        let step = hash.rotate_left(32) | 1;
+        let mut slots = [0; DEPTH];
+        for (row, slot) in slots.iter_mut().enumerate() {
+            let column = hash.wrapping_add(step.wrapping_mul(row as u64)) % self.width as u64;
+            *slot = row * self.width + column as usize;
+        }
+        slots
+    }
+
+    pub(crate) fn record(&self, key: &str) {
+        for slot in self.slots(key) {
+            let _ =
+                self.counters[slot].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    count.checked_add(1)
+                });
+        }
+        let additions = self.additions.fetch_add(1, Ordering::Relaxed) + 1;
+        if additions.is_multiple_of(self.width as u64 * SAMPLE_FACTOR) {
+            self.halve();
+        }
+    }
+
+    fn halve(&self) {
+        for counter in self.counters.iter() {
+            let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                Some(count / 2)
+            });
+        }
+    }
+
+    pub(crate) fn estimate(&self, key: &str) -> u32 {
+        self.slots(key)
+            .iter()
+            .map(|&slot| self.counters[slot].load(Ordering::Relaxed))
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let counters: Vec<u32> = self
+            .counters
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .collect();
+        let sketch = PyDict::new(py);
+        sketch.set_item("width", self.width)?;
+        sketch.set_item("depth", DEPTH)?;
+        sketch.set_item("additions", self.additions.load(Ordering::Relaxed))?;
+        sketch.set_item("counters", counters)?;
+        Ok(sketch)
+    }
+
+    pub(crate) fn from_dict(sketch: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let width: usize = get_option(sketch, "width")?.unwrap_or(DEFAULT_WIDTH);
+        let depth: usize = get_option(sketch, "depth")?.unwrap_or(DEPTH);
+        let counters: Vec<u32> = get_option(sketch, "counters")?.unwrap_or_default();
+        if depth != DEPTH || width == 0 || counters.len() != width * DEPTH {
+            return Err(PyValueError::new_err(format!(
+                "Popularity sketch of {} counters does not fit width {} and depth {}",
+                counters.len(),
+                width,
+                depth
+            )));
+        }
+        let restored = Self::new(width);
+        restored.load(&counters, get_option(sketch, "additions")?.unwrap_or(0));
+        Ok(restored)
+    }
+
+    // Takes over the counts of `other`, which must have the same width
+    pub(crate) fn restore(&self, other: &PopularitySketch) -> PyResult<()> {
+        if other.width != self.width {
+            return Err(PyValueError::new_err(format!(
+                "Popularity sketch width {} does not match this cache's {}",
+                other.width, self.width
+            )));
+        }
+        let counters: Vec<u32> = other
+            .counters
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .collect();
+        self.load(&counters, other.additions.load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    fn load(&self, counters: &[u32], additions: u64) {
+        for (counter, &count) in self.counters.iter().zip(counters) {
+            counter.store(count, Ordering::Relaxed);
+        }
+        self.additions.store(additions, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_popularity_sketch() {
+        let sketch = PopularitySketch::new(64);
+        for _ in 0..20 {
+            sketch.record("hot");
+        }
+        sketch.record("cold");
+        assert!(sketch.estimate("hot") >= 20);
+        assert!(sketch.estimate("cold") >= 1);
+        assert!(sketch.estimate("hot") > sketch.estimate("cold"));
+        assert_eq!(sketch.slots("hot"), sketch.slots("hot"));
+
+        let aged = PopularitySketch::new(64);
+        for _ in 0..20 {
+            aged.record("hot");
+        }
+        aged.halve();
+        assert_eq!(aged.estimate("hot"), 10);
+
+        let copy = PopularitySketch::new(64);
+        copy.restore(&sketch).unwrap();
+        assert_eq!(copy.estimate("hot"), sketch.estimate("hot"));
+        assert!(PopularitySketch::new(32).restore(&sketch).is_err());
+    }
+}
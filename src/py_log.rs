@@ -0,0 +1,13 @@
+use pyo3::prelude::*;
+
+pub(crate) const WARNING: u8 = 30;
+pub(crate) const ERROR: u8 = 40;
+
+pub(crate) fn log(level: u8, message: &str) {
+    Python::with_gil(|py| {
+        let _ = py
+            .import("logging")
+            .and_then(|logging| logging.call_method1("getLogger", ("rustflight",)))
+            .and_then(|logger| logger.call_method1("log", (level, message)));
+    })
+}
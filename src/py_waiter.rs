@@ -1,191 +1,6312 @@
+use crate::auto_key::derive_key;
+use crate::awaitable::await_result;
+use crate::cancel::CancelToken;
+use crate::canonical::Canonicalizer;
+use crate::config::{
+    duration_from_secs, optional_secs, timeout_from_secs, CacheConfig, FailurePolicy,
+    TimeoutPolicy, WakeStrategy, WritePolicy,
+};
+use crate::context::{accepts_flight_ctx, FlightContext};
+use crate::decorator::FlightDecorator;
+use crate::dispatch::HookDispatcher;
+use crate::errors::{
+    CacheClosed, CacheDegraded, ComputeTimeout, FunctionMismatch, LeaseHeld, QuotaExceeded,
+};
+use crate::fallback::FallbackPolicy;
+use crate::filter::EntryFilter;
+use crate::fork;
+use crate::freeze::MAX_FREEZE_DEPTH;
+use crate::handle::{Completion, FlightHandle};
+use crate::key_map::{glob_match, AllShards, KeyMap, ShardedKeyMap};
+use crate::lease::{Lease, LeaseTable};
+use crate::memory::MemoryBudget;
+use crate::mismatch::{func_fingerprint, MismatchPolicy};
+use crate::overlay::CacheOverlay;
+use crate::pool::CallPool;
+use crate::popularity::{PopularitySketch, DEFAULT_WIDTH};
+use crate::py_log;
+use crate::refresh::{RefreshJob, RefreshLane, RefreshOutcome, RefreshQueue};
+use crate::removals::RemovalLog;
+use crate::schedule::{CronSchedule, Scheduler};
+use crate::scope::FlightScope;
+use crate::simulate::get_option;
+use crate::snapshot::{migrate_snapshot, METADATA_FORMAT, METADATA_VERSION};
+use crate::stats::CacheStats;
+use crate::stripe::{LockStripe, LockStripes};
+use crate::supervisor::Supervisor;
+use crate::tenant::{tenant_prefix, QuotaPolicy, TenantState, TenantView};
+use crate::threads::ThreadSettings;
+use crate::timed::{bucket_key, parse_bucket};
+use crate::trace::{unix_now, AccessTrace, TraceKind};
+use pyo3::exceptions::{
+    PyKeyError, PyPermissionError, PyRuntimeError, PyTimeoutError, PyTypeError, PyValueError,
+};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyTuple};
-use std::collections::HashMap;
-use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use pyo3::types::{PyBytes, PyDict, PyInt, PyList, PyString, PyTuple, PyWeakrefReference};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, LazyLock, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often await_warm looks again at keys nobody is computing
+const WARM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const REFRESH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const ALARM_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const EVICT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const VALIDATE_KEY: &str = "__rustflight__:validate";
+// How long the value of `validate`'s expiry probe lives
+const VALIDATE_EXPIRY: Duration = Duration::from_millis(5);
+
+struct Loader {
+    func: Py<PyAny>,
+    args: Py<PyAny>,
+    kwargs: Py<PyAny>,
+}
 
 struct PyCacheEntry {
     value: Option<Py<PyAny>>,
     ready: bool,
+    created_at: Instant,
+    last_access: Instant,
+    expires_at: Option<Instant>,
+    token: Py<CancelToken>,
+    func_id: Option<u64>,
+    loader: Option<Loader>,
+    leader: Option<String>,
+    provenance: Option<String>,
+    alarmed: bool,
+    overrun: bool,
+    // Opened with `start` and resolved from outside the cache
+    external: bool,
+    // The leader failed and the next waiter to wake takes over
+    handoff: bool,
+    handoffs: u32,
+    error: Option<PyErr>,
+    tags: Option<Py<PyAny>>,
+    meta: Option<Py<PyAny>>,
+    tenant: Option<Arc<TenantState>>,
+    freeze: Option<bool>,
+    memory: Arc<MemoryBudget>,
+    weight: usize,
+    previous: VecDeque<(f64, Py<PyAny>)>,
+    hits: u64,
+    waiters: usize,
+    abandoned: usize,
 }
 
 impl PyCacheEntry {
-    fn pending() -> Self {
+    fn pending(token: Py<CancelToken>, options: CallOptions, memory: Arc<MemoryBudget>) -> Self {
+        if let Some(tenant) = &options.tenant {
+            tenant.entries.fetch_add(1, Ordering::Relaxed);
+        }
         Self {
             value: None,
             ready: false,
+            created_at: Instant::now(),
+            last_access: Instant::now(),
+            expires_at: options.expires_at,
+            token,
+            func_id: None,
+            loader: None,
+            leader: None,
+            provenance: options.provenance,
+            alarmed: false,
+            overrun: false,
+            external: false,
+            handoff: false,
+            handoffs: 0,
+            error: None,
+            tags: options.tags,
+            meta: options.meta,
+            tenant: options.tenant,
+            freeze: options.freeze,
+            memory,
+            weight: 0,
+            previous: VecDeque::new(),
+            hits: 0,
+            waiters: 0,
+            abandoned: 0,
         }
     }
 
-    fn ready(&mut self, new_value: Py<PyAny>) {
+    fn completed(
+        py: Python<'_>,
+        value: Py<PyAny>,
+        options: CallOptions,
+        memory: Arc<MemoryBudget>,
+    ) -> PyResult<Self> {
+        let token = Py::new(py, CancelToken::default())?;
+        let weight = size_of(py, &value);
+        let mut entry = Self::pending(token, options, memory);
+        entry.ready(value, weight);
+        Ok(entry)
+    }
+
+    fn served(&self) -> usize {
+        self.waiters.saturating_sub(self.abandoned)
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.ready && self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    // The returned value is the only reference Python needs; tags are only
+    // cloned when an access check will look at them
+    fn read(&mut self, py: Python<'_>, with_tags: bool) -> (Py<PyAny>, Option<Py<PyAny>>) {
+        let value = self.touch().clone_ref(py);
+        let tags = self
+            .tags
+            .as_ref()
+            .filter(|_| with_tags)
+            .map(|tags| tags.clone_ref(py));
+        (value, tags)
+    }
+
+    fn ready(&mut self, new_value: Py<PyAny>, weight: usize) {
         self.value = Some(new_value);
-        self.ready = true
+        self.ready = true;
+        self.weight = weight;
+        self.memory.add(weight);
+        if let Some(tenant) = &self.tenant {
+            tenant.memory.fetch_add(weight, Ordering::Relaxed);
+        }
+    }
+
+    fn refreshed(&mut self, new_value: Py<PyAny>, weight: usize, keep_history: usize) {
+        self.memory.sub(self.weight);
+        self.memory.add(weight);
+        if let Some(tenant) = &self.tenant {
+            tenant.memory.fetch_sub(self.weight, Ordering::Relaxed);
+            tenant.memory.fetch_add(weight, Ordering::Relaxed);
+        }
+        if let Some(old_value) = self.value.replace(new_value) {
+            if keep_history > 0 {
+                self.previous.push_front((self.updated_at(), old_value));
+                self.previous.truncate(keep_history);
+            }
+        }
+        self.weight = weight;
+        self.created_at = Instant::now();
+    }
+
+    fn updated_at(&self) -> f64 {
+        unix_now() - self.created_at.elapsed().as_secs_f64()
+    }
+
+    fn touch(&mut self) -> &Py<PyAny> {
+        self.last_access = Instant::now();
+        self.hits += 1;
+        self.value.as_ref().expect("None after read!")
+    }
+
+    // Newest first, starting with the current value
+    fn history(&self, py: Python<'_>) -> Vec<(f64, Py<PyAny>)> {
+        let current = self
+            .value
+            .as_ref()
+            .map(|value| (self.updated_at(), value.clone_ref(py)));
+        current
+            .into_iter()
+            .chain(
+                self.previous
+                    .iter()
+                    .map(|(updated_at, value)| (*updated_at, value.clone_ref(py))),
+            )
+            .collect()
+    }
+}
+
+impl Drop for PyCacheEntry {
+    fn drop(&mut self) {
+        self.memory.sub(self.weight);
+        if let Some(tenant) = &self.tenant {
+            tenant.entries.fetch_sub(1, Ordering::Relaxed);
+            tenant.memory.fetch_sub(self.weight, Ordering::Relaxed);
+        }
+    }
+}
+
+// How a call got its value, for `py_call_with_info`
+#[derive(Clone, Copy, Default)]
+pub(crate) struct CallInfo {
+    pub(crate) leader: bool,
+    // Waiters that received the flight's result; zero for plain hits
+    pub(crate) shared: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct CallOptions {
+    pub(crate) tags: Option<Py<PyAny>>,
+    pub(crate) context: Option<Py<PyAny>>,
+    pub(crate) meta: Option<Py<PyAny>>,
+    pub(crate) expires_at: Option<Instant>,
+    pub(crate) provenance: Option<String>,
+    pub(crate) tenant: Option<Arc<TenantState>>,
+    // Overrides the cache's `wait_timeout`; `Some(None)` waits indefinitely
+    pub(crate) wait_timeout: Option<Option<Duration>>,
+    // Overrides the cache's `freeze` for the value this call computes
+    pub(crate) freeze: Option<bool>,
+}
+
+// What the waiters of an entry block on: a condition variable of its own,
+// or with `lock_stripes` one shared with the other keys of its stripe
+enum FlightSignal {
+    Own(Condvar),
+    Striped(Arc<LockStripe>),
+}
+
+impl FlightSignal {
+    fn new(stripes: Option<&LockStripes>, key: &str) -> Self {
+        match stripes {
+            Some(stripes) => FlightSignal::Striped(stripes.get(key)),
+            None => FlightSignal::Own(Condvar::new()),
+        }
+    }
+
+    fn wake(&self, strategy: WakeStrategy) {
+        match self {
+            FlightSignal::Own(cvar) => strategy.wake(cvar),
+            FlightSignal::Striped(stripe) => stripe.wake(),
+        }
+    }
+
+    // Stripes only wake everyone, so there is nothing to pass on
+    fn pass_on(&self, strategy: WakeStrategy) {
+        if let FlightSignal::Own(cvar) = self {
+            strategy.pass_on(cvar);
+        }
+    }
+
+    fn wake_all(&self) {
+        self.wake(WakeStrategy::All);
     }
 }
 
 enum PyEntryState {
-    Pending(Arc<(Mutex<PyCacheEntry>, Condvar)>),
+    Pending(Arc<(Mutex<PyCacheEntry>, FlightSignal)>),
+}
+
+impl PyEntryState {
+    fn new(entry: PyCacheEntry, signal: FlightSignal) -> Self {
+        PyEntryState::Pending(Arc::new((Mutex::new(entry), signal)))
+    }
+
+    fn is_ready(&self) -> bool {
+        match self {
+            PyEntryState::Pending(lock_var) => lock_var.0.lock().unwrap().ready,
+        }
+    }
+
+    // Holds a value readers are served, i.e. ready and not expired unless
+    // stale values are served anyway while degraded
+    fn is_fresh(&self, now: Instant, degraded: bool) -> bool {
+        match self {
+            PyEntryState::Pending(lock_var) => {
+                let entry = lock_var.0.lock().unwrap();
+                entry.ready && (degraded || !entry.is_expired(now))
+            }
+        }
+    }
+
+    fn last_access(&self) -> Instant {
+        match self {
+            PyEntryState::Pending(lock_var) => lock_var.0.lock().unwrap().last_access,
+        }
+    }
+
+    // Neither a leader nor any waiter holds a reference anymore, so nobody
+    // will ever complete this entry.
+    fn is_orphaned(&self) -> bool {
+        match self {
+            PyEntryState::Pending(lock_var) => {
+                let entry = lock_var.0.lock().unwrap();
+                Arc::strong_count(lock_var) == 1 && !entry.ready && !entry.external
+            }
+        }
+    }
+
+    fn weight(&self) -> usize {
+        match self {
+            PyEntryState::Pending(lock_var) => lock_var.0.lock().unwrap().weight,
+        }
+    }
+
+    fn matches(&self, filter: &EntryFilter, key: &str, now: Instant) -> bool {
+        match self {
+            PyEntryState::Pending(lock_var) => {
+                let entry = lock_var.0.lock().unwrap();
+                filter.matches(
+                    key,
+                    entry.ready,
+                    now.saturating_duration_since(entry.created_at),
+                )
+            }
+        }
+    }
 }
 
-#[pyclass]
+#[pyclass(frozen, weakref)]
 pub struct PyCache {
-    cache: Arc<Mutex<HashMap<String, PyEntryState>>>,
-    timeout: u64,
+    cache: Arc<ShardedKeyMap<PyEntryState>>,
+    supervisor: Arc<Supervisor>,
+    trace: Option<AccessTrace>,
+    stats: Arc<CacheStats>,
+    tenants: Mutex<HashMap<String, Arc<TenantState>>>,
+    refresh: Arc<RefreshQueue>,
+    memory: Arc<MemoryBudget>,
+    scheduler: Arc<Scheduler>,
+    scheduler_started: Once,
+    // Background threads start with the first work they have, so a cache
+    // that never needs them never spawns them
+    sweeper_started: Once,
+    refresher_started: Once,
+    degraded: Arc<AtomicBool>,
+    degraded_default: Mutex<Option<Py<PyAny>>>,
+    enabled: AtomicBool,
+    closed: AtomicBool,
+    // Key prefixes of the namespaces switched off with `enable_namespace`
+    disabled_namespaces: Mutex<HashSet<String>>,
+    hooks: Arc<HookDispatcher>,
+    generation: AtomicU64,
+    leases: LeaseTable,
+    popularity: Option<PopularitySketch>,
+    removals: Option<Arc<RemovalLog>>,
+    stripes: Option<Arc<LockStripes>>,
+    calls: Arc<CallPool>,
+    config: Arc<CacheConfig>,
 }
 
 #[pymethods]
 impl PyCache {
     #[new]
-    fn new(timeout: u64) -> Self {
-        Self {
-            cache: Arc::new(Mutex::new(HashMap::new())),
-            timeout,
+    // Durations are float seconds. `wait_timeout` takes precedence over
+    // `timeout`; without either, waiters wait for the leader indefinitely
+    #[pyo3(signature = (
+        timeout=None,
+        trace_capacity=None,
+        sweep_interval=1.0,
+        cancel_abandoned=false,
+        retries=0,
+        retry_predicate=None,
+        on_error=None,
+        post_process=None,
+        freeze=false,
+        check_access=None,
+        on_quota_exceeded=None,
+        on_func_mismatch=None,
+        write_policy="last",
+        max_inflight_alarm=None,
+        on_inflight_alarm=None,
+        soft_memory=None,
+        max_memory=None,
+        keep_history=0,
+        delta_refresh=false,
+        refresh_comparator=None,
+        wait_timeout=None,
+        compute_timeout=None,
+        on_hit=None,
+        on_evict=None,
+        hook_queue_size=1024,
+        record_provenance=false,
+        canonicalize=None,
+        fallback=None,
+        on_hook_error=None,
+        disable_hook_after=None,
+        ttl=None,
+        wake="all",
+        wake_batch=64,
+        max_size=None,
+        store_results=true,
+        negative_ttl=None,
+        negative_sentinel=None,
+        event_loop=None,
+        track_popularity=false,
+        on_miss=None,
+        on_leader_start=None,
+        on_leader_done=None,
+        on_timeout=None,
+        thread_nice=None,
+        thread_affinity=None,
+        timeout_policy="lead",
+        on_leader_failure="broadcast",
+        max_handoffs=1,
+        timeout_fallback=None,
+        leader_elector=None,
+        shards=16,
+        removal_log=None,
+        lock_stripes=None,
+        call_workers=16,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        timeout: Option<f64>,
+        trace_capacity: Option<usize>,
+        sweep_interval: f64,
+        cancel_abandoned: bool,
+        retries: u32,
+        retry_predicate: Option<Py<PyAny>>,
+        on_error: Option<Py<PyAny>>,
+        post_process: Option<Py<PyAny>>,
+        freeze: bool,
+        check_access: Option<Py<PyAny>>,
+        on_quota_exceeded: Option<Py<PyAny>>,
+        on_func_mismatch: Option<&str>,
+        write_policy: &str,
+        max_inflight_alarm: Option<f64>,
+        on_inflight_alarm: Option<Py<PyAny>>,
+        soft_memory: Option<usize>,
+        max_memory: Option<usize>,
+        keep_history: usize,
+        delta_refresh: bool,
+        refresh_comparator: Option<Py<PyAny>>,
+        wait_timeout: Option<f64>,
+        compute_timeout: Option<f64>,
+        on_hit: Option<Py<PyAny>>,
+        on_evict: Option<Py<PyAny>>,
+        hook_queue_size: usize,
+        record_provenance: bool,
+        canonicalize: Option<&Bound<'_, PyAny>>,
+        fallback: Option<Py<FallbackPolicy>>,
+        on_hook_error: Option<Py<PyAny>>,
+        disable_hook_after: Option<u32>,
+        ttl: Option<f64>,
+        wake: &str,
+        wake_batch: usize,
+        max_size: Option<usize>,
+        store_results: bool,
+        negative_ttl: Option<f64>,
+        negative_sentinel: Option<Py<PyAny>>,
+        event_loop: Option<Py<PyAny>>,
+        track_popularity: bool,
+        on_miss: Option<Py<PyAny>>,
+        on_leader_start: Option<Py<PyAny>>,
+        on_leader_done: Option<Py<PyAny>>,
+        on_timeout: Option<Py<PyAny>>,
+        thread_nice: Option<i32>,
+        thread_affinity: Option<Vec<usize>>,
+        timeout_policy: &str,
+        on_leader_failure: &str,
+        max_handoffs: u32,
+        timeout_fallback: Option<Py<PyAny>>,
+        leader_elector: Option<Py<PyAny>>,
+        shards: usize,
+        removal_log: Option<usize>,
+        lock_stripes: Option<usize>,
+        call_workers: usize,
+    ) -> PyResult<Self> {
+        let wait_timeout = optional_secs(wait_timeout.or(timeout))?;
+        if let (Some(soft_memory), Some(max_memory)) = (soft_memory, max_memory) {
+            if soft_memory > max_memory {
+                return Err(PyValueError::new_err(
+                    "soft_memory must not be larger than max_memory",
+                ));
+            }
+        }
+        let on_func_mismatch = on_func_mismatch.map(MismatchPolicy::parse).transpose()?;
+        let write_policy = WritePolicy::parse(write_policy)?;
+        let canonicalize = canonicalize.map(Canonicalizer::parse).transpose()?;
+        let wake = WakeStrategy::parse(wake, wake_batch)?;
+        // A striped condition variable is shared by several keys, so a
+        // staged wakeup could land on another key's waiter and get lost
+        if lock_stripes.is_some() && wake != WakeStrategy::All {
+            return Err(PyValueError::new_err(
+                "lock_stripes only supports the 'all' wake strategy",
+            ));
         }
+        let threads = ThreadSettings::parse(thread_nice, thread_affinity)?;
+        let timeout_policy = TimeoutPolicy::parse(timeout_policy)?;
+        let failure_policy = FailurePolicy::parse(on_leader_failure)?;
+        Ok(Self::with_config(CacheConfig {
+            wait_timeout,
+            compute_timeout: optional_secs(compute_timeout)?,
+            trace_capacity,
+            sweep_interval: duration_from_secs(sweep_interval)?,
+            cancel_abandoned,
+            retries,
+            retry_predicate,
+            on_error,
+            post_process,
+            freeze,
+            check_access,
+            on_quota_exceeded,
+            on_func_mismatch,
+            write_policy,
+            max_inflight_alarm: optional_secs(max_inflight_alarm)?,
+            on_inflight_alarm,
+            soft_memory,
+            max_memory,
+            keep_history,
+            delta_refresh,
+            refresh_comparator,
+            on_hit,
+            on_evict,
+            hook_queue_size: hook_queue_size.max(1),
+            record_provenance,
+            canonicalize,
+            fallback,
+            on_hook_error,
+            disable_hook_after: disable_hook_after.map(|limit| limit.max(1)),
+            ttl: optional_secs(ttl)?,
+            wake,
+            max_size: max_size.map(|max_size| max_size.max(1)),
+            store_results,
+            negative_ttl: optional_secs(negative_ttl)?,
+            negative_sentinel,
+            event_loop,
+            track_popularity,
+            on_miss,
+            on_leader_start,
+            on_leader_done,
+            on_timeout,
+            threads,
+            timeout_policy,
+            failure_policy,
+            max_handoffs,
+            timeout_fallback,
+            leader_elector,
+            shards: shards.max(1),
+            removal_log,
+            lock_stripes: lock_stripes.map(|stripes| stripes.max(1)),
+            call_workers: call_workers.max(1),
+        }))
     }
 
+    // `timeout` overrides the cache's wait timeout for this call, in
+    // seconds; `math.inf` waits indefinitely. `freeze` overrides the cache's
+    // policy for the value computed by this call.
+    #[pyo3(signature = (py_func, args, kwargs, key, *, tags=None, context=None, meta=None, expire_at=None, provenance=None, timeout=None, freeze=None))]
+    #[allow(clippy::too_many_arguments)]
     fn py_call(
-        &self,
-        py: Python<'_>,
+        slf: &Bound<'_, Self>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+        tags: Option<Py<PyAny>>,
+        context: Option<Py<PyAny>>,
+        meta: Option<Py<PyAny>>,
+        expire_at: Option<&Bound<'_, PyAny>>,
+        provenance: Option<String>,
+        timeout: Option<f64>,
+        freeze: Option<bool>,
+    ) -> PyResult<Py<PyAny>> {
+        let options = CallOptions {
+            tags,
+            context,
+            meta,
+            expires_at: expire_at.map(instant_from_datetime).transpose()?,
+            provenance,
+            tenant: None,
+            wait_timeout: timeout.map(timeout_from_secs).transpose()?,
+            freeze,
+        };
+        let py = slf.py();
+        Self::call_bounded(
+            &slf.clone().unbind(),
+            py,
+            py_func,
+            args,
+            kwargs,
+            &key,
+            options,
+        )
+    }
+
+    // Starts or joins the flight for `key` on the cache's call pool and
+    // returns a handle to its outcome right away; `timeout` and `freeze`
+    // work as for `py_call`
+    #[pyo3(signature = (py_func, args, kwargs, key, *, tags=None, context=None, meta=None, expire_at=None, provenance=None, timeout=None, freeze=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_call_nowait(
+        slf: &Bound<'_, Self>,
         py_func: Py<PyAny>,
         args: Py<PyAny>,
         kwargs: Py<PyAny>,
         key: String,
-    ) -> Py<PyAny> {
-        let mut cache = self.cache.lock().unwrap();
+        tags: Option<Py<PyAny>>,
+        context: Option<Py<PyAny>>,
+        meta: Option<Py<PyAny>>,
+        expire_at: Option<&Bound<'_, PyAny>>,
+        provenance: Option<String>,
+        timeout: Option<f64>,
+        freeze: Option<bool>,
+    ) -> PyResult<FlightHandle> {
+        let options = CallOptions {
+            tags,
+            context,
+            meta,
+            expires_at: expire_at.map(instant_from_datetime).transpose()?,
+            provenance,
+            tenant: None,
+            wait_timeout: timeout.map(timeout_from_secs).transpose()?,
+            freeze,
+        };
+        let completion = Self::spawn_call(
+            slf.clone().unbind(),
+            py_func,
+            args,
+            kwargs,
+            key.clone(),
+            options,
+        )?;
+        Ok(FlightHandle::new(key, completion))
+    }
 
-        let cached_value = cache.get(&key);
+    // Like `py_call`, but returns `(value, is_leader, waiters_served)`
+    #[pyo3(signature = (py_func, args, kwargs, key, *, tags=None, context=None, meta=None, expire_at=None, provenance=None, timeout=None, freeze=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_call_with_info(
+        slf: &Bound<'_, Self>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+        tags: Option<Py<PyAny>>,
+        context: Option<Py<PyAny>>,
+        meta: Option<Py<PyAny>>,
+        expire_at: Option<&Bound<'_, PyAny>>,
+        provenance: Option<String>,
+        timeout: Option<f64>,
+        freeze: Option<bool>,
+    ) -> PyResult<(Py<PyAny>, bool, usize)> {
+        let options = CallOptions {
+            tags,
+            context,
+            meta,
+            expires_at: expire_at.map(instant_from_datetime).transpose()?,
+            provenance,
+            tenant: None,
+            wait_timeout: timeout.map(timeout_from_secs).transpose()?,
+            freeze,
+        };
+        let mut info = CallInfo::default();
+        let value = Self::call_bounded_with_info(
+            &slf.clone().unbind(),
+            slf.py(),
+            py_func,
+            args,
+            kwargs,
+            &key,
+            options,
+            &mut info,
+        )?;
+        Ok((value, info.leader, info.shared))
+    }
 
-        if let Some(value_state) = cached_value {
-            match value_state {
-                PyEntryState::Pending(lock_var) => {
-                    let (lock, cvar) = &**lock_var;
-                    let entry = lock.lock().unwrap();
-                    if entry.ready {
-                        return entry
-                            .value
-                            .as_ref()
-                            .expect("None after read!")
-                            .clone_ref(py);
-                    }
-                    drop(entry);
-
-                    let _ = py.allow_threads(move || {
-                        let wait_guard = lock.lock().unwrap();
-                        let _ = cvar
-                            .wait_timeout(wait_guard, Duration::from_millis(self.timeout))
-                            .unwrap();
-                    });
+    // Like `py_call`, with the key derived from the function and arguments
+    #[pyo3(signature = (py_func, args, kwargs, *, tags=None, context=None, meta=None, expire_at=None, provenance=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_call_auto(
+        slf: &Bound<'_, Self>,
+        py_func: Bound<'_, PyAny>,
+        args: Bound<'_, PyTuple>,
+        kwargs: Bound<'_, PyDict>,
+        tags: Option<Py<PyAny>>,
+        context: Option<Py<PyAny>>,
+        meta: Option<Py<PyAny>>,
+        expire_at: Option<&Bound<'_, PyAny>>,
+        provenance: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let key = derive_key(&py_func, &args, &kwargs)?;
+        Self::py_call(
+            slf,
+            py_func.unbind(),
+            args.into_any().unbind(),
+            kwargs.into_any().unbind(),
+            key,
+            tags,
+            context,
+            meta,
+            expire_at,
+            provenance,
+            None,
+            None,
+        )
+    }
 
-                    let entry = lock.lock().unwrap();
-                    if entry.ready {
-                        return entry
-                            .value
-                            .as_ref()
-                            .expect("None after read!")
-                            .clone_ref(py);
-                    }
+    // Seeds `key` without computing; `ttl` in seconds overrides the
+    // cache-wide one for this entry, and `math.inf` keeps it until dropped
+    #[pyo3(signature = (key, value, *, ttl=None, refresher=None))]
+    fn set(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        value: Py<PyAny>,
+        ttl: Option<f64>,
+        refresher: Option<Py<PyAny>>,
+    ) -> PyResult<bool> {
+        let ttl = ttl.map(timeout_from_secs).transpose()?;
+        self.write(py, key, value, refresher, ttl, None)
+    }
+
+    // Opens a flight for `key` that a producer outside the cache resolves
+    // with `complete` or `fail`; callers block on it like on any other
+    // flight. Returns false if `key` already has a flight or a fresh value.
+    pub(crate) fn start(&self, py: Python<'_>, key: &str) -> PyResult<bool> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let key = key.as_ref();
+        let token = Py::new(py, CancelToken::default())?;
+        let mut entry = PyCacheEntry::pending(token, CallOptions::default(), self.memory.clone());
+        entry.external = true;
+        entry.provenance = self.provenance(py, None);
+
+        let mut cache = self.cache.lock(key).expect("Unable to lock cache!");
+        if let Some(PyEntryState::Pending(lock_var)) = cache.get(key) {
+            let current = lock_var.0.lock().unwrap();
+            if !current.ready || !current.is_expired(Instant::now()) {
+                return Ok(false);
+            }
+        }
+        let signal = FlightSignal::new(self.stripes.as_deref(), key);
+        if let Some(previous) = cache.insert(key, PyEntryState::new(entry, signal)) {
+            log_removal(self.removal_log(), key, &previous, "expired");
+        }
+        Ok(true)
+    }
+
+    // Resolves the pending flight of `key` with `value`, waking its waiters.
+    // Without one the value is simply stored. Returns whether a flight was
+    // resolved.
+    pub(crate) fn complete(&self, py: Python<'_>, key: &str, value: Py<PyAny>) -> PyResult<bool> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let key = key.as_ref();
+        let Some(flight) = self.pending_flight(key) else {
+            self.write(py, key, value, None, None, None)?;
+            return Ok(false);
+        };
+        let weight = size_of(py, &value);
+        let expires_at = self.config.result_expiry(py, &value);
+        let (lock, cvar) = &*flight;
+        let mut entry = lock.lock().expect("Unable to get cache entry for update");
+        if entry.ready || entry.overrun {
+            return Ok(false);
+        }
+        if entry.expires_at.is_none() {
+            entry.expires_at = expires_at;
+        }
+        entry.ready(value, weight);
+        cvar.wake(self.config.wake);
+        drop(entry);
+        if !self.config.store_results {
+            self.remove_flight(key, &flight);
+        }
+        self.enforce_size_limit(py);
+        self.enforce_memory_limit(py);
+        Ok(true)
+    }
+
+    // Raises `exception` in the waiters of the pending flight of `key`,
+    // following the leader failure policy. Returns whether there was one.
+    fn fail(&self, py: Python<'_>, key: &str, exception: Bound<'_, PyAny>) -> PyResult<bool> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let Some(flight) = self.pending_flight(&key) else {
+            return Ok(false);
+        };
+        self.fail_pending(py, &key, &flight, &PyErr::from_value(exception));
+        Ok(true)
+    }
+
+    // Passive read: blocks until `key` has a fresh value and returns it, or
+    // raises the error of the flight it waited on. It never starts a flight
+    // nor calls anything; with no flight yet it waits for one to show up.
+    // `timeout` is in seconds, as for `py_call`.
+    #[pyo3(signature = (key, timeout=None))]
+    fn wait_for(&self, py: Python<'_>, key: &str, timeout: Option<f64>) -> PyResult<Py<PyAny>> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let key = key.as_ref();
+        let wait_timeout = timeout
+            .map(timeout_from_secs)
+            .transpose()?
+            .unwrap_or(self.config.wait_timeout);
+        let deadline = wait_timeout.map(|wait_timeout| Instant::now() + wait_timeout);
+        let lock_var = loop {
+            let now = Instant::now();
+            let current = match self
+                .cache
+                .read(key)
+                .expect("Unable to lock cache!")
+                .get(key)
+            {
+                Some(PyEntryState::Pending(lock_var))
+                    if !lock_var.0.lock().unwrap().is_expired(now) =>
+                {
+                    Some(lock_var.clone())
                 }
+                _ => None,
+            };
+            if let Some(lock_var) = current {
+                break lock_var;
             }
+            let slice = match deadline {
+                Some(deadline) if now >= deadline => {
+                    return Err(PyTimeoutError::new_err(format!(
+                        "Timed out waiting for cache entry '{}'",
+                        key
+                    )))
+                }
+                Some(deadline) => (deadline - now).min(SIGNAL_CHECK_INTERVAL),
+                None => SIGNAL_CHECK_INTERVAL,
+            };
+            py.allow_threads(|| thread::sleep(slice));
+            py.check_signals()?;
+        };
+
+        // A failed leader's handoff is left to callers that can compute
+        let (lock, cvar) = &*lock_var;
+        let resolved = wait_while(py, lock, cvar, deadline, in_flight)?;
+        if resolved {
+            cvar.pass_on(self.config.wake);
         }
-        // Insert waiting state and drop call
-        let placeholder = PyCacheEntry::pending();
-        let notification = Condvar::new();
-        let pending_entry = Arc::new((Mutex::new(placeholder), notification));
-        cache.insert(key.clone(), PyEntryState::Pending(pending_entry.clone()));
+        let mut entry = lock.lock().unwrap();
+        if entry.ready {
+            return Ok(entry.touch().clone_ref(py));
+        }
+        if let Some(err) = &entry.error {
+            return Err(err.clone_ref(py));
+        }
+        if entry.overrun {
+            return Err(ComputeTimeout::new_err(format!(
+                "Computing cache entry '{}' exceeded compute_timeout",
+                key
+            )));
+        }
+        Err(PyTimeoutError::new_err(format!(
+            "Timed out waiting for cache entry '{}'",
+            key
+        )))
+    }
+
+    // Tears the cache down: every blocked waiter wakes with CacheClosed,
+    // running leaders are asked to stop, all entries are dropped, background
+    // threads are stopped and joined, and reads and writes are refused from
+    // then on. Closing again does nothing.
+    fn close(&self, py: Python<'_>) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+        let dropped = cache.retain(|_, _| false);
         drop(cache);
+        for PyEntryState::Pending(lock_var) in &dropped {
+            let (lock, cvar) = &**lock_var;
+            let mut entry = lock.lock().unwrap();
+            if entry.ready {
+                continue;
+            }
+            entry.error = Some(CacheClosed::new_err("The cache was closed"));
+            entry.token.get().cancel();
+            cvar.wake_all();
+        }
+        // Components take the GIL to run hooks and callbacks
+        py.allow_threads(|| self.supervisor.shutdown());
+        self.calls.stop();
+    }
 
-        // Do calculation
-        let args_tuple: &Bound<'_, PyTuple> =
-            args.downcast_bound(py).expect("Unable to cast to PyTuple!");
-        let kwargs_dict: &Bound<'_, PyDict>;
-        kwargs_dict = kwargs
-            .downcast_bound(py)
-            .expect("Unable to cast to PyDict!");
-        let result = py_func
-            .call(py, args_tuple, Some(kwargs_dict))
-            .expect("PyCall failed");
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
 
-        // Notify waiting values and update state
-        let (lock, cvar) = &*pending_entry;
-        let mut entry = lock.lock().expect("Unable to get cache entry for update");
-        entry.ready(result.clone_ref(py));
-        cvar.notify_all();
-        result
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.close(py);
+        false
     }
 
-    fn drop(&self, key: String) {
-        let mut cache = self.cache.lock().expect("Unable to lock cache!");
-        cache.remove(&key);
+    // Read-only mapping view over keys with a fresh value, as stored, i.e.
+    // after canonicalization. None of these count as hits.
+    fn __contains__(&self, py: Python<'_>, key: &str) -> PyResult<bool> {
+        let key = self.canonical_key(py, key)?;
+        let degraded = self.degraded.load(Ordering::SeqCst);
+        Ok(self
+            .cache
+            .read(&key)
+            .expect("Unable to lock cache!")
+            .get(&key)
+            .is_some_and(|state| state.is_fresh(Instant::now(), degraded)))
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use pyo3::{
-        ffi::c_str,
-        types::{IntoPyDict, PyTuple},
-    };
+    fn __len__(&self, py: Python<'_>) -> usize {
+        self.keys(py).len()
+    }
 
-    #[test]
-    fn test_pycall() {
-        let pycache = PyCache::new(10000);
-        let args: [i8; 2] = [1, 10];
-        let kwargs: [(&'static str, i16); 1] = [("multiplier", 100)];
-        let test_key: String = "test".to_string();
+    // An empty cache is still a cache, so `cache or PyCache()` keeps it
+    fn __bool__(&self) -> bool {
+        true
+    }
 
-        Python::with_gil(|py| {
-            let pyfunc: Py<PyAny> = PyModule::from_code(
-                py,
-                c_str!(
-                    "from random import randint
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<Py<PyAny>> {
+        self.ensure_open()?;
+        let degraded = self.degraded.load(Ordering::SeqCst);
+        let canonical = self.canonical_key(py, key)?;
+        let lock_var = self
+            .cache
+            .read(&canonical)
+            .expect("Unable to lock cache!")
+            .get(&canonical)
+            .map(|PyEntryState::Pending(lock_var)| lock_var.clone());
+        lock_var
+            .and_then(|lock_var| {
+                let entry = lock_var.0.lock().unwrap();
+                let fresh = entry.ready && (degraded || !entry.is_expired(Instant::now()));
+                fresh.then(|| {
+                    entry
+                        .value
+                        .as_ref()
+                        .expect("None after ready!")
+                        .clone_ref(py)
+                })
+            })
+            .ok_or_else(|| PyKeyError::new_err(key.to_string()))
+    }
 
-def f(lower, upper, multiplier):
-                        return randint(lower, upper)*multiplier"
-                ),
-                c_str!(""),
-                c_str!(""),
-            )
-            .unwrap()
-            .getattr("f")
-            .unwrap()
-            .into();
-            let py_args: Bound<'_, PyTuple> = PyTuple::new(py, &args).unwrap();
-            let py_kwargs: Bound<'_, PyDict> = kwargs.into_py_dict(py).unwrap();
+    // Snapshot of the keys with a fresh value
+    fn keys(&self, py: Python<'_>) -> Vec<String> {
+        let degraded = self.degraded.load(Ordering::SeqCst);
+        let cache = &self.cache;
+        py.allow_threads(|| {
+            let now = Instant::now();
+            cache
+                .lock_all()
+                .expect("Unable to lock cache!")
+                .iter()
+                .filter(|(_, state)| state.is_fresh(now, degraded))
+                .map(|(key, _)| key.to_string())
+                .collect()
+        })
+    }
 
-            let _ = pycache.py_call(
-                py,
-                pyfunc.clone_ref(py),
-                py_args.clone().into(),
-                py_kwargs.into(),
-                test_key.clone(),
-            );
+    // Fresh value of `key`, or `default`; never computes nor waits on a
+    // pending flight
+    #[pyo3(signature = (key, default=None))]
+    fn get(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        default: Option<Py<PyAny>>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        Ok(self.lookup(py, key)?.or(default))
+    }
 
-            // Assert state of cache
-            let cache = pycache.cache.lock().unwrap();
-            let cached_entry = cache.get(&test_key).unwrap();
-            let expected: i32;
-            match cached_entry {
-                PyEntryState::Pending(val) => {
-                    let (lock, _) = &**val;
-                    let entry = lock.lock().unwrap();
-                    assert_eq!(entry.ready, true);
-                    expected = entry.value.as_ref().unwrap().extract::<i32>(py).unwrap();
+    fn drop(&self, py: Python<'_>, key: String) -> PyResult<()> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, &key)?;
+        self.leases.check(&key, None)?;
+        self.remove(&key);
+        Ok(())
+    }
+
+    // Patterns match stored keys, i.e. after canonicalization. Both return
+    // how many entries were dropped, in-flight ones included; leased keys
+    // are kept, as drop() refuses them too.
+    fn drop_prefix(&self, prefix: &str) -> PyResult<usize> {
+        self.ensure_open()?;
+        Ok(self.drop_where(|key| key.starts_with(prefix)))
+    }
+
+    // `*` matches any run of characters, `?` a single one
+    fn drop_matching(&self, pattern: &str) -> PyResult<usize> {
+        self.ensure_open()?;
+        Ok(self.drop_where(|key| glob_match(pattern, key)))
+    }
+
+    // Exclusive write rights on `key` for `ttl` seconds; plain writes
+    // fail with LeaseHeld until the lease is released or runs out
+    fn lease(slf: &Bound<'_, Self>, key: &str, ttl: f64) -> PyResult<Lease> {
+        slf.get().ensure_open()?;
+        let key = slf.get().canonical_key(slf.py(), key)?.into_owned();
+        let ttl = duration_from_secs(ttl)?;
+        match slf.get().leases.acquire(&key, ttl) {
+            Some(id) => Ok(Lease::new(slf.clone().unbind(), key, id)),
+            None => Err(LeaseHeld::new_err(format!(
+                "Cache entry '{}' is already leased",
+                key
+            ))),
+        }
+    }
+
+    #[pyo3(signature = (schedule, *, prefix=None, namespace=None))]
+    fn invalidate_at(
+        &self,
+        schedule: &str,
+        prefix: Option<String>,
+        namespace: Option<&str>,
+    ) -> PyResult<()> {
+        self.ensure_open()?;
+        let schedule = CronSchedule::parse(schedule)?;
+        let prefix = match (prefix, namespace) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "Pass either prefix or namespace, not both",
+                ))
+            }
+            (_, Some(namespace)) => tenant_prefix(namespace),
+            (prefix, None) => prefix.unwrap_or_default(),
+        };
+        self.scheduler
+            .add(schedule, prefix, (unix_now() / 60.0) as u64);
+
+        self.scheduler_started.call_once(|| {
+            let weak_cache = Arc::downgrade(&self.cache);
+            let scheduler = self.scheduler.clone();
+            let degraded = self.degraded.clone();
+            let removals = self.removals.clone();
+            self.supervisor.spawn("scheduler", move |shutdown| loop {
+                if !shutdown.sleep(SCHEDULE_POLL_INTERVAL) {
+                    return;
+                }
+                let Some(cache) = weak_cache.upgrade() else {
+                    return;
+                };
+                // Invalidations that came due while degraded fire on recovery
+                if degraded.load(Ordering::Relaxed) {
+                    continue;
+                }
+                for prefix in scheduler.due((unix_now() / 60.0) as u64) {
+                    cache
+                        .lock_all()
+                        .expect("Unable to lock cache!")
+                        .retain(|key, state| {
+                            if !(key.starts_with(&prefix) && state.is_ready()) {
+                                return true;
+                            }
+                            log_removal(removals.as_deref(), key, state, "schedule");
+                            false
+                        });
                 }
+            });
+        });
+        Ok(())
+    }
+
+    // Incident mode: stored values are served however stale and misses
+    // return `default`, or raise CacheDegraded, instead of computing.
+    #[pyo3(signature = (enabled=true, *, default=None))]
+    fn degraded(&self, enabled: bool, default: Option<Py<PyAny>>) -> bool {
+        *self
+            .degraded_default
+            .lock()
+            .expect("Unable to lock degraded default!") = default;
+        self.degraded.swap(enabled, Ordering::SeqCst)
+    }
+
+    // Kill switch: while disabled, calls run the function directly, without
+    // caching or coalescing. Stored entries are kept for when it is back on.
+    #[getter]
+    fn get_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    #[setter]
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    // The kill switch for one namespace; returns whether it was enabled
+    #[pyo3(signature = (namespace, enabled=true))]
+    fn enable_namespace(&self, namespace: &str, enabled: bool) -> bool {
+        let mut disabled = self
+            .disabled_namespaces
+            .lock()
+            .expect("Unable to lock disabled namespaces!");
+        let prefix = tenant_prefix(namespace);
+        match enabled {
+            true => !disabled.remove(&prefix),
+            false => disabled.insert(prefix),
+        }
+    }
+
+    #[pyo3(signature = (key, priority="normal"))]
+    fn refresh(&self, py: Python<'_>, key: String, priority: &str) -> PyResult<bool> {
+        self.ensure_open()?;
+        let lane = RefreshLane::parse(priority)?;
+        let key = self.canonical_key(py, &key)?.into_owned();
+        let cache = self.cache.read(&key).expect("Unable to lock cache!");
+        if !cache.get(&key).is_some_and(|state| state.is_ready()) {
+            return Ok(false);
+        }
+        drop(cache);
+        self.start_refresher();
+        Ok(self.refresh.push(key, lane))
+    }
+
+    #[pyo3(signature = (template, bucket="5m", prewarm=None))]
+    fn timed_key(&self, template: &str, bucket: &str, prewarm: Option<f64>) -> PyResult<String> {
+        let bucket = parse_bucket(bucket)?;
+        let now = unix_now();
+        let bucket_start = now as u64 / bucket * bucket;
+        let key = bucket_key(template, bucket_start);
+
+        // Shortly before rollover, compute the next bucket in the background
+        // with the loader of the current one.
+        let rollover = (bucket_start + bucket) as f64;
+        if prewarm.is_some_and(|prewarm| rollover - now <= prewarm) {
+            let ready = self
+                .cache
+                .read(&key)
+                .expect("Unable to lock cache!")
+                .get(&key)
+                .is_some_and(|state| state.is_ready());
+            if ready {
+                let next = bucket_key(template, bucket_start + bucket);
+                self.start_refresher();
+                self.refresh
+                    .push_from(next, Some(key.clone()), RefreshLane::High);
             }
-            drop(cache);
-            let actual = pycache
-                .py_call(
-                    py,
-                    pyfunc.clone_ref(py),
-                    py_args.clone().into(),
-                    PyDict::new(py).into(),
-                    test_key,
-                )
-                .extract::<i32>(py)
-                .unwrap();
+        }
+        Ok(key)
+    }
 
-            assert_eq!(actual, expected);
+    // Typed getters convert the stored value in place and raise TypeError on a
+    // mismatch; like lookups they return None on a miss and never compute.
+    fn get_int(&self, py: Python<'_>, key: &str) -> PyResult<Option<i64>> {
+        self.lookup_with(py, key, |value| {
+            value
+                .downcast::<PyInt>()
+                .map_err(|_| type_mismatch(key, value, "int"))?
+                .extract()
+        })?
+        .transpose()
+    }
+
+    fn get_str(&self, py: Python<'_>, key: &str) -> PyResult<Option<Py<PyString>>> {
+        self.lookup_with(py, key, |value| {
+            value
+                .downcast::<PyString>()
+                .map(|value| value.clone().unbind())
+                .map_err(|_| type_mismatch(key, value, "str"))
+        })?
+        .transpose()
+    }
+
+    fn get_bytes(&self, py: Python<'_>, key: &str) -> PyResult<Option<Py<PyBytes>>> {
+        self.lookup_with(py, key, |value| {
+            value
+                .downcast::<PyBytes>()
+                .map(|value| value.clone().unbind())
+                .map_err(|_| type_mismatch(key, value, "bytes"))
+        })?
+        .transpose()
+    }
+
+    // Ready values for many keys with a single pass over the map lock. Keys
+    // are plain strings or `(namespace, key)` pairs addressing a tenant's
+    // entries; missing or stale keys come back as None.
+    fn get_many(
+        &self,
+        py: Python<'_>,
+        keys: Vec<Bound<'_, PyAny>>,
+    ) -> PyResult<Vec<Option<Py<PyAny>>>> {
+        self.ensure_open()?;
+        let mut resolved = Vec::with_capacity(keys.len());
+        for key in &keys {
+            let key = match key.extract::<(String, String)>() {
+                Ok((namespace, key)) => {
+                    tenant_prefix(&namespace) + &self.canonical_key(py, &key)?
+                }
+                Err(_) => self
+                    .canonical_key(py, &key.extract::<String>()?)?
+                    .into_owned(),
+            };
+            resolved.push(key);
+        }
+        let flights: Vec<_> = {
+            let cache = self.cache.lock_all().expect("Unable to lock cache!");
+            resolved
+                .iter()
+                .map(|key| {
+                    cache
+                        .get(key)
+                        .map(|PyEntryState::Pending(lock_var)| lock_var.clone())
+                })
+                .collect()
+        };
+        let degraded = self.degraded.load(Ordering::SeqCst);
+        let now = Instant::now();
+        Ok(flights
+            .into_iter()
+            .map(|flight| {
+                let lock_var = flight?;
+                let mut entry = lock_var.0.lock().unwrap();
+                let fresh = entry.ready && (degraded || !entry.is_expired(now));
+                fresh.then(|| entry.touch().clone_ref(py))
+            })
+            .collect())
+    }
+
+    fn entry_info<'py>(&self, py: Python<'py>, key: &str) -> PyResult<Option<Bound<'py, PyDict>>> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let lock_var = match self
+            .cache
+            .read(&key)
+            .expect("Unable to lock cache!")
+            .get(&key)
+        {
+            Some(PyEntryState::Pending(lock_var)) => lock_var.clone(),
+            None => return Ok(None),
+        };
+        let now = Instant::now();
+        let entry = lock_var.0.lock().unwrap();
+        let info = PyDict::new(py);
+        info.set_item("key", &*key)?;
+        info.set_item("ready", entry.ready)?;
+        info.set_item(
+            "namespace",
+            entry.tenant.as_ref().map(|tenant| tenant.name.as_str()),
+        )?;
+        info.set_item("weight", entry.weight)?;
+        info.set_item("hits", entry.hits)?;
+        info.set_item(
+            "age",
+            now.saturating_duration_since(entry.created_at)
+                .as_secs_f64(),
+        )?;
+        info.set_item(
+            "ttl",
+            entry
+                .expires_at
+                .map(|expires_at| expires_at.saturating_duration_since(now).as_secs_f64()),
+        )?;
+        info.set_item("leader", entry.leader.as_deref())?;
+        info.set_item("provenance", entry.provenance.as_deref())?;
+        info.set_item("meta", &entry.meta)?;
+        Ok(Some(info))
+    }
+
+    fn history(&self, py: Python<'_>, key: &str) -> PyResult<Vec<(f64, Py<PyAny>)>> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let lock_var = match self
+            .cache
+            .read(&key)
+            .expect("Unable to lock cache!")
+            .get(&key)
+        {
+            Some(PyEntryState::Pending(lock_var)) => lock_var.clone(),
+            None => return Ok(Vec::new()),
+        };
+        let entry = lock_var.0.lock().unwrap();
+        Ok(entry.history(py))
+    }
+
+    #[pyo3(signature = (name, *, max_entries=None, max_memory=None, on_quota=None))]
+    fn tenant(
+        slf: &Bound<'_, Self>,
+        name: String,
+        max_entries: Option<usize>,
+        max_memory: Option<usize>,
+        on_quota: Option<&str>,
+    ) -> PyResult<TenantView> {
+        let mut tenants = slf.get().tenants.lock().expect("Unable to lock tenants!");
+        let state = tenants
+            .entry(name.clone())
+            .or_insert_with(|| Arc::new(TenantState::new(name)))
+            .clone();
+        drop(tenants);
+        state.set_quota(max_entries, max_memory, on_quota)?;
+        Ok(TenantView::new(slf.clone().unbind(), state))
+    }
+
+    // `@cache.flight()` routes every call of the decorated function through
+    // the cache; `key` is a format template, a callable or None to derive it.
+    // `freeze` overrides the cache's policy for this function only.
+    #[pyo3(signature = (key=None, *, freeze=None))]
+    fn flight(
+        slf: &Bound<'_, Self>,
+        key: Option<Bound<'_, PyAny>>,
+        freeze: Option<bool>,
+    ) -> PyResult<FlightDecorator> {
+        FlightDecorator::new(slf.clone().unbind(), key, freeze)
+    }
+
+    #[pyo3(signature = (promote=false))]
+    fn overlay(slf: &Bound<'_, Self>, promote: bool) -> CacheOverlay {
+        CacheOverlay::new(slf.clone().unbind(), promote)
+    }
+
+    fn scope(slf: &Bound<'_, Self>) -> FlightScope {
+        FlightScope::new(slf.clone().unbind())
+    }
+
+    // Calls `callback` with the `stats()` dict every `interval` seconds from
+    // a background thread, for telemetry stacks without a dedicated
+    // exporter. Stops when the cache is closed or garbage collected; a
+    // cache has at most one emitter.
+    #[pyo3(signature = (callback, interval=10.0))]
+    fn emit_stats(slf: &Bound<'_, Self>, callback: Py<PyAny>, interval: f64) -> PyResult<()> {
+        slf.get().ensure_open()?;
+        if !(interval > 0.0 && interval.is_finite()) {
+            return Err(PyValueError::new_err(
+                "interval must be a positive number of seconds",
+            ));
+        }
+        if slf.get().supervisor.is_running("stats-emitter") {
+            return Err(PyRuntimeError::new_err(
+                "Stats are already emitted for this cache",
+            ));
+        }
+        let interval = Duration::from_secs_f64(interval);
+        let weak_cache = PyWeakrefReference::new(slf)?.unbind();
+        slf.get()
+            .supervisor
+            .spawn("stats-emitter", move |shutdown| loop {
+                if !shutdown.sleep(interval) {
+                    return;
+                }
+                let running = Python::with_gil(|py| {
+                    let Ok(Some(cache)) = weak_cache.bind(py).upgrade_as::<PyCache>() else {
+                        return false;
+                    };
+                    let emitted = cache
+                        .get()
+                        .stats(py)
+                        .and_then(|stats| callback.call1(py, (stats,)));
+                    if let Err(err) = emitted {
+                        py_log::log(
+                            py_log::WARNING,
+                            &format!("Emitting cache stats failed: {}", err),
+                        );
+                    }
+                    true
+                });
+                if !running {
+                    return;
+                }
+            });
+        Ok(())
+    }
+
+    #[pyo3(signature = (keys, timeout=None, fraction=1.0))]
+    fn await_warm(
+        &self,
+        py: Python<'_>,
+        keys: Vec<String>,
+        timeout: Option<f64>,
+        fraction: f64,
+    ) -> PyResult<bool> {
+        self.ensure_open()?;
+        let keys = keys
+            .iter()
+            .map(|key| Ok(self.canonical_key(py, key)?.into_owned()))
+            .collect::<PyResult<Vec<_>>>()?;
+        let required = (keys.len() as f64 * fraction.clamp(0.0, 1.0)).ceil() as usize;
+        let deadline = optional_secs(timeout)?.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let now = Instant::now();
+            let degraded = self.degraded.load(Ordering::SeqCst);
+            let mut warm = 0;
+            let mut flights = Vec::new();
+            let mut unstarted = false;
+            for key in &keys {
+                let cache = self.cache.read(key).expect("Unable to lock cache!");
+                match cache.get(key) {
+                    Some(state) if state.is_fresh(now, degraded) => warm += 1,
+                    Some(state @ PyEntryState::Pending(lock_var)) if !state.is_ready() => {
+                        flights.push(lock_var.clone())
+                    }
+                    _ => unstarted = true,
+                }
+            }
+
+            if warm >= required {
+                return Ok(true);
+            }
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Ok(false);
+            }
+            // Keys without a flight have nothing to wait on, so they are
+            // looked at again after a while
+            let wake_at = if unstarted {
+                let recheck = now + WARM_POLL_INTERVAL;
+                Some(deadline.map_or(recheck, |deadline| deadline.min(recheck)))
+            } else {
+                deadline
+            };
+            match flights.first() {
+                Some(flight) => {
+                    let (lock, cvar) = &**flight;
+                    if wait_while(py, lock, cvar, wake_at, in_flight)? {
+                        cvar.pass_on(self.config.wake);
+                    }
+                }
+                None => {
+                    let left = wake_at.map_or(WARM_POLL_INTERVAL, |wake_at| {
+                        wake_at.saturating_duration_since(now)
+                    });
+                    py.allow_threads(|| thread::sleep(left));
+                    py.check_signals()?;
+                }
+            }
+        }
+    }
+
+    // Removes every ready entry and returns how many; flights in progress
+    // are left to finish
+    fn clear(&self) -> usize {
+        let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+        cache
+            .retain(|key, state| {
+                if !state.is_ready() {
+                    return true;
+                }
+                log_removal(self.removal_log(), key, state, "clear");
+                false
+            })
+            .len()
+    }
+
+    // For configuration reloads: waits for the flights in progress to
+    // finish, then empties the cache. `timeout` is in seconds; returns
+    // false, leaving the cache as it is, if they did not finish in time.
+    #[pyo3(signature = (timeout=None))]
+    fn drain(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<bool> {
+        let deadline = timeout
+            .map(timeout_from_secs)
+            .transpose()?
+            .flatten()
+            .map(|timeout| Instant::now() + timeout);
+        let flights: Vec<_> = self
+            .cache
+            .lock_all()
+            .expect("Unable to lock cache!")
+            .values()
+            .filter(|state| !state.is_ready())
+            .map(|PyEntryState::Pending(lock_var)| lock_var.clone())
+            .collect();
+        for flight in flights {
+            let (lock, cvar) = &*flight;
+            if !wait_while(py, lock, cvar, deadline, in_flight)? {
+                return Ok(false);
+            }
+            cvar.pass_on(self.config.wake);
+        }
+
+        let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+        cache.retain(|key, state| {
+            log_removal(self.removal_log(), key, state, "drain");
+            false
+        });
+        Ok(true)
+    }
+
+    fn expiry_forecast<'py>(
+        &self,
+        py: Python<'py>,
+        buckets: usize,
+        interval: f64,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let interval = duration_from_secs(interval)?.max(Duration::from_millis(1));
+        let now = Instant::now();
+        let mut counts = vec![0usize; buckets];
+        let mut later = 0usize;
+        let mut never = 0usize;
+
+        let cache = self.cache.lock_all().expect("Unable to lock cache!");
+        for state in cache.values() {
+            let PyEntryState::Pending(lock_var) = state;
+            let entry = lock_var.0.lock().unwrap();
+            match entry.expires_at {
+                None => never += 1,
+                Some(expires_at) => {
+                    let remaining = expires_at.saturating_duration_since(now);
+                    let bucket = remaining.div_duration_f64(interval) as usize;
+                    match counts.get_mut(bucket) {
+                        Some(count) => *count += 1,
+                        None => later += 1,
+                    }
+                }
+            }
+        }
+        drop(cache);
+
+        let forecast = PyDict::new(py);
+        forecast.set_item("interval", interval.as_secs_f64())?;
+        forecast.set_item("buckets", counts)?;
+        forecast.set_item("later", later)?;
+        forecast.set_item("never", never)?;
+        Ok(forecast)
+    }
+
+    // Entries removed lately, newest first, with why and when they went
+    #[pyo3(signature = (key=None))]
+    fn recently_removed<'py>(
+        &self,
+        py: Python<'py>,
+        key: Option<&str>,
+    ) -> PyResult<Bound<'py, PyList>> {
+        match &self.removals {
+            Some(removals) => removals.to_list(py, key),
+            None => Err(PyValueError::new_err(
+                "The removal log is disabled, construct the cache with removal_log",
+            )),
+        }
+    }
+
+    #[pyo3(signature = (format="csv"))]
+    fn export_trace<'py>(&self, py: Python<'py>, format: &str) -> PyResult<Bound<'py, PyAny>> {
+        match &self.trace {
+            Some(trace) => trace.export(py, format),
+            None => Err(PyValueError::new_err(
+                "Access tracing is disabled, construct the cache with trace_capacity",
+            )),
+        }
+    }
+
+    #[pyo3(signature = (include_values=false))]
+    fn to_arrow<'py>(&self, py: Python<'py>, include_values: bool) -> PyResult<Bound<'py, PyAny>> {
+        let now = Instant::now();
+        let unix_now = unix_now();
+        let mut keys = Vec::new();
+        let mut namespaces = Vec::new();
+        let mut created_at = Vec::new();
+        let mut ttls = Vec::new();
+        let mut hits = Vec::new();
+        let mut weights = Vec::new();
+        let mut values = Vec::new();
+
+        let cache = self.cache.lock_all().expect("Unable to lock cache!");
+        for (key, state) in cache.iter() {
+            let PyEntryState::Pending(lock_var) = state;
+            let entry = lock_var.0.lock().unwrap();
+            keys.push(key.to_string());
+            namespaces.push(entry.tenant.as_ref().map(|tenant| tenant.name.clone()));
+            created_at.push(
+                unix_now
+                    - now
+                        .saturating_duration_since(entry.created_at)
+                        .as_secs_f64(),
+            );
+            ttls.push(
+                entry
+                    .expires_at
+                    .map(|expires_at| expires_at.saturating_duration_since(now).as_secs_f64()),
+            );
+            hits.push(entry.hits);
+            weights.push(entry.weight);
+            if include_values {
+                values.push(entry.value.as_ref().map(|value| value.clone_ref(py)));
+            }
+        }
+        drop(cache);
+
+        let columns = PyDict::new(py);
+        columns.set_item("key", keys)?;
+        columns.set_item("namespace", namespaces)?;
+        columns.set_item("created_at", created_at)?;
+        columns.set_item("ttl", ttls)?;
+        columns.set_item("hits", hits)?;
+        columns.set_item("weight", weights)?;
+        if include_values {
+            let pickle = py.import("pickle")?;
+            let values = values
+                .iter()
+                .map(|value| {
+                    value
+                        .as_ref()
+                        .map(|value| pickle.call_method1("dumps", (value,)))
+                        .transpose()
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            columns.set_item("value", values)?;
+        }
+        py.import("pyarrow")?
+            .getattr("RecordBatch")?
+            .call_method1("from_pydict", (columns,))
+    }
+
+    // Keys and bookkeeping only, values never leave the process
+    fn metadata_snapshot<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let entries = PyList::empty(py);
+        for summary in self.summaries(py, false) {
+            if !summary.ready {
+                continue;
+            }
+            let metadata = PyDict::new(py);
+            metadata.set_item("key", summary.key)?;
+            metadata.set_item("namespace", summary.namespace)?;
+            metadata.set_item("weight", summary.weight)?;
+            metadata.set_item("hits", summary.hits)?;
+            metadata.set_item("age", summary.age)?;
+            metadata.set_item("ttl", summary.ttl)?;
+            entries.append(metadata)?;
+        }
+
+        let snapshot = PyDict::new(py);
+        snapshot.set_item("format", METADATA_FORMAT)?;
+        snapshot.set_item("version", METADATA_VERSION)?;
+        snapshot.set_item("taken_at", unix_now())?;
+        snapshot.set_item("entries", entries)?;
+        if let Some(popularity) = &self.popularity {
+            snapshot.set_item("popularity", popularity.to_dict(py)?)?;
+        }
+        Ok(snapshot)
+    }
+
+    // Carries key popularity over from a metadata snapshot taken before a
+    // restart; returns false if the snapshot has none to offer
+    fn restore_popularity(&self, snapshot: &Bound<'_, PyDict>) -> PyResult<bool> {
+        let Some(popularity) = &self.popularity else {
+            return Err(PyValueError::new_err(
+                "Popularity is not tracked, create the cache with track_popularity=True",
+            ));
+        };
+        let snapshot = migrate_snapshot(snapshot)?;
+        let Some(sketch) = get_option::<Bound<'_, PyDict>>(&snapshot, "popularity")? else {
+            return Ok(false);
+        };
+        popularity.restore(&PopularitySketch::from_dict(&sketch)?)?;
+        Ok(true)
+    }
+
+    #[pyo3(signature = (predicate_spec=None, action="count"))]
+    fn for_each_entry_rust<'py>(
+        &self,
+        py: Python<'py>,
+        predicate_spec: Option<&Bound<'py, PyDict>>,
+        action: &str,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let filter = EntryFilter::from_spec(predicate_spec)?;
+        let cache = &self.cache;
+        let removals = self.removal_log();
+
+        match action {
+            "count" => {
+                let count = py.allow_threads(|| {
+                    let now = Instant::now();
+                    let cache = cache.lock_all().expect("Unable to lock cache!");
+                    cache
+                        .iter()
+                        .filter(|(key, state)| state.matches(&filter, key, now))
+                        .count()
+                });
+                Ok(count.into_pyobject(py)?.into_any())
+            }
+            "export" => {
+                let keys = py.allow_threads(|| {
+                    let now = Instant::now();
+                    let cache = cache.lock_all().expect("Unable to lock cache!");
+                    cache
+                        .iter()
+                        .filter(|(key, state)| state.matches(&filter, key, now))
+                        .map(|(key, _)| key.to_string())
+                        .collect::<Vec<_>>()
+                });
+                keys.into_pyobject(py)
+            }
+            "evict" => {
+                // Pending entries are never evicted from under their waiters
+                let evicted = py.allow_threads(|| {
+                    let now = Instant::now();
+                    let mut cache = cache.lock_all().expect("Unable to lock cache!");
+                    cache.retain(|key, state| {
+                        if !(state.is_ready() && state.matches(&filter, key, now)) {
+                            return true;
+                        }
+                        log_removal(removals, key, state, "evict");
+                        false
+                    })
+                });
+                Ok(evicted.len().into_pyobject(py)?.into_any())
+            }
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown action '{}', expected 'count', 'export' or 'evict'",
+                action
+            ))),
+        }
+    }
+
+    // Zeroes the counters in `stats()`; gauges such as the entry count and
+    // memory in use are left alone
+    pub(crate) fn reset_stats(&self) {
+        self.stats.reset();
+        self.memory.sync_evictions.store(0, Ordering::Relaxed);
+        self.memory.background_evictions.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let cache = self.cache.lock_all().expect("Unable to lock cache!");
+        let entries = cache.len();
+        let key_bytes = cache.key_bytes();
+        drop(cache);
+
+        let stats = PyDict::new(py);
+        stats.set_item("shards", self.cache.shards())?;
+        stats.set_item("entries", entries)?;
+        stats.set_item("key_bytes", key_bytes)?;
+        stats.set_item("call_threads", self.calls.started())?;
+        stats.set_item("hits", self.stats.hits.load(Ordering::Relaxed))?;
+        stats.set_item("misses", self.stats.misses.load(Ordering::Relaxed))?;
+        stats.set_item(
+            "coalesced_waits",
+            self.stats.coalesced_waits.load(Ordering::Relaxed),
+        )?;
+        // Waiters that gave up after wait_timeout
+        stats.set_item("timeouts", self.stats.wait_timeouts.load(Ordering::Relaxed))?;
+        stats.set_item(
+            "evictions",
+            self.stats.size_evictions.load(Ordering::Relaxed)
+                + self.memory.sync_evictions.load(Ordering::Relaxed)
+                + self.memory.background_evictions.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "abandoned_waits",
+            self.stats.abandoned_waits.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "unconsumed_results",
+            self.stats.unconsumed_results.load(Ordering::Relaxed),
+        )?;
+        stats.set_item("retries", self.stats.retries.load(Ordering::Relaxed))?;
+        stats.set_item(
+            "quota_exceeded",
+            self.stats.quota_exceeded.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "func_mismatches",
+            self.stats.func_mismatches.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "write_conflicts",
+            self.stats.write_conflicts.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "inflight_alarms",
+            self.stats.inflight_alarms.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "compute_overruns",
+            self.stats.compute_overruns.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "failed_calls",
+            self.stats.failed_calls.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "elections_lost",
+            self.stats.elections_lost.load(Ordering::Relaxed),
+        )?;
+        stats.set_item("handoffs", self.stats.handoffs.load(Ordering::Relaxed))?;
+        stats.set_item("degraded", self.degraded.load(Ordering::Relaxed))?;
+        stats.set_item("enabled", self.enabled.load(Ordering::Relaxed))?;
+        stats.set_item(
+            "bypassed_calls",
+            self.stats.bypassed_calls.load(Ordering::Relaxed),
+        )?;
+        let fallback = PyDict::new(py);
+        fallback.set_item("stale", self.stats.fallback_stale.load(Ordering::Relaxed))?;
+        fallback.set_item(
+            "default",
+            self.stats.fallback_default.load(Ordering::Relaxed),
+        )?;
+        fallback.set_item(
+            "timeouts",
+            self.stats.fallback_timeouts.load(Ordering::Relaxed),
+        )?;
+        stats.set_item("fallback", fallback)?;
+        stats.set_item(
+            "degraded_misses",
+            self.stats.degraded_misses.load(Ordering::Relaxed),
+        )?;
+        stats.set_item("hooks", self.hooks.to_dict(py)?)?;
+        stats.set_item(
+            "scheduled_invalidations",
+            self.scheduler.fired.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "access_denied",
+            self.stats.access_denied.load(Ordering::Relaxed),
+        )?;
+        let depth = PyDict::new(py);
+        for (lane, jobs) in self.refresh.depths() {
+            depth.set_item(lane, jobs)?;
+        }
+        let refresh = PyDict::new(py);
+        refresh.set_item("depth", depth)?;
+        refresh.set_item("refreshed", self.refresh.completed())?;
+        refresh.set_item("unchanged", self.refresh.unchanged())?;
+        refresh.set_item("latency_mean", self.refresh.mean_latency())?;
+        refresh.set_item("latency_max", self.refresh.max_latency())?;
+        refresh.set_item(
+            "failing",
+            self.refresh
+                .failing()
+                .into_iter()
+                .collect::<HashMap<_, _>>(),
+        )?;
+        stats.set_item("refresh_queue", refresh)?;
+        let budget = PyDict::new(py);
+        budget.set_item("used", self.memory.used())?;
+        budget.set_item("soft_limit", self.memory.soft_limit)?;
+        budget.set_item("hard_limit", self.memory.hard_limit)?;
+        budget.set_item("pressure", self.memory.pressure())?;
+        budget.set_item(
+            "background_evictions",
+            self.memory.background_evictions.load(Ordering::Relaxed),
+        )?;
+        budget.set_item(
+            "sync_evictions",
+            self.memory.sync_evictions.load(Ordering::Relaxed),
+        )?;
+        stats.set_item("memory", budget)?;
+        stats.set_item("leases", self.leases.len())?;
+        stats.set_item(
+            "forked_flights_dropped",
+            self.stats.forked_flights_dropped.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "size_evictions",
+            self.stats.size_evictions.load(Ordering::Relaxed),
+        )?;
+        stats.set_item(
+            "orphaned_pendings_reclaimed",
+            self.stats
+                .orphaned_pendings_reclaimed
+                .load(Ordering::Relaxed),
+        )?;
+        Ok(stats)
+    }
+
+    // Keys of the flights in progress, including ones opened with `start`
+    fn pending_keys(&self, py: Python<'_>) -> Vec<String> {
+        self.keys_where(py, false)
+    }
+
+    // Keys holding a value, expired ones included unlike `keys()`
+    fn ready_keys(&self, py: Python<'_>) -> Vec<String> {
+        self.keys_where(py, true)
+    }
+
+    // One dict per entry with its state, for diagnosing stuck requests:
+    // "pending", "handoff" once its leader failed and a waiter is taking
+    // over, "overrun" past compute_timeout, "ready" or "expired"
+    fn inspect<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let entries = PyList::empty(py);
+        for summary in self.summaries(py, false) {
+            let item = PyDict::new(py);
+            item.set_item("key", summary.key)?;
+            item.set_item("state", summary.state)?;
+            item.set_item("age", summary.age)?;
+            item.set_item("waiters", summary.waiters)?;
+            item.set_item("leader", summary.leader)?;
+            item.set_item("external", summary.external)?;
+            entries.append(item)?;
+        }
+        Ok(entries)
+    }
+
+    #[pyo3(signature = (max_entries=20))]
+    fn debug_dump<'py>(&self, py: Python<'py>, max_entries: usize) -> PyResult<Bound<'py, PyDict>> {
+        let pending = PyList::empty(py);
+        let sample = PyList::empty(py);
+        for summary in self.summaries(py, true) {
+            if !summary.ready {
+                let flight = PyDict::new(py);
+                flight.set_item("key", summary.key)?;
+                flight.set_item("waiting_for", summary.age)?;
+                flight.set_item("waiters", summary.waiters)?;
+                flight.set_item("meta", summary.meta)?;
+                pending.append(flight)?;
+            } else if sample.len() < max_entries {
+                let value_type = summary
+                    .value
+                    .as_ref()
+                    .map(|value| value.bind(py).get_type().name())
+                    .transpose()?;
+                let item = PyDict::new(py);
+                item.set_item("key", summary.key)?;
+                item.set_item("age", summary.age)?;
+                item.set_item("hits", summary.hits)?;
+                item.set_item("weight", summary.weight)?;
+                item.set_item("ttl", summary.ttl)?;
+                item.set_item("namespace", summary.namespace)?;
+                item.set_item("value_type", value_type)?;
+                sample.append(item)?;
+            }
+        }
+
+        let dump = PyDict::new(py);
+        dump.set_item("config", self.config.to_dict(py)?)?;
+        dump.set_item("stats", self.stats(py)?)?;
+        dump.set_item("health", self.health(py)?)?;
+        dump.set_item("pending", pending)?;
+        dump.set_item("sample", sample)?;
+        Ok(dump)
+    }
+
+    fn validate<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let mut report = ValidationReport::default();
+
+        let hooks = [
+            ("retry_predicate", &self.config.retry_predicate),
+            ("on_error", &self.config.on_error),
+            ("post_process", &self.config.post_process),
+            ("check_access", &self.config.check_access),
+            ("on_quota_exceeded", &self.config.on_quota_exceeded),
+            ("on_hit", &self.config.on_hit),
+            ("on_evict", &self.config.on_evict),
+            ("on_miss", &self.config.on_miss),
+            ("on_leader_start", &self.config.on_leader_start),
+            ("on_leader_done", &self.config.on_leader_done),
+            ("on_timeout", &self.config.on_timeout),
+            ("on_hook_error", &self.config.on_hook_error),
+        ];
+        for (name, hook) in hooks {
+            match hook {
+                Some(hook) if !hook.bind(py).is_callable() => {
+                    report.fail(name, "hook is not callable".to_string())
+                }
+                Some(_) => report.pass(name),
+                None => report.skip(name, "not configured"),
+            }
+        }
+        for (name, state) in self.supervisor.components() {
+            if state.alive.load(Ordering::SeqCst) {
+                report.pass(name);
+            } else if state.stopped.load(Ordering::SeqCst) {
+                report.skip(name, "background thread finished");
+            } else {
+                report.fail(name, "background thread is not running".to_string());
+            }
+        }
+
+        // Where the probe's value does not stay in the cache, there is
+        // nothing to hit, expire or drop
+        let not_stored = if !self.config.store_results {
+            Some("store_results is off")
+        } else if self.degraded.load(Ordering::SeqCst) {
+            Some("cache is degraded")
+        } else if self.bypassed(VALIDATE_KEY) {
+            Some("cache is disabled")
+        } else {
+            None
+        };
+        let probe = Probe::start();
+        let func = py.get_type::<PyDict>().into_any().unbind();
+        let call = |expires_at: Option<Instant>| {
+            self.call(
+                py,
+                func.clone_ref(py),
+                PyTuple::empty(py).into_any().unbind(),
+                PyDict::new(py).into_any().unbind(),
+                VALIDATE_KEY,
+                CallOptions {
+                    expires_at,
+                    ..Default::default()
+                },
+            )
+        };
+        let sample = PyDict::new(py).into_any().unbind();
+        let first = match self.post_process(py, sample, None, None) {
+            Ok(_) => match call(None) {
+                Ok(first) => {
+                    report.pass("compute");
+                    Some(first)
+                }
+                Err(err) => {
+                    report.fail("compute", err.to_string());
+                    None
+                }
+            },
+            Err(err) => {
+                report.fail("compute", format!("post_process failed: {}", err));
+                None
+            }
+        };
+        let skip_reason = match (&first, not_stored) {
+            (None, _) => Some("compute failed"),
+            (Some(_), reason) => reason,
+        };
+        if let (Some(first), None) = (&first, skip_reason) {
+            match call(None) {
+                Ok(second) if second.is(first) => report.pass("hit"),
+                Ok(_) => report.fail("hit", "second call recomputed".to_string()),
+                Err(err) => report.fail("hit", err.to_string()),
+            }
+
+            // Dropped through `drop_prefix`, since partitioned caches store
+            // the key with a function suffix; the next call has to recompute
+            self.drop_prefix(VALIDATE_KEY)?;
+            let expires_at = self.config.ttl.map(|_| Instant::now() + VALIDATE_EXPIRY);
+            let recomputed = call(expires_at);
+            match &recomputed {
+                Ok(third) if !third.is(first) => report.pass("evict"),
+                Ok(_) => report.fail("evict", "value still served after drop".to_string()),
+                Err(err) => report.fail("evict", err.to_string()),
+            }
+
+            match (self.config.ttl, recomputed) {
+                (None, _) => report.skip("expire", "no ttl configured"),
+                (Some(_), Ok(third)) => {
+                    py.allow_threads(|| thread::sleep(VALIDATE_EXPIRY * 2));
+                    match call(None) {
+                        Ok(fourth) if !fourth.is(&third) => report.pass("expire"),
+                        Ok(_) => {
+                            report.fail("expire", "value still served after expiry".to_string())
+                        }
+                        Err(err) => report.fail("expire", err.to_string()),
+                    }
+                }
+                (Some(_), Err(_)) => report.skip("expire", "evict failed"),
+            }
+            self.drop_prefix(VALIDATE_KEY)?;
+        } else if let Some(reason) = skip_reason {
+            report.skip("hit", reason);
+            report.skip("evict", reason);
+            report.skip("expire", reason);
+        }
+        drop(probe);
+        report.skip("remote", "no remote tier configured");
+
+        report.into_dict(py)
+    }
+
+    fn health<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        // Everything is read first and the dicts are built once the locks
+        // are released
+        let counts = self.cache.lock_all().ok().map(|entries| {
+            let pending = entries.values().filter(|state| !state.is_ready()).count();
+            (entries.len(), pending)
+        });
+        let states: Vec<_> = self
+            .supervisor
+            .components()
+            .into_iter()
+            .map(|(name, state)| {
+                let last_error = state.last_error.lock().unwrap().clone();
+                (
+                    name,
+                    state.alive.load(Ordering::SeqCst),
+                    state.stopped.load(Ordering::SeqCst),
+                    state.restarts.load(Ordering::SeqCst),
+                    last_error,
+                )
+            })
+            .collect();
+        let used = self.memory.used();
+        let failed_calls = self.stats.failed_calls.load(Ordering::Relaxed);
+        let misses = self.stats.misses.load(Ordering::Relaxed);
+        let (hook_errors, hook_calls) = self.hooks.calls();
+
+        // A component that finished on purpose is stopped, not dead
+        let mut ready = true;
+        let components = PyDict::new(py);
+        for (name, alive, stopped, restarts, last_error) in states {
+            ready &= alive || stopped;
+            let component = PyDict::new(py);
+            component.set_item("alive", alive)?;
+            component.set_item("stopped", stopped)?;
+            component.set_item("restarts", restarts)?;
+            component.set_item("last_error", last_error)?;
+            components.set_item(name, component)?;
+        }
+
+        let cache = PyDict::new(py);
+        match counts {
+            Some((entries, pending)) => {
+                cache.set_item("reachable", true)?;
+                cache.set_item("entries", entries)?;
+                cache.set_item("pending", pending)?;
+            }
+            None => {
+                ready = false;
+                cache.set_item("reachable", false)?;
+            }
+        }
+
+        let memory = PyDict::new(py);
+        memory.set_item("used", used)?;
+        memory.set_item("max_memory", self.config.max_memory)?;
+        memory.set_item(
+            "usage",
+            self.config
+                .max_memory
+                .map(|max_memory| rate(used as u64, max_memory as u64)),
+        )?;
+        memory.set_item("pressure", self.memory.pressure())?;
+
+        let health = PyDict::new(py);
+        health.set_item("ready", ready)?;
+        health.set_item("components", components)?;
+        health.set_item("cache", cache)?;
+        health.set_item("memory", memory)?;
+        // Computations that raised, against all computations started
+        health.set_item("failed_call_rate", rate(failed_calls, misses))?;
+        health.set_item("hook_error_rate", rate(hook_errors, hook_calls))?;
+        Ok(health)
+    }
+}
+
+impl PyCache {
+    pub(crate) fn with_config(config: CacheConfig) -> Self {
+        let cache = Arc::new(ShardedKeyMap::new(config.shards));
+        let supervisor = Arc::new(Supervisor::new(config.threads.clone()));
+        let stats = Arc::new(CacheStats::default());
+        let on_hook_error = config
+            .on_hook_error
+            .as_ref()
+            .map(|hook| Python::with_gil(|py| hook.clone_ref(py)));
+        let hooks = Arc::new(HookDispatcher::start(
+            &supervisor,
+            config.hook_queue_size,
+            on_hook_error,
+            config.disable_hook_after,
+        ));
+
+        if let Some(alarm) = config.max_inflight_alarm {
+            let weak_cache = Arc::downgrade(&cache);
+            let alarm_stats = stats.clone();
+            let on_inflight_alarm = config
+                .on_inflight_alarm
+                .as_ref()
+                .map(|hook| Python::with_gil(|py| hook.clone_ref(py)));
+            let alarm = alarm.max(Duration::from_millis(1));
+            supervisor.spawn("inflight-alarm", move |shutdown| loop {
+                if !shutdown.sleep((alarm / 2).min(ALARM_POLL_INTERVAL)) {
+                    return;
+                }
+                let Some(cache) = weak_cache.upgrade() else {
+                    return;
+                };
+                for (key, leader, waiters, elapsed) in overdue_flights(&cache, alarm) {
+                    alarm_stats.inflight_alarms.fetch_add(1, Ordering::Relaxed);
+                    Python::with_gil(|py| {
+                        let fired = on_inflight_alarm.as_ref().map(|hook| {
+                            hook.call1(py, (&key, &leader, waiters, elapsed.as_secs_f64()))
+                        });
+                        if fired.is_none_or(|result| result.is_err()) {
+                            py_log::log(
+                                py_log::WARNING,
+                                &format!(
+                                    "Cache entry '{}' has been in flight for {:?} (leader {}, {} waiters)",
+                                    key,
+                                    elapsed,
+                                    leader.as_deref().unwrap_or("unknown"),
+                                    waiters
+                                ),
+                            );
+                        }
+                    });
+                }
+            });
+        }
+
+        if let Some(compute_timeout) = config.compute_timeout {
+            let weak_cache = Arc::downgrade(&cache);
+            let watchdog_stats = stats.clone();
+            let compute_timeout = compute_timeout.max(Duration::from_millis(1));
+            let wake = config.wake;
+            supervisor.spawn("compute-watchdog", move |shutdown| loop {
+                if !shutdown.sleep((compute_timeout / 2).min(ALARM_POLL_INTERVAL)) {
+                    return;
+                }
+                let Some(cache) = weak_cache.upgrade() else {
+                    return;
+                };
+                let overrun = cache
+                    .lock_all()
+                    .expect("Unable to lock cache!")
+                    .retain(|_, state| !fail_overrun(state, compute_timeout, wake));
+                watchdog_stats
+                    .compute_overruns
+                    .fetch_add(overrun.len() as u64, Ordering::Relaxed);
+            });
+        }
+
+        let memory = Arc::new(MemoryBudget::new(config.soft_memory, config.max_memory));
+        let removals = config
+            .removal_log
+            .map(|capacity| Arc::new(RemovalLog::new(capacity)));
+        if let Some(soft_limit) = config.soft_memory {
+            let weak_cache = Arc::downgrade(&cache);
+            let evictor_memory = memory.clone();
+            let evictor_hooks = hooks.clone();
+            let evictor_removals = removals.clone();
+            let on_evict = config
+                .on_evict
+                .as_ref()
+                .map(|hook| Python::with_gil(|py| hook.clone_ref(py)));
+            supervisor.spawn("evictor", move |shutdown| loop {
+                if !shutdown.sleep(EVICT_POLL_INTERVAL) {
+                    return;
+                }
+                let Some(cache) = weak_cache.upgrade() else {
+                    return;
+                };
+                if evictor_memory.over_soft() {
+                    let mut cache = cache.lock_all().expect("Unable to lock cache!");
+                    let evicted = trim(
+                        &mut cache,
+                        &evictor_memory,
+                        soft_limit,
+                        evictor_removals.as_deref(),
+                    );
+                    drop(cache);
+                    evictor_memory
+                        .background_evictions
+                        .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+                    if let Some(on_evict) = &on_evict {
+                        Python::with_gil(|py| {
+                            notify_evicted(py, &evictor_hooks, on_evict, evicted, "memory")
+                        });
+                    }
+                }
+            });
+        }
+
+        let config = Arc::new(config);
+        let refresh = Arc::new(RefreshQueue::default());
+
+        Self {
+            cache,
+            supervisor,
+            trace: config.trace_capacity.map(AccessTrace::new),
+            stats,
+            tenants: Mutex::new(HashMap::new()),
+            refresh,
+            memory,
+            scheduler: Arc::new(Scheduler::default()),
+            scheduler_started: Once::new(),
+            sweeper_started: Once::new(),
+            refresher_started: Once::new(),
+            degraded: Arc::new(AtomicBool::new(false)),
+            degraded_default: Mutex::new(None),
+            enabled: AtomicBool::new(true),
+            closed: AtomicBool::new(false),
+            disabled_namespaces: Mutex::new(HashSet::new()),
+            hooks,
+            generation: AtomicU64::new(fork::generation()),
+            leases: LeaseTable::default(),
+            popularity: config
+                .track_popularity
+                .then(|| PopularitySketch::new(DEFAULT_WIDTH)),
+            removals,
+            stripes: config
+                .lock_stripes
+                .map(|stripes| Arc::new(LockStripes::new(stripes))),
+            calls: Arc::new(CallPool::new(config.call_workers)),
+            config,
+        }
+    }
+
+    // Reclaims pending entries whose leader went away without resolving
+    fn start_sweeper(&self) {
+        self.sweeper_started.call_once(|| {
+            let weak_cache = Arc::downgrade(&self.cache);
+            let sweeper_stats = self.stats.clone();
+            let sweep_interval = self.config.sweep_interval.max(Duration::from_millis(1));
+            self.supervisor.spawn("sweeper", move |shutdown| loop {
+                if !shutdown.sleep(sweep_interval) {
+                    return;
+                }
+                let Some(cache) = weak_cache.upgrade() else {
+                    return;
+                };
+                let reclaimed = cache
+                    .lock_all()
+                    .expect("Unable to lock cache!")
+                    .retain(|_, state: &PyEntryState| !state.is_orphaned());
+                sweeper_stats
+                    .orphaned_pendings_reclaimed
+                    .fetch_add(reclaimed.len() as u64, Ordering::Relaxed);
+            });
+        });
+    }
+
+    fn start_refresher(&self) {
+        self.refresher_started.call_once(|| {
+            let weak_cache = Arc::downgrade(&self.cache);
+            let refresher_queue = self.refresh.clone();
+            let refresher_config = self.config.clone();
+            let refresher_memory = self.memory.clone();
+            let refresher_stripes = self.stripes.clone();
+            self.supervisor.spawn("refresher", move |shutdown| loop {
+                let job = refresher_queue.pop(REFRESH_POLL_INTERVAL);
+                if shutdown.requested() {
+                    return;
+                }
+                let Some(cache) = weak_cache.upgrade() else {
+                    return;
+                };
+                let Some(job) = job else {
+                    continue;
+                };
+                let result = Python::with_gil(|py| {
+                    refresh_entry(
+                        py,
+                        &cache,
+                        &refresher_config,
+                        &refresher_memory,
+                        refresher_stripes.as_deref(),
+                        &job,
+                    )
+                    .map_err(|err| err.to_string())
+                });
+                match result {
+                    Ok(RefreshOutcome::Skipped) => refresher_queue.forget(&job.key),
+                    Ok(outcome) => refresher_queue.complete(&job, outcome),
+                    Err(err) => {
+                        let message = format!(
+                            "Refreshing cache entry '{}' ({} priority) failed: {}",
+                            job.key,
+                            job.lane.as_str(),
+                            err
+                        );
+                        let failures = refresher_queue.retry_later(job);
+                        py_log::log(
+                            py_log::WARNING,
+                            &format!("{} ({} consecutive failures)", message, failures),
+                        );
+                    }
+                }
+            });
+        });
+    }
+
+    // Runs `call` on the cache's call pool, reporting through the returned
+    // completion
+    pub(crate) fn spawn_call(
+        cache: Py<Self>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+        options: CallOptions,
+    ) -> PyResult<Arc<Completion>> {
+        let completion = Arc::new(Completion::default());
+        let outcome = completion.clone();
+        let calls = cache.get().calls.clone();
+        calls.submit(move || {
+            let result =
+                Python::with_gil(|py| cache.get().call(py, py_func, args, kwargs, &key, options));
+            outcome.complete(result);
+        })?;
+        Ok(completion)
+    }
+
+    // Asks the flight for `key` to stop through its cancel token, unless
+    // other callers are still waiting on it
+    pub(crate) fn cancel_flight(&self, py: Python<'_>, key: &str) -> bool {
+        let Ok(key) = self.canonical_key(py, key) else {
+            return false;
+        };
+        let cache = self.cache.read(&key).expect("Unable to lock cache!");
+        let Some(PyEntryState::Pending(lock_var)) = cache.get(&key) else {
+            return false;
+        };
+        let entry = lock_var.0.lock().unwrap();
+        if entry.ready || entry.waiters > entry.abandoned {
+            return false;
+        }
+        entry.token.get().cancel();
+        true
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn call(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: &str,
+        options: CallOptions,
+    ) -> PyResult<Py<PyAny>> {
+        let mut info = CallInfo::default();
+        self.call_with_info(py, py_func, args, kwargs, key, options, &mut info)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn call_with_info(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: &str,
+        mut options: CallOptions,
+        info: &mut CallInfo,
+    ) -> PyResult<Py<PyAny>> {
+        self.ensure_open()?;
+        // Tenant views canonicalize keys before adding their prefix
+        let key = match options.tenant {
+            Some(_) => Cow::Borrowed(key),
+            None => self.canonical_key(py, key)?,
+        };
+        let key = key.as_ref();
+        self.after_fork();
+        let func_id = self
+            .config
+            .on_func_mismatch
+            .map(|_| func_fingerprint(py_func.bind(py)));
+        let partitioned;
+        let key = match func_id {
+            Some(func_id) if self.config.on_func_mismatch == Some(MismatchPolicy::Partition) => {
+                partitioned = format!("{}#{:016x}", key, func_id);
+                partitioned.as_str()
+            }
+            _ => key,
+        };
+
+        let wait_timeout = options.wait_timeout.unwrap_or(self.config.wait_timeout);
+        // Hits only borrow `meta`; it is handed to the entry on a miss
+        let meta = options.meta.take();
+        let meta = meta.as_ref();
+
+        if self.bypassed(key) {
+            self.counters()
+                .bypassed_calls
+                .fetch_add(1, Ordering::Relaxed);
+            let token = Py::new(py, CancelToken::default())?;
+            return self
+                .call_leader(py, &py_func, &args, &kwargs, key, &token, meta)
+                .and_then(|result| self.post_process(py, result, options.freeze, meta));
+        }
+
+        if let Some(popularity) = self.popularity.as_ref().filter(|_| !probing()) {
+            popularity.record(key);
+        }
+
+        if self.degraded.load(Ordering::SeqCst) {
+            return self.serve_degraded(py, key, &options, meta);
+        }
+
+        // Hold our own reference so the map lock is not kept while waiting;
+        // expired entries are replaced as if they were missing. Hits and
+        // joins only take the shard's read lock. A miss checks again under
+        // the write lock, since another caller may have started the flight.
+        let now = Instant::now();
+        let mut cached_value = live_entry(&self.cache.read(key).unwrap(), key, now);
+        let mut cache = match cached_value {
+            Some(_) => None,
+            None => {
+                let cache = self.cache.lock(key).unwrap();
+                cached_value = live_entry(&cache, key, now);
+                Some(cache)
+            }
+        };
+
+        if let Some(lock_var) = cached_value {
+            drop(cache);
+
+            let (lock, cvar) = &*lock_var;
+            let mut entry = lock.lock().unwrap();
+            if entry
+                .func_id
+                .zip(func_id)
+                .is_some_and(|(cached, requested)| cached != requested)
+            {
+                drop(entry);
+                self.func_mismatch(key)?;
+                entry = lock.lock().unwrap();
+            }
+            if entry.ready {
+                let (value, entry_tags) = entry.read(py, self.config.check_access.is_some());
+                drop(entry);
+                self.record(py, key, TraceKind::Hit, meta);
+                self.counters().hits.fetch_add(1, Ordering::Relaxed);
+                self.notify_hit(py, key, meta);
+                if let Some(tenant) = &options.tenant {
+                    tenant.hits.fetch_add(1, Ordering::Relaxed);
+                }
+                self.check_access(py, key, entry_tags, options.context.as_ref(), meta)?;
+                return Ok(value);
+            }
+            entry.waiters += 1;
+            drop(entry);
+            self.record(py, key, TraceKind::Wait, meta);
+            self.counters()
+                .coalesced_waits
+                .fetch_add(1, Ordering::Relaxed);
+
+            let waiting = Instant::now();
+            let deadline = wait_timeout.map(|wait_timeout| waiting + wait_timeout);
+            let mut entry = loop {
+                let resolved = match wait_resolved(py, lock, cvar, deadline) {
+                    Ok(resolved) => resolved,
+                    Err(interrupt) => {
+                        let mut entry = lock.lock().unwrap();
+                        entry.abandoned += 1;
+                        self.counters()
+                            .abandoned_waits
+                            .fetch_add(1, Ordering::Relaxed);
+                        if self.config.cancel_abandoned && entry.abandoned == entry.waiters {
+                            entry.token.get().cancel();
+                        }
+                        return Err(interrupt);
+                    }
+                };
+                let entry = lock.lock().unwrap();
+                if !resolved {
+                    break entry;
+                }
+                // Another waiter was promoted first, wait for its result
+                if !entry.ready && !entry.overrun && !entry.handoff && entry.error.is_none() {
+                    continue;
+                }
+                cvar.pass_on(self.config.wake);
+                break entry;
+            };
+            if entry.handoff {
+                entry.handoff = false;
+                entry.waiters -= 1;
+                let token = entry.token.clone_ref(py);
+                drop(entry);
+                info.leader = true;
+                self.notify_leader_start(py, key, meta);
+                return self.lead_flight(
+                    py,
+                    &py_func,
+                    &args,
+                    &kwargs,
+                    key,
+                    &lock_var,
+                    &token,
+                    options.tenant.clone(),
+                    meta,
+                    info,
+                );
+            }
+            if entry.overrun && !entry.ready {
+                drop(entry);
+                self.notify_timeout(py, key, waiting, meta);
+                return Err(ComputeTimeout::new_err(format!(
+                    "Computing cache entry '{}' exceeded compute_timeout",
+                    key
+                )));
+            }
+            if entry.ready {
+                info.shared = entry.served();
+                let (value, entry_tags) = entry.read(py, self.config.check_access.is_some());
+                drop(entry);
+                self.check_access(py, key, entry_tags, options.context.as_ref(), meta)?;
+                return Ok(value);
+            }
+            if let Some(err) = &entry.error {
+                return Err(err.clone_ref(py));
+            }
+            entry.abandoned += 1;
+            self.counters()
+                .abandoned_waits
+                .fetch_add(1, Ordering::Relaxed);
+            self.counters()
+                .wait_timeouts
+                .fetch_add(1, Ordering::Relaxed);
+            if self.config.cancel_abandoned && entry.abandoned == entry.waiters {
+                entry.token.get().cancel();
+            }
+            drop(entry);
+            self.notify_timeout(py, key, waiting, meta);
+            match self.config.timeout_policy {
+                TimeoutPolicy::Raise => {
+                    return Err(PyTimeoutError::new_err(format!(
+                        "Timed out after {:?} waiting for cache entry '{}'",
+                        waiting.elapsed(),
+                        key
+                    )))
+                }
+                TimeoutPolicy::Fallback => {
+                    return Ok(match &self.config.timeout_fallback {
+                        Some(fallback) => fallback.clone_ref(py),
+                        None => py.None(),
+                    })
+                }
+                TimeoutPolicy::Lead => {}
+            }
+            cache = Some(self.cache.lock(key).unwrap());
+        }
+        let mut cache = cache.expect("Missing cache lock!");
+        // Insert waiting state and drop call
+        self.record(py, key, TraceKind::Miss, meta);
+        self.counters().misses.fetch_add(1, Ordering::Relaxed);
+        info.leader = true;
+        let tenant = options.tenant.clone();
+        if let Some(tenant) = &tenant {
+            tenant.misses.fetch_add(1, Ordering::Relaxed);
+            if tenant.over_entries() {
+                let policy = tenant.policy();
+                // Evicting looks at every shard, which must not be locked
+                // while holding one of them
+                drop(cache);
+                let mut evicted = Vec::new();
+                if policy == QuotaPolicy::Evict {
+                    let mut all = self.cache.lock_all().expect("Unable to lock cache!");
+                    while tenant.over_entries() {
+                        let Some(key) = evict_lru(&mut all, tenant, self.removal_log()) else {
+                            break;
+                        };
+                        evicted.push(key);
+                    }
+                }
+                self.notify_evicted(py, evicted, "quota");
+                self.quota_exceeded(py, tenant, key, "entries", meta);
+                match policy {
+                    QuotaPolicy::Evict => cache = self.cache.lock(key).unwrap(),
+                    QuotaPolicy::Skip => {
+                        let token = Py::new(py, CancelToken::default())?;
+                        self.notify_miss(py, key, meta);
+                        let started = Instant::now();
+                        let result = self.lead(
+                            py,
+                            &py_func,
+                            &args,
+                            &kwargs,
+                            key,
+                            &token,
+                            options.freeze,
+                            meta,
+                        );
+                        self.notify_leader_done(py, key, started, result.is_ok(), meta);
+                        return result;
+                    }
+                    QuotaPolicy::Raise => {
+                        return Err(QuotaExceeded::new_err(format!(
+                            "Tenant '{}' exceeded its entry quota",
+                            tenant.name
+                        )))
+                    }
+                }
+            }
+        }
+        self.start_sweeper();
+        let token = Py::new(py, CancelToken::default())?;
+        options.meta = meta.map(|meta| meta.clone_ref(py));
+        let mut placeholder =
+            PyCacheEntry::pending(token.clone_ref(py), options, self.memory.clone());
+        placeholder.func_id = func_id;
+        if self.config.max_inflight_alarm.is_some() {
+            placeholder.leader = current_thread_name(py);
+        }
+        placeholder.provenance = self.provenance(py, placeholder.provenance.take());
+        placeholder.loader = Some(Loader {
+            func: py_func.clone_ref(py),
+            args: args.clone_ref(py),
+            kwargs: kwargs.clone_ref(py),
+        });
+        let notification = FlightSignal::new(self.stripes.as_deref(), key);
+        let pending_entry = Arc::new((Mutex::new(placeholder), notification));
+        // A ready entry only gets replaced here once its ttl ran out
+        let previous = cache.insert(key, PyEntryState::Pending(pending_entry.clone()));
+        if let Some(previous) = previous.filter(|previous| previous.is_ready()) {
+            log_removal(self.removal_log(), key, &previous, "expired");
+        }
+        drop(cache);
+        self.notify_miss(py, key, meta);
+
+        self.lead_flight(
+            py,
+            &py_func,
+            &args,
+            &kwargs,
+            key,
+            &pending_entry,
+            &token,
+            tenant,
+            meta,
+            info,
+        )
+    }
+
+    // Computes a flight as its leader, either the caller that started it or
+    // a waiter promoted after the previous leader failed, and lands the result
+    #[allow(clippy::too_many_arguments)]
+    fn lead_flight(
+        &self,
+        py: Python<'_>,
+        py_func: &Py<PyAny>,
+        args: &Py<PyAny>,
+        kwargs: &Py<PyAny>,
+        key: &str,
+        pending_entry: &Arc<(Mutex<PyCacheEntry>, FlightSignal)>,
+        token: &Py<CancelToken>,
+        tenant: Option<Arc<TenantState>>,
+        meta: Option<&Py<PyAny>>,
+        info: &mut CallInfo,
+    ) -> PyResult<Py<PyAny>> {
+        // Do calculation
+        let started = Instant::now();
+        let freeze = pending_entry.0.lock().unwrap().freeze;
+        let result = self.lead(py, py_func, args, kwargs, key, token, freeze, meta);
+        self.notify_leader_done(py, key, started, result.is_ok(), meta);
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                self.fail_pending(py, key, pending_entry, &err);
+                return Err(err);
+            }
+        };
+
+        // Without stored results the flight leaves the map before it lands,
+        // so later callers start a new one; waiters hold their own reference
+        if !self.config.store_results {
+            self.remove_flight(key, pending_entry);
+        }
+
+        // Notify waiting values and update state
+        let weight = size_of(py, &result);
+        let expires_at = self.config.result_expiry(py, &result);
+        let (lock, cvar) = &**pending_entry;
+        let mut entry = lock.lock().expect("Unable to get cache entry for update");
+        if entry.overrun && !entry.ready {
+            // Waiters were already failed and the entry dropped, so the late
+            // result only goes to this caller
+            return Ok(result);
+        }
+        if entry.ready {
+            // A manual write won the race and waiters already observed it
+            let value = entry.value.as_ref().expect("None after ready!");
+            return Ok(value.clone_ref(py));
+        }
+        if entry.waiters > 0 && entry.abandoned == entry.waiters {
+            self.counters()
+                .unconsumed_results
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        // An explicit `expire_at` wins over the cache-wide ttl
+        if entry.expires_at.is_none() {
+            entry.expires_at = expires_at;
+        }
+        entry.ready(result.clone_ref(py), weight);
+        info.shared = entry.served();
+        cvar.wake(self.config.wake);
+        drop(entry);
+        if !self.config.store_results {
+            return Ok(result);
+        }
+
+        if let Some(tenant) = &tenant {
+            if tenant.over_memory() {
+                let policy = tenant.policy();
+                let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+                let mut evicted = Vec::new();
+                match policy {
+                    QuotaPolicy::Evict => {
+                        while tenant.over_memory() {
+                            let Some(key) = evict_lru(&mut cache, tenant, self.removal_log())
+                            else {
+                                break;
+                            };
+                            evicted.push(key);
+                        }
+                    }
+                    QuotaPolicy::Skip | QuotaPolicy::Raise => {
+                        if let Some(state) = cache.remove(key) {
+                            log_removal(self.removal_log(), key, &state, "quota");
+                        }
+                    }
+                }
+                drop(cache);
+                self.notify_evicted(py, evicted, "quota");
+                self.quota_exceeded(py, tenant, key, "memory", meta);
+                if policy == QuotaPolicy::Raise {
+                    return Err(QuotaExceeded::new_err(format!(
+                        "Tenant '{}' exceeded its memory quota",
+                        tenant.name
+                    )));
+                }
+            }
+        }
+        self.enforce_size_limit(py);
+        self.enforce_memory_limit(py);
+        Ok(result)
+    }
+
+    // Re-raises a failed computation in every waiter and drops the pending
+    // entry, unless it was already replaced, so the next call starts afresh.
+    // With the promote policy one live waiter leads the flight instead.
+    fn fail_pending(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        pending_entry: &Arc<(Mutex<PyCacheEntry>, FlightSignal)>,
+        err: &PyErr,
+    ) {
+        let (lock, cvar) = &**pending_entry;
+        let mut entry = lock.lock().expect("Unable to get cache entry for update");
+        if entry.ready {
+            return;
+        }
+        self.counters().failed_calls.fetch_add(1, Ordering::Relaxed);
+        if self.config.failure_policy == FailurePolicy::Promote
+            && entry.handoffs < self.config.max_handoffs
+            && entry.waiters > entry.abandoned
+            && !entry.token.get().cancelled()
+        {
+            entry.handoffs += 1;
+            entry.handoff = true;
+            cvar.wake(self.config.wake);
+            self.counters().handoffs.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        entry.error = Some(err.clone_ref(py));
+        cvar.wake(self.config.wake);
+        drop(entry);
+
+        self.remove_flight(key, pending_entry);
+    }
+
+    // Removes `key` only if it still maps to this flight
+    fn remove_flight(&self, key: &str, flight: &Arc<(Mutex<PyCacheEntry>, FlightSignal)>) {
+        let mut cache = self.cache.lock(key).expect("Unable to lock cache!");
+        if let Some(PyEntryState::Pending(current)) = cache.get(key) {
+            if Arc::ptr_eq(current, flight) {
+                cache.remove(key);
+            }
+        }
+    }
+
+    // Flights inherited from the parent process lost their leader thread in
+    // the fork and would never complete, so the first call in a child drops
+    // them. Ready values are kept.
+    fn after_fork(&self) {
+        let generation = fork::generation();
+        if self.generation.swap(generation, Ordering::SeqCst) == generation {
+            return;
+        }
+        let dropped = self
+            .cache
+            .lock_all()
+            .expect("Unable to lock cache!")
+            .retain(|_, state| state.is_ready());
+        self.counters()
+            .forked_flights_dropped
+            .fetch_add(dropped.len() as u64, Ordering::Relaxed);
+    }
+
+    fn enforce_size_limit(&self, py: Python<'_>) {
+        let Some(max_size) = self.config.max_size else {
+            return;
+        };
+        let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+        if cache.len() <= max_size {
+            return;
+        }
+        let evicted = shrink(&mut cache, max_size, self.removal_log());
+        drop(cache);
+        self.counters()
+            .size_evictions
+            .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        self.notify_evicted(py, evicted, "size");
+    }
+
+    fn enforce_memory_limit(&self, py: Python<'_>) {
+        let Some(hard_limit) = self.memory.hard_limit else {
+            return;
+        };
+        if self.memory.over_hard() {
+            let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+            let evicted = trim(&mut cache, &self.memory, hard_limit, self.removal_log());
+            drop(cache);
+            self.memory
+                .sync_evictions
+                .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+            self.notify_evicted(py, evicted, "memory");
+        }
+    }
+
+    // An explicit label wins over the captured caller location
+    fn provenance(&self, py: Python<'_>, label: Option<String>) -> Option<String> {
+        label.or_else(|| {
+            self.config
+                .record_provenance
+                .then(|| caller_location(py))
+                .flatten()
+        })
+    }
+
+    fn notify_hit(&self, py: Python<'_>, key: &str, meta: Option<&Py<PyAny>>) {
+        if let Some(on_hit) = self.hook(&self.config.on_hit) {
+            self.hooks.dispatch(py, "on_hit", on_hit, (key,), meta);
+        }
+    }
+
+    // Lifecycle hooks of the leader and waiters; like `on_hit` they run on
+    // the hook thread, never under the map or entry locks
+    fn notify_miss(&self, py: Python<'_>, key: &str, meta: Option<&Py<PyAny>>) {
+        if let Some(on_miss) = self.hook(&self.config.on_miss) {
+            self.hooks.dispatch(py, "on_miss", on_miss, (key,), meta);
+        }
+        self.notify_leader_start(py, key, meta);
+    }
+
+    fn notify_leader_start(&self, py: Python<'_>, key: &str, meta: Option<&Py<PyAny>>) {
+        if let Some(on_leader_start) = self.hook(&self.config.on_leader_start) {
+            self.hooks
+                .dispatch(py, "on_leader_start", on_leader_start, (key,), meta);
+        }
+    }
+
+    fn notify_leader_done(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        started: Instant,
+        succeeded: bool,
+        meta: Option<&Py<PyAny>>,
+    ) {
+        if let Some(on_leader_done) = self.hook(&self.config.on_leader_done) {
+            let elapsed = started.elapsed().as_secs_f64();
+            self.hooks.dispatch(
+                py,
+                "on_leader_done",
+                on_leader_done,
+                (key, elapsed, succeeded),
+                meta,
+            );
+        }
+    }
+
+    fn notify_timeout(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        waiting: Instant,
+        meta: Option<&Py<PyAny>>,
+    ) {
+        if let Some(on_timeout) = self.hook(&self.config.on_timeout) {
+            let waited = waiting.elapsed().as_secs_f64();
+            self.hooks
+                .dispatch(py, "on_timeout", on_timeout, (key, waited), meta);
+        }
+    }
+
+    fn notify_evicted(&self, py: Python<'_>, keys: Vec<String>, reason: &str) {
+        if let Some(on_evict) = self.hook(&self.config.on_evict) {
+            notify_evicted(py, &self.hooks, on_evict, keys, reason);
+        }
+    }
+
+    // Ready value for `key` if there is one, never starting a computation
+    pub(crate) fn lookup(&self, py: Python<'_>, key: &str) -> PyResult<Option<Py<PyAny>>> {
+        self.lookup_with(py, key, |value| value.clone().unbind())
+    }
+
+    pub(crate) fn canonical_key<'a>(&self, py: Python<'_>, key: &'a str) -> PyResult<Cow<'a, str>> {
+        match &self.config.canonicalize {
+            Some(canonicalizer) => canonicalizer.apply(py, key),
+            None => Ok(Cow::Borrowed(key)),
+        }
+    }
+
+    // `read` runs under the entry lock, so it must not call back into Python
+    fn lookup_with<R>(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        read: impl FnOnce(&Bound<'_, PyAny>) -> R,
+    ) -> PyResult<Option<R>> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let lock_var = match self
+            .cache
+            .read(&key)
+            .expect("Unable to lock cache!")
+            .get(&key)
+        {
+            Some(PyEntryState::Pending(lock_var)) => lock_var.clone(),
+            None => return Ok(None),
+        };
+        let degraded = self.degraded.load(Ordering::SeqCst);
+        let mut entry = lock_var.0.lock().unwrap();
+        let fresh = entry.ready && (degraded || !entry.is_expired(Instant::now()));
+        Ok(fresh.then(|| read(entry.touch().bind(py))))
+    }
+
+    // Like `call`, but never waits longer than the fallback policy allows. A
+    // value that is not fresh is computed on a helper thread that keeps
+    // running after the caller has been served the fallback.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn call_bounded(
+        slf: &Py<Self>,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: &str,
+        options: CallOptions,
+    ) -> PyResult<Py<PyAny>> {
+        let mut info = CallInfo::default();
+        Self::call_bounded_with_info(slf, py, py_func, args, kwargs, key, options, &mut info)
+    }
+
+    // A fallback value counts as neither led nor shared
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn call_bounded_with_info(
+        slf: &Py<Self>,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: &str,
+        options: CallOptions,
+        info: &mut CallInfo,
+    ) -> PyResult<Py<PyAny>> {
+        let this = slf.get();
+        let Some(policy) = &this.config.fallback else {
+            return this.call_with_info(py, py_func, args, kwargs, key, options, info);
+        };
+        let policy = policy.get();
+        let canonical = match options.tenant {
+            Some(_) => Cow::Borrowed(key),
+            None => this.canonical_key(py, key)?,
+        };
+        let (stale, fresh) = this.peek(py, &canonical);
+        if fresh {
+            return this.call_with_info(py, py_func, args, kwargs, key, options, info);
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let cache = slf.clone_ref(py);
+        let owned_key = key.to_string();
+        // Queued behind busy pool threads, the call still counts against
+        // `max_wait` and keeps running for the cache after a fallback
+        this.calls.submit(move || {
+            let mut info = CallInfo::default();
+            let result = Python::with_gil(|py| {
+                cache
+                    .get()
+                    .call_with_info(py, py_func, args, kwargs, &owned_key, options, &mut info)
+            });
+            let _ = sender.send(result.map(|value| (value, info)));
+        })?;
+        let max_wait = policy.max_wait();
+        if let Ok(result) = py.allow_threads(move || receiver.recv_timeout(max_wait)) {
+            return result.map(|(value, served)| {
+                *info = served;
+                value
+            });
+        }
+
+        if let Some(stale) = stale.filter(|_| policy.serve_stale()) {
+            this.stats.fallback_stale.fetch_add(1, Ordering::Relaxed);
+            return Ok(stale);
+        }
+        if let Some(default) = policy.default_provider() {
+            this.stats.fallback_default.fetch_add(1, Ordering::Relaxed);
+            return default.call1(py, (key,));
+        }
+        this.stats.fallback_timeouts.fetch_add(1, Ordering::Relaxed);
+        Err(ComputeTimeout::new_err(format!(
+            "No value for cache entry '{}' within {:?}",
+            key,
+            policy.max_wait()
+        )))
+    }
+
+    // Stored value for `key` even if expired, and whether it is still fresh
+    fn peek(&self, py: Python<'_>, key: &str) -> (Option<Py<PyAny>>, bool) {
+        let lock_var = match self
+            .cache
+            .read(key)
+            .expect("Unable to lock cache!")
+            .get(key)
+        {
+            Some(PyEntryState::Pending(lock_var)) => lock_var.clone(),
+            None => return (None, false),
+        };
+        let entry = lock_var.0.lock().unwrap();
+        if !entry.ready {
+            return (None, false);
+        }
+        let value = entry.value.as_ref().map(|value| value.clone_ref(py));
+        (value, !entry.is_expired(Instant::now()))
+    }
+
+    fn bypassed(&self, key: &str) -> bool {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return true;
+        }
+        self.disabled_namespaces
+            .lock()
+            .expect("Unable to lock disabled namespaces!")
+            .iter()
+            .any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    fn serve_degraded(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        options: &CallOptions,
+        meta: Option<&Py<PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let lock_var = self
+            .cache
+            .read(key)
+            .expect("Unable to lock cache!")
+            .get(key)
+            .map(|PyEntryState::Pending(lock_var)| lock_var.clone());
+        let stored = lock_var.and_then(|lock_var| {
+            let mut entry = lock_var.0.lock().unwrap();
+            entry
+                .ready
+                .then(|| entry.read(py, self.config.check_access.is_some()))
+        });
+        if let Some((value, entry_tags)) = stored {
+            self.record(py, key, TraceKind::Hit, meta);
+            self.counters().hits.fetch_add(1, Ordering::Relaxed);
+            self.notify_hit(py, key, meta);
+            self.check_access(py, key, entry_tags, options.context.as_ref(), meta)?;
+            return Ok(value);
+        }
+
+        self.counters()
+            .degraded_misses
+            .fetch_add(1, Ordering::Relaxed);
+        let default = self
+            .degraded_default
+            .lock()
+            .expect("Unable to lock degraded default!");
+        match &*default {
+            Some(default) => Ok(default.clone_ref(py)),
+            None => Err(CacheDegraded::new_err(format!(
+                "Cache is degraded and holds no value for '{}'",
+                key
+            ))),
+        }
+    }
+
+    // Returns whether `value` is what readers of `key` now observe; races
+    // with an in-flight computation are settled by the write policy. A
+    // `refresher` is called with the key whenever the entry is refreshed.
+    pub(crate) fn store(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        value: Py<PyAny>,
+        refresher: Option<Py<PyAny>>,
+    ) -> PyResult<bool> {
+        self.write(py, key, value, refresher, None, None)
+    }
+
+    pub(crate) fn store_leased(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        value: Py<PyAny>,
+        lease: u64,
+    ) -> PyResult<bool> {
+        self.write(py, key, value, None, None, Some(lease))
+    }
+
+    pub(crate) fn leases(&self) -> &LeaseTable {
+        &self.leases
+    }
+
+    fn drop_where(&self, matches: impl Fn(&str) -> bool) -> usize {
+        let mut cache = self.cache.lock_all().expect("Unable to lock cache!");
+        let dropped = cache.retain(|key, state| {
+            if !matches(key) || self.leases.check(key, None).is_err() {
+                return true;
+            }
+            log_removal(self.removal_log(), key, state, "drop");
+            false
+        });
+        dropped.len()
+    }
+
+    fn ensure_open(&self) -> PyResult<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(CacheClosed::new_err("The cache is closed"));
+        }
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        value: Py<PyAny>,
+        refresher: Option<Py<PyAny>>,
+        ttl: Option<Option<Duration>>,
+        lease: Option<u64>,
+    ) -> PyResult<bool> {
+        self.ensure_open()?;
+        let key = self.canonical_key(py, key)?;
+        let key = key.as_ref();
+        self.leases.check(key, lease)?;
+        let expires_at = match ttl {
+            Some(ttl) => ttl.map(|ttl| Instant::now() + ttl),
+            None => self.config.ttl_expiry(),
+        };
+        let options = CallOptions {
+            expires_at,
+            provenance: self.provenance(py, None),
+            ..Default::default()
+        };
+        let mut entry = PyCacheEntry::completed(py, value, options, self.memory.clone())?;
+        if let Some(refresher) = refresher {
+            entry.loader = Some(Loader {
+                func: refresher,
+                args: PyTuple::new(py, [key])?.into_any().unbind(),
+                kwargs: PyDict::new(py).into_any().unbind(),
+            });
+        }
+        let mut cache = self.cache.lock(key).expect("Unable to lock cache!");
+        let in_flight = match cache.get(key) {
+            Some(PyEntryState::Pending(lock_var)) if !lock_var.0.lock().unwrap().ready => {
+                Some(lock_var.clone())
+            }
+            _ => None,
+        };
+        let Some(in_flight) = in_flight else {
+            let replaced = cache.get(key).filter(|_| self.config.keep_history > 0);
+            if let Some(PyEntryState::Pending(replaced)) = replaced {
+                let mut history = replaced.0.lock().unwrap().history(py);
+                history.truncate(self.config.keep_history);
+                entry.previous = history.into();
+            }
+            let signal = FlightSignal::new(self.stripes.as_deref(), key);
+            cache.insert(key, PyEntryState::new(entry, signal));
+            drop(cache);
+            self.enforce_size_limit(py);
+            self.enforce_memory_limit(py);
+            return Ok(true);
+        };
+        drop(cache);
+
+        self.counters()
+            .write_conflicts
+            .fetch_add(1, Ordering::Relaxed);
+        if self.config.write_policy == WritePolicy::Last {
+            return Ok(false);
+        }
+        let (lock, cvar) = &*in_flight;
+        let mut pending = lock.lock().unwrap();
+        if pending.ready {
+            return Ok(false);
+        }
+        let value = entry.value.take().expect("None after ready!");
+        pending.expires_at = pending.expires_at.or(entry.expires_at);
+        pending.ready(value, entry.weight);
+        cvar.wake(self.config.wake);
+        Ok(true)
+    }
+
+    fn keys_where(&self, py: Python<'_>, ready: bool) -> Vec<String> {
+        let cache = &self.cache;
+        py.allow_threads(|| {
+            cache
+                .lock_all()
+                .expect("Unable to lock cache!")
+                .iter()
+                .filter(|(_, state)| state.is_ready() == ready)
+                .map(|(key, _)| key.to_string())
+                .collect()
+        })
+    }
+
+    fn pending_flight(&self, key: &str) -> Option<Arc<(Mutex<PyCacheEntry>, FlightSignal)>> {
+        match self
+            .cache
+            .read(key)
+            .expect("Unable to lock cache!")
+            .get(key)
+        {
+            Some(PyEntryState::Pending(lock_var)) if !lock_var.0.lock().unwrap().ready => {
+                Some(lock_var.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        let mut cache = self.cache.lock(key).expect("Unable to lock cache!");
+        if let Some(state) = cache.remove(key) {
+            log_removal(self.removal_log(), key, &state, "drop");
+        }
+    }
+
+    // Computes `key` as the local leader, first winning the election of the
+    // configured `leader_elector`, if any, across processes
+    #[allow(clippy::too_many_arguments)]
+    fn lead(
+        &self,
+        py: Python<'_>,
+        py_func: &Py<PyAny>,
+        args: &Py<PyAny>,
+        kwargs: &Py<PyAny>,
+        key: &str,
+        token: &Py<CancelToken>,
+        freeze: Option<bool>,
+        meta: Option<&Py<PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let Some(elector) = &self.config.leader_elector else {
+            return self
+                .call_leader(py, py_func, args, kwargs, key, token, meta)
+                .and_then(|result| self.post_process(py, result, freeze, meta));
+        };
+        let timeout = self
+            .config
+            .wait_timeout
+            .map(|timeout| timeout.as_secs_f64());
+        if !elector
+            .call_method1(py, "acquire", (key, timeout))?
+            .bind(py)
+            .is_truthy()?
+        {
+            self.counters()
+                .elections_lost
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(ComputeTimeout::new_err(format!(
+                "Could not become leader for cache entry '{}' within {:?}",
+                key,
+                self.config.wait_timeout.unwrap_or_default()
+            )));
+        }
+        let result = self
+            .call_leader(py, py_func, args, kwargs, key, token, meta)
+            .and_then(|result| self.post_process(py, result, freeze, meta));
+        if let Err(err) = elector.call_method1(py, "release", (key,)) {
+            py_log::log(
+                py_log::WARNING,
+                &format!("Unable to release leadership of '{}': {}", key, err),
+            );
+        }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn call_leader(
+        &self,
+        py: Python<'_>,
+        py_func: &Py<PyAny>,
+        args: &Py<PyAny>,
+        kwargs: &Py<PyAny>,
+        key: &str,
+        token: &Py<CancelToken>,
+        meta: Option<&Py<PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let args_tuple: &Bound<'_, PyTuple> = args.downcast_bound(py)?;
+        let kwargs_dict: &Bound<'_, PyDict> = kwargs.downcast_bound(py)?;
+        let pass_flight_ctx = accepts_flight_ctx(py_func.bind(py));
+        let deadline = self
+            .config
+            .compute_timeout
+            .or(self.config.wait_timeout)
+            .map(|timeout| Instant::now() + timeout);
+
+        let mut attempt = 1;
+        let mut last_exception: Option<Py<PyAny>> = None;
+        loop {
+            let call_kwargs = if self.config.cancel_abandoned || pass_flight_ctx {
+                let call_kwargs = kwargs_dict.copy()?;
+                if self.config.cancel_abandoned {
+                    call_kwargs.set_item("cancel_token", token.clone_ref(py))?;
+                }
+                if pass_flight_ctx {
+                    let flight_ctx = FlightContext::new(
+                        key.to_string(),
+                        attempt,
+                        token.clone_ref(py),
+                        deadline,
+                        last_exception.as_ref().map(|exc| exc.clone_ref(py)),
+                        meta.map(|meta| meta.clone_ref(py)),
+                    );
+                    call_kwargs.set_item("flight_ctx", flight_ctx)?;
+                }
+                call_kwargs
+            } else {
+                kwargs_dict.clone()
+            };
+
+            let result = py_func
+                .call(py, args_tuple, Some(&call_kwargs))
+                .and_then(|result| await_result(py, self.config.event_loop.as_ref(), result));
+            let err = match result {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+            let exception: Py<PyAny> = err.value(py).clone().into_any().unbind();
+
+            if let Some(on_error) = &self.config.on_error {
+                self.hooks.dispatch(
+                    py,
+                    "on_error",
+                    on_error,
+                    (key, exception.clone_ref(py), attempt),
+                    meta,
+                );
+            }
+            if attempt > self.config.retries || !self.is_retryable(py, &exception, meta) {
+                return Err(err);
+            }
+            self.counters().retries.fetch_add(1, Ordering::Relaxed);
+            last_exception = Some(exception);
+            attempt += 1;
+        }
+    }
+
+    fn quota_exceeded(
+        &self,
+        py: Python<'_>,
+        tenant: &TenantState,
+        key: &str,
+        quota: &str,
+        meta: Option<&Py<PyAny>>,
+    ) {
+        self.counters()
+            .quota_exceeded
+            .fetch_add(1, Ordering::Relaxed);
+        if let Some(on_quota_exceeded) = &self.config.on_quota_exceeded {
+            self.hooks.dispatch(
+                py,
+                "on_quota_exceeded",
+                on_quota_exceeded,
+                (tenant.name.as_str(), key, quota, tenant.policy().as_str()),
+                meta,
+            );
+        }
+    }
+
+    fn func_mismatch(&self, key: &str) -> PyResult<()> {
+        self.counters()
+            .func_mismatches
+            .fetch_add(1, Ordering::Relaxed);
+        let message = format!(
+            "Cache entry '{}' was requested with a different function than the one that computed it",
+            key
+        );
+        match self.config.on_func_mismatch {
+            Some(MismatchPolicy::Raise) => Err(FunctionMismatch::new_err(message)),
+            Some(MismatchPolicy::Warn) => {
+                py_log::log(py_log::WARNING, &message);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_access(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        tags: Option<Py<PyAny>>,
+        context: Option<&Py<PyAny>>,
+        meta: Option<&Py<PyAny>>,
+    ) -> PyResult<()> {
+        let Some(check_access) = &self.config.check_access else {
+            return Ok(());
+        };
+        let allowed = call_hook(py, check_access, (key, tags, context), meta)?
+            .bind(py)
+            .is_truthy()?;
+        if !allowed {
+            self.counters()
+                .access_denied
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(PyPermissionError::new_err(format!(
+                "Access to cache entry '{}' denied",
+                key
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn await_result(&self, py: Python<'_>, value: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        await_result(py, self.config.event_loop.as_ref(), value)
+    }
+
+    pub(crate) fn post_process(
+        &self,
+        py: Python<'_>,
+        value: Py<PyAny>,
+        freeze: Option<bool>,
+        meta: Option<&Py<PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        apply_post_process(py, &self.config, value, freeze, meta)
+    }
+
+    fn is_retryable(
+        &self,
+        py: Python<'_>,
+        exception: &Py<PyAny>,
+        meta: Option<&Py<PyAny>>,
+    ) -> bool {
+        match &self.config.retry_predicate {
+            Some(retry_predicate) => {
+                call_hook(py, retry_predicate, (exception.clone_ref(py),), meta)
+                    .and_then(|retry| retry.bind(py).is_truthy())
+                    .unwrap_or(false)
+            }
+            None => true,
+        }
+    }
+
+    fn record(&self, py: Python<'_>, key: &str, kind: TraceKind, meta: Option<&Py<PyAny>>) {
+        if let Some(trace) = self.trace.as_ref().filter(|_| !probing()) {
+            trace.record(key, kind, meta.map(|meta| meta.clone_ref(py)));
+        }
+    }
+
+    // The probe calls of `validate` count into throwaway counters, and skip
+    // the hooks and the removal log, so they never show up as traffic
+    fn counters(&self) -> &CacheStats {
+        match probing() {
+            true => &PROBE_STATS,
+            false => &self.stats,
+        }
+    }
+
+    // Copies the bookkeeping of every entry out under the locks, so the
+    // introspection dicts get built after they are released
+    fn summaries(&self, py: Python<'_>, values: bool) -> Vec<EntrySummary> {
+        let now = Instant::now();
+        let cache = self.cache.lock_all().expect("Unable to lock cache!");
+        cache
+            .iter()
+            .map(|(key, state)| {
+                let PyEntryState::Pending(lock_var) = state;
+                let entry = lock_var.0.lock().unwrap();
+                let state = if entry.ready {
+                    if entry.is_expired(now) {
+                        "expired"
+                    } else {
+                        "ready"
+                    }
+                } else if entry.overrun {
+                    "overrun"
+                } else if entry.handoff {
+                    "handoff"
+                } else {
+                    "pending"
+                };
+                EntrySummary {
+                    key: key.to_string(),
+                    state,
+                    ready: entry.ready,
+                    age: now
+                        .saturating_duration_since(entry.created_at)
+                        .as_secs_f64(),
+                    ttl: entry
+                        .expires_at
+                        .map(|expires_at| expires_at.saturating_duration_since(now).as_secs_f64()),
+                    hits: entry.hits,
+                    weight: entry.weight,
+                    waiters: entry.served(),
+                    leader: entry.leader.clone(),
+                    external: entry.external,
+                    namespace: entry.tenant.as_ref().map(|tenant| tenant.name.clone()),
+                    meta: entry.meta.as_ref().map(|meta| meta.clone_ref(py)),
+                    value: entry
+                        .value
+                        .as_ref()
+                        .filter(|_| values)
+                        .map(|value| value.clone_ref(py)),
+                }
+            })
+            .collect()
+    }
+
+    fn hook<'a>(&self, hook: &'a Option<Py<PyAny>>) -> Option<&'a Py<PyAny>> {
+        hook.as_ref().filter(|_| !probing())
+    }
+
+    fn removal_log(&self) -> Option<&RemovalLog> {
+        self.removals.as_deref().filter(|_| !probing())
+    }
+}
+
+thread_local! {
+    static PROBING: Cell<bool> = const { Cell::new(false) };
+}
+
+static PROBE_STATS: LazyLock<CacheStats> = LazyLock::new(CacheStats::default);
+
+fn probing() -> bool {
+    PROBING.with(Cell::get)
+}
+
+// Marks the calls made on this thread as `validate` probes until dropped
+struct Probe;
+
+impl Probe {
+    fn start() -> Self {
+        PROBING.with(|probing| probing.set(true));
+        Probe
+    }
+}
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        PROBING.with(|probing| probing.set(false));
+    }
+}
+
+// What `inspect`, `debug_dump` and `metadata_snapshot` report of an entry
+struct EntrySummary {
+    key: String,
+    // "pending", "handoff", "overrun", "ready" or "expired"
+    state: &'static str,
+    ready: bool,
+    age: f64,
+    ttl: Option<f64>,
+    hits: u64,
+    weight: usize,
+    // Still waiting, i.e. not counting those that gave up
+    waiters: usize,
+    leader: Option<String>,
+    external: bool,
+    namespace: Option<String>,
+    meta: Option<Py<PyAny>>,
+    value: Option<Py<PyAny>>,
+}
+
+#[derive(Default)]
+struct ValidationReport {
+    checks: Vec<(&'static str, &'static str, Option<String>)>,
+}
+
+impl ValidationReport {
+    fn pass(&mut self, name: &'static str) {
+        self.checks.push((name, "ok", None));
+    }
+
+    fn fail(&mut self, name: &'static str, detail: String) {
+        self.checks.push((name, "failed", Some(detail)));
+    }
+
+    fn skip(&mut self, name: &'static str, detail: &str) {
+        self.checks
+            .push((name, "skipped", Some(detail.to_string())));
+    }
+
+    fn into_dict(self, py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let checks = PyList::empty(py);
+        let mut problems = Vec::new();
+        for (name, status, detail) in self.checks {
+            if status == "failed" {
+                problems.push(format!(
+                    "{}: {}",
+                    name,
+                    detail.as_deref().unwrap_or_default()
+                ));
+            }
+            let check = PyDict::new(py);
+            check.set_item("name", name)?;
+            check.set_item("status", status)?;
+            check.set_item("detail", detail)?;
+            checks.append(check)?;
+        }
+        let report = PyDict::new(py);
+        report.set_item("ok", problems.is_empty())?;
+        report.set_item("checks", checks)?;
+        report.set_item("problems", problems)?;
+        Ok(report)
+    }
+}
+
+fn apply_post_process(
+    py: Python<'_>,
+    config: &CacheConfig,
+    value: Py<PyAny>,
+    freeze: Option<bool>,
+    meta: Option<&Py<PyAny>>,
+) -> PyResult<Py<PyAny>> {
+    let value = match &config.post_process {
+        Some(post_process) => call_hook(py, post_process, (value,), meta)?,
+        None => value,
+    };
+    if freeze.unwrap_or(config.freeze) {
+        return Ok(crate::freeze::freeze(value.bind(py), MAX_FREEZE_DEPTH)?.unbind());
+    }
+    Ok(value)
+}
+
+// Recomputes a ready entry in place; readers keep getting the previous value
+// until the new one is in.
+fn refresh_entry(
+    py: Python<'_>,
+    cache: &ShardedKeyMap<PyEntryState>,
+    config: &CacheConfig,
+    memory: &Arc<MemoryBudget>,
+    stripes: Option<&LockStripes>,
+    job: &RefreshJob,
+) -> PyResult<RefreshOutcome> {
+    let source = job.source.as_deref().unwrap_or(&job.key);
+    let lock_var = match cache
+        .read(source)
+        .expect("Unable to lock cache!")
+        .get(source)
+    {
+        Some(PyEntryState::Pending(lock_var)) => lock_var.clone(),
+        None => return Ok(RefreshOutcome::Skipped),
+    };
+    let entry = lock_var.0.lock().unwrap();
+    let Some(loader) = entry.loader.as_ref().filter(|_| entry.ready) else {
+        return Ok(RefreshOutcome::Skipped);
+    };
+    let old_value = entry.value.as_ref().map(|value| value.clone_ref(py));
+    let func = loader.func.clone_ref(py);
+    let args = loader.args.clone_ref(py);
+    let kwargs = loader.kwargs.clone_ref(py);
+    let meta = entry.meta.as_ref().map(|meta| meta.clone_ref(py));
+    let freeze = entry.freeze;
+    drop(entry);
+
+    let args = args.downcast_bound::<PyTuple>(py)?;
+    let kwargs = kwargs.downcast_bound::<PyDict>(py)?;
+    let value = func.call(py, args, Some(kwargs))?;
+    let value = await_result(py, config.event_loop.as_ref(), value)?;
+    let value = apply_post_process(py, config, value, freeze, meta.as_ref())?;
+
+    if job.source.is_none() {
+        let unchanged = match &old_value {
+            Some(old_value) if config.delta_refresh => unchanged(py, config, old_value, &value),
+            _ => false,
+        };
+        let expires_at = config.result_expiry(py, &value);
+        if unchanged {
+            // Keep the object readers already hold, only the freshness moves
+            let mut entry = lock_var.0.lock().unwrap();
+            entry.created_at = Instant::now();
+            entry.expires_at = expires_at.or(entry.expires_at);
+            return Ok(RefreshOutcome::Unchanged);
+        }
+        let weight = size_of(py, &value);
+        let mut entry = lock_var.0.lock().unwrap();
+        entry.refreshed(value, weight, config.keep_history);
+        entry.expires_at = expires_at.or(entry.expires_at);
+        return Ok(RefreshOutcome::Updated);
+    }
+    let options = CallOptions {
+        meta,
+        expires_at: config.result_expiry(py, &value),
+        provenance: config.record_provenance.then(|| "refresh".to_string()),
+        freeze,
+        ..Default::default()
+    };
+    let mut entry = PyCacheEntry::completed(py, value, options, memory.clone())?;
+    entry.loader = Some(Loader {
+        func,
+        args: args.clone().into_any().unbind(),
+        kwargs: kwargs.clone().into_any().unbind(),
+    });
+
+    let mut cache = cache.lock(&job.key).expect("Unable to lock cache!");
+    if cache.get(&job.key).is_some() {
+        return Ok(RefreshOutcome::Skipped);
+    }
+    let signal = FlightSignal::new(stripes, &job.key);
+    cache.insert(&job.key, PyEntryState::new(entry, signal));
+    Ok(RefreshOutcome::Updated)
+}
+
+// A comparison that fails counts as a change, so the new value is published
+fn unchanged(py: Python<'_>, config: &CacheConfig, old: &Py<PyAny>, new: &Py<PyAny>) -> bool {
+    match &config.refresh_comparator {
+        Some(comparator) => comparator
+            .call1(py, (old, new))
+            .and_then(|equal| equal.bind(py).is_truthy()),
+        None => old.bind(py).eq(new),
+    }
+    .unwrap_or(false)
+}
+
+// `part` as a fraction of `whole`, zero while there is no `whole` yet
+fn rate(part: u64, whole: u64) -> f64 {
+    match whole {
+        0 => 0.0,
+        whole => part as f64 / whole as f64,
+    }
+}
+
+// The entry for `key` unless it is missing or expired
+fn live_entry(
+    cache: &KeyMap<PyEntryState>,
+    key: &str,
+    now: Instant,
+) -> Option<Arc<(Mutex<PyCacheEntry>, FlightSignal)>> {
+    match cache.get(key) {
+        Some(PyEntryState::Pending(lock_var)) if !lock_var.0.lock().unwrap().is_expired(now) => {
+            Some(lock_var.clone())
+        }
+        _ => None,
+    }
+}
+
+// How often a waiter comes back to check for Python signals, so that
+// Ctrl-C reaches threads blocked on a flight
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+// Waits in slices until the entry resolves or `deadline` passes, running
+// Python's signal handlers in between. Returns whether the entry resolved,
+// or the exception a handler raised, e.g. KeyboardInterrupt.
+fn wait_resolved(
+    py: Python<'_>,
+    lock: &Mutex<PyCacheEntry>,
+    cvar: &FlightSignal,
+    deadline: Option<Instant>,
+) -> PyResult<bool> {
+    wait_while(py, lock, cvar, deadline, |entry| {
+        !entry.ready && !entry.overrun && !entry.handoff && entry.error.is_none()
+    })
+}
+
+// Still computing; failed leaders' handoffs count as computing
+fn in_flight(entry: &mut PyCacheEntry) -> bool {
+    !entry.ready && !entry.overrun && entry.error.is_none()
+}
+
+fn wait_while(
+    py: Python<'_>,
+    lock: &Mutex<PyCacheEntry>,
+    signal: &FlightSignal,
+    deadline: Option<Instant>,
+    pending: fn(&mut PyCacheEntry) -> bool,
+) -> PyResult<bool> {
+    loop {
+        let slice = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) if !left.is_zero() => left.min(SIGNAL_CHECK_INTERVAL),
+                _ => return Ok(false),
+            },
+            None => SIGNAL_CHECK_INTERVAL,
+        };
+        let resolved = py.allow_threads(|| match signal {
+            FlightSignal::Own(cvar) => {
+                let entry = lock.lock().unwrap();
+                let (mut entry, _) = cvar.wait_timeout_while(entry, slice, pending).unwrap();
+                !pending(&mut entry)
+            }
+            // The generation is read before the entry is checked, so a
+            // wakeup in between is not missed. The two locks are never
+            // held together, so wakers may hold the entry lock.
+            FlightSignal::Striped(stripe) => {
+                let seen = stripe.generation();
+                if !pending(&mut lock.lock().unwrap()) {
+                    return true;
+                }
+                stripe.wait_changed(seen, slice);
+                !pending(&mut lock.lock().unwrap())
+            }
+        });
+        if resolved {
+            return Ok(true);
+        }
+        py.check_signals()?;
+    }
+}
+
+// Wakes the waiters of a computation running past `compute_timeout` with
+// an error; the leader is asked to stop through its cancel token.
+fn fail_overrun(state: &PyEntryState, compute_timeout: Duration, wake: WakeStrategy) -> bool {
+    let PyEntryState::Pending(lock_var) = state;
+    let mut entry = lock_var.0.lock().unwrap();
+    if entry.ready || entry.created_at.elapsed() < compute_timeout {
+        return false;
+    }
+    entry.overrun = true;
+    entry.token.get().cancel();
+    lock_var.1.wake(wake);
+    true
+}
+
+// Each flight is reported once, the first time it is seen past `alarm`
+fn overdue_flights(
+    cache: &ShardedKeyMap<PyEntryState>,
+    alarm: Duration,
+) -> Vec<(String, Option<String>, usize, Duration)> {
+    let cache = cache.lock_all().expect("Unable to lock cache!");
+    let mut overdue = Vec::new();
+    for (key, state) in cache.iter() {
+        let PyEntryState::Pending(lock_var) = state;
+        let mut entry = lock_var.0.lock().unwrap();
+        let elapsed = entry.created_at.elapsed();
+        if entry.ready || entry.alarmed || elapsed < alarm {
+            continue;
+        }
+        entry.alarmed = true;
+        overdue.push((
+            key.to_string(),
+            entry.leader.clone(),
+            entry.served(),
+            elapsed,
+        ));
+    }
+    overdue
+}
+
+fn current_thread_name(py: Python<'_>) -> Option<String> {
+    py.import("threading")
+        .and_then(|threading| threading.call_method0("current_thread"))
+        .and_then(|thread| thread.getattr("name"))
+        .and_then(|name| name.extract())
+        .ok()
+}
+
+// "module:line" of the innermost Python frame, i.e. the code calling the cache
+fn caller_location(py: Python<'_>) -> Option<String> {
+    let frame = py
+        .import("sys")
+        .and_then(|sys| sys.call_method0("_getframe"))
+        .ok()?;
+    let module: String = frame
+        .getattr("f_globals")
+        .and_then(|globals| globals.get_item("__name__"))
+        .and_then(|name| name.extract())
+        .ok()?;
+    let line: u32 = frame
+        .getattr("f_lineno")
+        .and_then(|line| line.extract())
+        .ok()?;
+    Some(format!("{}:{}", module, line))
+}
+
+fn type_mismatch(key: &str, value: &Bound<'_, PyAny>, expected: &str) -> PyErr {
+    let found = value
+        .get_type()
+        .name()
+        .map_or_else(|_| "unknown".to_string(), |name| name.to_string());
+    PyTypeError::new_err(format!(
+        "Cache entry '{}' holds a {}, expected {}",
+        key, found, expected
+    ))
+}
+
+// Python's datetime.timestamp() treats naive datetimes as local time
+pub(crate) fn instant_from_datetime(datetime: &Bound<'_, PyAny>) -> PyResult<Instant> {
+    let timestamp: f64 = datetime.call_method0("timestamp")?.extract()?;
+    let remaining = (timestamp - unix_now()).max(0.0);
+    Ok(Instant::now() + Duration::from_secs_f64(remaining))
+}
+
+// Hooks only receive meta when the caller attached some, so hooks written
+// without a meta parameter keep working.
+pub(crate) fn call_hook<'py, A: IntoPyObject<'py, Target = PyTuple>>(
+    py: Python<'py>,
+    hook: &Py<PyAny>,
+    args: A,
+    meta: Option<&Py<PyAny>>,
+) -> PyResult<Py<PyAny>> {
+    match meta {
+        Some(meta) => {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("meta", meta)?;
+            hook.call(py, args, Some(&kwargs))
+        }
+        None => hook.call1(py, args),
+    }
+}
+
+// Drops ready entries, least recently used first, until at most `target`
+// bytes are held.
+fn trim(
+    cache: &mut AllShards<'_, PyEntryState>,
+    memory: &MemoryBudget,
+    target: usize,
+    removals: Option<&RemovalLog>,
+) -> Vec<String> {
+    let mut candidates: Vec<(Instant, String)> = cache
+        .iter()
+        .filter(|(_, state)| state.is_ready())
+        .map(|(key, state)| (state.last_access(), key.to_string()))
+        .collect();
+    candidates.sort();
+
+    let mut evicted = Vec::new();
+    for (_, key) in candidates {
+        if memory.used() <= target {
+            break;
+        }
+        if let Some(state) = cache.remove(&key) {
+            log_removal(removals, &key, &state, "memory");
+        }
+        evicted.push(key);
+    }
+    evicted
+}
+
+// Evicts least recently used ready entries until at most `max_size` remain.
+// Pending entries are kept for their waiters, even if that leaves more.
+fn shrink(
+    cache: &mut AllShards<'_, PyEntryState>,
+    max_size: usize,
+    removals: Option<&RemovalLog>,
+) -> Vec<String> {
+    let mut candidates: Vec<(Instant, String)> = cache
+        .iter()
+        .filter(|(_, state)| state.is_ready())
+        .map(|(key, state)| (state.last_access(), key.to_string()))
+        .collect();
+    candidates.sort();
+
+    let excess = cache.len().saturating_sub(max_size);
+    let evicted: Vec<String> = candidates
+        .into_iter()
+        .take(excess)
+        .map(|(_, key)| key)
+        .collect();
+    for key in &evicted {
+        if let Some(state) = cache.remove(key) {
+            log_removal(removals, key, &state, "size");
+        }
+    }
+    evicted
+}
+
+fn evict_lru(
+    cache: &mut AllShards<'_, PyEntryState>,
+    tenant: &TenantState,
+    removals: Option<&RemovalLog>,
+) -> Option<String> {
+    let prefix = tenant.prefix();
+    let lru = cache
+        .iter()
+        .filter(|(key, state)| key.starts_with(&prefix) && state.is_ready())
+        .min_by_key(|(_, state)| state.last_access())
+        .map(|(key, _)| key.to_string())?;
+    tenant.evictions.fetch_add(1, Ordering::Relaxed);
+    if let Some(state) = cache.remove(&lru) {
+        log_removal(removals, &lru, &state, "quota");
+    }
+    Some(lru)
+}
+
+fn log_removal(
+    removals: Option<&RemovalLog>,
+    key: impl fmt::Display,
+    state: &PyEntryState,
+    reason: &'static str,
+) {
+    if let Some(removals) = removals {
+        removals.record(&key.to_string(), reason, state.weight());
+    }
+}
+
+fn notify_evicted(
+    py: Python<'_>,
+    hooks: &HookDispatcher,
+    on_evict: &Py<PyAny>,
+    keys: Vec<String>,
+    reason: &str,
+) {
+    for key in keys {
+        hooks.dispatch(py, "on_evict", on_evict, (key, reason), None);
+    }
+}
+
+fn size_of(py: Python<'_>, value: &Py<PyAny>) -> usize {
+    py.import("sys")
+        .and_then(|sys| sys.call_method1("getsizeof", (value,)))
+        .and_then(|size| size.extract())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::{
+        ffi::c_str,
+        types::{IntoPyDict, PyTuple},
+    };
+
+    use std::ffi::CStr;
+
+    // Defines `f` with the Python source `code`
+    fn define(py: Python<'_>, code: &CStr) -> Py<PyAny> {
+        let globals = PyDict::new(py);
+        py.run(code, Some(&globals), None).unwrap();
+        globals.get_item("f").unwrap().unwrap().unbind()
+    }
+
+    // Calls `func` without arguments through the cache under `key`
+    fn call_func(
+        pycache: &PyCache,
+        py: Python<'_>,
+        func: &Py<PyAny>,
+        key: &str,
+        options: CallOptions,
+    ) -> PyResult<Py<PyAny>> {
+        pycache.call(
+            py,
+            func.clone_ref(py),
+            PyTuple::empty(py).into_any().unbind(),
+            PyDict::new(py).into_any().unbind(),
+            key,
+            options,
+        )
+    }
+
+    // Blocks without the GIL until `key` has a flight in progress
+    fn await_pending(pycache: &PyCache, py: Python<'_>, key: &str) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !pycache
+            .pending_keys(py)
+            .iter()
+            .any(|pending| pending == key)
+        {
+            assert!(Instant::now() < deadline, "no flight for '{}'", key);
+            py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+        }
+    }
+
+    // Stores `value` under each of `keys`
+    fn store_all(pycache: &PyCache, py: Python<'_>, keys: &[&str], value: i64) {
+        for key in keys {
+            let value = value.into_pyobject(py).unwrap().into_any().unbind();
+            assert!(pycache.store(py, key, value, None).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_pycall() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        let args: [i8; 2] = [1, 10];
+        let kwargs: [(&'static str, i16); 1] = [("multiplier", 100)];
+        let test_key: String = "test".to_string();
+
+        Python::with_gil(|py| {
+            let pycache = Bound::new(py, pycache).unwrap();
+            let pyfunc: Py<PyAny> = PyModule::from_code(
+                py,
+                c_str!(
+                    "from random import randint
+
+def f(lower, upper, multiplier):
+                        return randint(lower, upper)*multiplier"
+                ),
+                c_str!(""),
+                c_str!(""),
+            )
+            .unwrap()
+            .getattr("f")
+            .unwrap()
+            .into();
+            let py_args: Bound<'_, PyTuple> = PyTuple::new(py, args).unwrap();
+            let py_kwargs: Bound<'_, PyDict> = kwargs.into_py_dict(py).unwrap();
+
+            let _ = PyCache::py_call(
+                &pycache,
+                pyfunc.clone_ref(py),
+                py_args.clone().into(),
+                py_kwargs.into(),
+                test_key.clone(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            );
+
+            // Assert state of cache
+            let cache = pycache.get().cache.lock(&test_key).unwrap();
+            let cached_entry = cache.get(&test_key).unwrap();
+            let expected: i32 = match cached_entry {
+                PyEntryState::Pending(val) => {
+                    let (lock, _) = &**val;
+                    let entry = lock.lock().unwrap();
+                    assert!(entry.ready);
+                    entry.value.as_ref().unwrap().extract::<i32>(py).unwrap()
+                }
+            };
+            drop(cache);
+            let actual = PyCache::py_call(
+                &pycache,
+                pyfunc.clone_ref(py),
+                py_args.clone().into(),
+                PyDict::new(py).into(),
+                test_key,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+            .extract::<i32>(py)
+            .unwrap();
+
+            assert_eq!(actual, expected);
+        })
+    }
+
+    #[test]
+    fn test_pycall_error() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let pyfunc: Py<PyAny> = PyModule::from_code(
+                py,
+                c_str!("def f():\n    raise ValueError('boom')"),
+                c_str!(""),
+                c_str!(""),
+            )
+            .unwrap()
+            .getattr("f")
+            .unwrap()
+            .into();
+
+            let err = pycache
+                .call(
+                    py,
+                    pyfunc,
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    "test",
+                    CallOptions::default(),
+                )
+                .unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+
+            // The failed flight is not kept, so the next call starts afresh
+            assert!(pycache.cache.lock("test").unwrap().get("test").is_none());
+            assert_eq!(pycache.stats.failed_calls.load(Ordering::Relaxed), 1);
+        })
+    }
+
+    #[test]
+    fn test_failure_handoff() {
+        let pycache = PyCache::with_config(CacheConfig {
+            failure_policy: FailurePolicy::Promote,
+            max_handoffs: 1,
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let token = Py::new(py, CancelToken::default()).unwrap();
+            let mut entry =
+                PyCacheEntry::pending(token, CallOptions::default(), pycache.memory.clone());
+            entry.waiters = 1;
+            let flight = Arc::new((Mutex::new(entry), FlightSignal::new(None, "test")));
+            pycache
+                .cache
+                .lock("test")
+                .unwrap()
+                .insert("test", PyEntryState::Pending(flight.clone()));
+            let err = PyValueError::new_err("boom");
+
+            // The first failure hands the flight to the waiter
+            pycache.fail_pending(py, "test", &flight, &err);
+            assert!(flight.0.lock().unwrap().handoff);
+            assert!(pycache.cache.lock("test").unwrap().get("test").is_some());
+
+            // Once handoffs run out the error reaches every waiter
+            flight.0.lock().unwrap().handoff = false;
+            pycache.fail_pending(py, "test", &flight, &err);
+            assert!(flight.0.lock().unwrap().error.is_some());
+            assert!(pycache.cache.lock("test").unwrap().get("test").is_none());
+            assert_eq!(pycache.stats.handoffs.load(Ordering::Relaxed), 1);
+        })
+    }
+
+    #[test]
+    fn test_external_completion() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            assert!(pycache.start(py, "test").unwrap());
+            assert!(!pycache.start(py, "test").unwrap());
+            assert!(pycache.lookup(py, "test").unwrap().is_none());
+
+            let value = 42i64.into_pyobject(py).unwrap().into_any().unbind();
+            assert!(pycache.complete(py, "test", value).unwrap());
+            let waited = pycache.wait_for(py, "test", None).unwrap();
+            assert_eq!(waited.extract::<i32>(py).unwrap(), 42);
+
+            // Readers never start a flight of their own
+            let err = pycache.wait_for(py, "missing", Some(0.01)).unwrap_err();
+            assert!(err.is_instance_of::<PyTimeoutError>(py));
+            assert!(pycache
+                .cache
+                .lock("missing")
+                .unwrap()
+                .get("missing")
+                .is_none());
+
+            // Failing with nothing in flight is a no-op
+            let err = PyValueError::new_err("boom").into_value(py).into_any();
+            assert!(!pycache.fail(py, "test", err.into_bound(py)).unwrap());
+        })
+    }
+
+    #[test]
+    fn test_ttl() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            ttl: Some(Duration::ZERO),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let value = 42i64.into_pyobject(py).unwrap().into_any().unbind();
+            assert!(pycache.store(py, "test", value, None).unwrap());
+            assert!(pycache.lookup(py, "test").unwrap().is_none());
+        })
+    }
+
+    #[test]
+    fn test_max_size() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            max_size: Some(2),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            for key in ["a", "b", "c"] {
+                let value = key.into_pyobject(py).unwrap().into_any().unbind();
+                assert!(pycache.store(py, key, value, None).unwrap());
+            }
+            assert!(pycache.lookup(py, "a").unwrap().is_none());
+            assert!(pycache.lookup(py, "c").unwrap().is_some());
+            assert_eq!(pycache.stats.size_evictions.load(Ordering::Relaxed), 1);
+        })
+    }
+
+    #[test]
+    fn test_store_results_disabled() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            store_results: false,
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let pyfunc = py.eval(c_str!("lambda: 42"), None, None).unwrap();
+            let value = pycache
+                .call(
+                    py,
+                    pyfunc.unbind(),
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    "test",
+                    CallOptions::default(),
+                )
+                .unwrap();
+            assert_eq!(value.extract::<i32>(py).unwrap(), 42);
+            assert!(pycache.cache.lock("test").unwrap().get("test").is_none());
+        })
+    }
+
+    #[test]
+    fn test_negative_ttl() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            negative_ttl: Some(Duration::ZERO),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            for (key, func) in [
+                ("missing", c_str!("lambda: None")),
+                ("found", c_str!("lambda: 1")),
+            ] {
+                let pyfunc = py.eval(func, None, None).unwrap();
+                pycache
+                    .call(
+                        py,
+                        pyfunc.unbind(),
+                        PyTuple::empty(py).into_any().unbind(),
+                        PyDict::new(py).into_any().unbind(),
+                        key,
+                        CallOptions::default(),
+                    )
+                    .unwrap();
+            }
+            assert!(pycache.lookup(py, "missing").unwrap().is_none());
+            assert!(pycache.lookup(py, "found").unwrap().is_some());
+        })
+    }
+
+    #[test]
+    fn test_lazy_threads() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+        let components = |pycache: &PyCache| -> Vec<&'static str> {
+            pycache
+                .supervisor
+                .components()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+        assert!(components(&pycache).is_empty());
+
+        Python::with_gil(|py| {
+            let pyfunc = py.eval(c_str!("lambda: 42"), None, None).unwrap();
+            pycache
+                .call(
+                    py,
+                    pyfunc.unbind(),
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    "test",
+                    CallOptions::default(),
+                )
+                .unwrap();
+            // No hooks are configured, so the dispatcher stays idle
+            assert_eq!(components(&pycache), ["sweeper"]);
+
+            pycache.refresh(py, "test".to_string(), "normal").unwrap();
+            assert_eq!(components(&pycache), ["refresher", "sweeper"]);
+        })
+    }
+
+    #[test]
+    fn test_for_each_entry() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["user:1", "user:2", "item:1"], 1);
+            assert!(pycache.start(py, "user:3").unwrap());
+
+            let spec = |items: &[(&str, &str)]| items.into_py_dict(py).unwrap();
+            let count = |spec: Bound<'_, PyDict>| -> usize {
+                pycache
+                    .for_each_entry_rust(py, Some(&spec), "count")
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            };
+            assert_eq!(count(spec(&[("prefix", "user:")])), 3);
+            assert_eq!(count(spec(&[("prefix", "user:"), ("state", "ready")])), 2);
+            let pending: Vec<String> = pycache
+                .for_each_entry_rust(py, Some(&spec(&[("state", "pending")])), "export")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(pending, ["user:3"]);
+
+            // Evicting never touches the flight in progress
+            let evicted: usize = pycache
+                .for_each_entry_rust(py, Some(&spec(&[("prefix", "user:")])), "evict")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(evicted, 2);
+            assert_eq!(pycache.pending_keys(py), ["user:3"]);
+            assert_eq!(pycache.ready_keys(py), ["item:1"]);
+
+            assert!(pycache
+                .for_each_entry_rust(py, Some(&spec(&[("state", "stale")])), "count")
+                .is_err());
+            assert!(pycache.for_each_entry_rust(py, None, "delete").is_err());
+        })
+    }
+
+    #[test]
+    fn test_orphan_sweep() {
+        let pycache = PyCache::with_config(CacheConfig {
+            sweep_interval: Duration::from_millis(10),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            // A leader that went away without resolving leaves its
+            // placeholder as the only reference to the flight
+            let token = Py::new(py, CancelToken::default()).unwrap();
+            let entry =
+                PyCacheEntry::pending(token, CallOptions::default(), pycache.memory.clone());
+            pycache.cache.lock("orphan").unwrap().insert(
+                "orphan",
+                PyEntryState::new(entry, FlightSignal::new(None, "orphan")),
+            );
+            // Flights opened with `start` have no leader to lose
+            assert!(pycache.start(py, "external").unwrap());
+            pycache.start_sweeper();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            py.allow_threads(|| {
+                while pycache
+                    .stats
+                    .orphaned_pendings_reclaimed
+                    .load(Ordering::Relaxed)
+                    == 0
+                {
+                    assert!(Instant::now() < deadline, "orphan was not reclaimed");
+                    thread::sleep(Duration::from_millis(10));
+                }
+            });
+            assert_eq!(pycache.pending_keys(py), ["external"]);
+        })
+    }
+
+    #[test]
+    fn test_cancel_abandoned() {
+        let pycache = PyCache::with_config(CacheConfig {
+            cancel_abandoned: true,
+            timeout_policy: TimeoutPolicy::Raise,
+            ..Default::default()
+        });
+        let lead = Python::with_gil(|py| {
+            define(
+                py,
+                c_str!(
+                    "import time
+def f(cancel_token):
+    deadline = time.monotonic() + 5
+    while not cancel_token.cancelled and time.monotonic() < deadline:
+        time.sleep(0.005)
+    return cancel_token.cancelled"
+                ),
+            )
+        });
+
+        thread::scope(|scope| {
+            let leader = scope.spawn(|| {
+                Python::with_gil(|py| {
+                    let value = call_func(&pycache, py, &lead, "slow", CallOptions::default());
+                    value.unwrap().extract::<bool>(py).unwrap()
+                })
+            });
+            Python::with_gil(|py| {
+                await_pending(&pycache, py, "slow");
+                let options = CallOptions {
+                    wait_timeout: Some(Some(Duration::from_millis(20))),
+                    ..Default::default()
+                };
+                let err = call_func(&pycache, py, &lead, "slow", options).unwrap_err();
+                assert!(err.is_instance_of::<PyTimeoutError>(py));
+            });
+            // The only waiter gave up, so the leader was asked to stop
+            assert!(leader.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_flight_ctx() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = define(
+                py,
+                c_str!(
+                    "def f(flight_ctx):
+    return flight_ctx.key, flight_ctx.attempt, flight_ctx.remaining, flight_ctx.cancelled"
+                ),
+            );
+            let value = call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            let (key, attempt, remaining, cancelled): (String, u32, f64, bool) =
+                value.extract(py).unwrap();
+            assert_eq!(key, "test");
+            assert_eq!(attempt, 1);
+            assert!(remaining > 0.0 && remaining <= 10.0);
+            assert!(!cancelled);
+        })
+    }
+
+    #[test]
+    fn test_retries() {
+        let pycache = PyCache::with_config(CacheConfig {
+            retries: 2,
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = define(
+                py,
+                c_str!(
+                    "def f(flight_ctx):
+    if flight_ctx.attempt < 3:
+        raise ValueError(flight_ctx.attempt)
+    return flight_ctx.last_exception.args[0]"
+                ),
+            );
+            let value = call_func(&pycache, py, &func, "flaky", CallOptions::default()).unwrap();
+            assert_eq!(value.extract::<u32>(py).unwrap(), 2);
+            assert_eq!(pycache.stats.retries.load(Ordering::Relaxed), 2);
+
+            // Past the retry budget the last error reaches the caller
+            let func = define(py, c_str!("def f():\n    raise ValueError('boom')"));
+            let err = call_func(&pycache, py, &func, "broken", CallOptions::default()).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            assert_eq!(pycache.stats.retries.load(Ordering::Relaxed), 4);
+        })
+    }
+
+    #[test]
+    fn test_post_process_once() {
+        let (post_process, slow) = Python::with_gil(|py| {
+            let post_process = define(
+                py,
+                c_str!(
+                    "def f(value):
+    f.calls.append(value)
+    return value * 2
+f.calls = []"
+                ),
+            );
+            let slow = define(
+                py,
+                c_str!("import time\ndef f():\n    time.sleep(0.05)\n    return 21"),
+            );
+            (post_process, slow)
+        });
+        let pycache = PyCache::with_config(CacheConfig {
+            post_process: Some(Python::with_gil(|py| post_process.clone_ref(py))),
+            ..Default::default()
+        });
+
+        thread::scope(|scope| {
+            let leader = scope.spawn(|| {
+                Python::with_gil(|py| {
+                    let value = call_func(&pycache, py, &slow, "test", CallOptions::default());
+                    value.unwrap().extract::<i32>(py).unwrap()
+                })
+            });
+            Python::with_gil(|py| {
+                await_pending(&pycache, py, "test");
+                let value = call_func(&pycache, py, &slow, "test", CallOptions::default());
+                assert_eq!(value.unwrap().extract::<i32>(py).unwrap(), 42);
+            });
+            assert_eq!(leader.join().unwrap(), 42);
+        });
+
+        Python::with_gil(|py| {
+            let value = call_func(&pycache, py, &slow, "test", CallOptions::default());
+            assert_eq!(value.unwrap().extract::<i32>(py).unwrap(), 42);
+            let calls: Vec<i32> = post_process
+                .getattr(py, "calls")
+                .unwrap()
+                .extract(py)
+                .unwrap();
+            assert_eq!(calls, [21]);
+        })
+    }
+
+    #[test]
+    fn test_access_tags() {
+        let check_access = Python::with_gil(|py| {
+            py.eval(
+                c_str!("lambda key, tags, context: context in tags"),
+                None,
+                None,
+            )
+            .unwrap()
+            .unbind()
+        });
+        let pycache = PyCache::with_config(CacheConfig {
+            check_access: Some(check_access),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 42"), None, None).unwrap().unbind();
+            let options = |context: &str| CallOptions {
+                tags: Some(PyList::new(py, ["admin"]).unwrap().into_any().unbind()),
+                context: Some(context.into_pyobject(py).unwrap().into_any().unbind()),
+                ..Default::default()
+            };
+            let value = call_func(&pycache, py, &func, "secret", options("admin")).unwrap();
+            assert_eq!(value.extract::<i32>(py).unwrap(), 42);
+
+            // Tags stay with the entry, so hits are checked too
+            let err = call_func(&pycache, py, &func, "secret", options("guest")).unwrap_err();
+            assert!(err.is_instance_of::<PyPermissionError>(py));
+            assert_eq!(pycache.stats.access_denied.load(Ordering::Relaxed), 1);
+        })
+    }
+
+    #[test]
+    fn test_to_arrow() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            // Stands in for pyarrow where it is not installed
+            py.run(
+                c_str!(
+                    "import sys, types
+try:
+    import pyarrow
+except ImportError:
+    def from_pydict(columns):
+        return types.SimpleNamespace(to_pydict=lambda: columns)
+    sys.modules['pyarrow'] = types.SimpleNamespace(
+        RecordBatch=types.SimpleNamespace(from_pydict=from_pydict)
+    )"
+                ),
+                None,
+                None,
+            )
+            .unwrap();
+            store_all(&pycache, py, &["a"], 42);
+            assert!(pycache.start(py, "b").unwrap());
+
+            let columns = pycache
+                .to_arrow(py, true)
+                .unwrap()
+                .call_method0("to_pydict")
+                .unwrap();
+            let mut keys: Vec<String> = columns.get_item("key").unwrap().extract().unwrap();
+            keys.sort();
+            assert_eq!(keys, ["a", "b"]);
+            let values = columns.get_item("value").unwrap();
+            let pickle = py.import("pickle").unwrap();
+            let unpickled: Vec<Option<i64>> = values
+                .try_iter()
+                .unwrap()
+                .map(|value| {
+                    let value = value.unwrap();
+                    match value.is_none() {
+                        true => None,
+                        false => Some(
+                            pickle
+                                .call_method1("loads", (value,))
+                                .unwrap()
+                                .extract()
+                                .unwrap(),
+                        ),
+                    }
+                })
+                .collect();
+            assert!(unpickled.contains(&Some(42)));
+            assert!(unpickled.contains(&None));
+        })
+    }
+
+    #[test]
+    fn test_debug_dump() {
+        let pycache = PyCache::with_config(CacheConfig {
+            wait_timeout: Some(Duration::from_millis(1500)),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["a", "b", "c"], 1);
+            assert!(pycache.start(py, "pending").unwrap());
+
+            let dump = pycache.debug_dump(py, 2).unwrap();
+            let pending = dump.get_item("pending").unwrap().unwrap();
+            assert_eq!(pending.len().unwrap(), 1);
+            let flight = pending.get_item(0).unwrap();
+            let key: String = flight.get_item("key").unwrap().extract().unwrap();
+            assert_eq!(key, "pending");
+            assert_eq!(dump.get_item("sample").unwrap().unwrap().len().unwrap(), 2);
+
+            let config = dump.get_item("config").unwrap().unwrap();
+            let wait_timeout: f64 = config.get_item("wait_timeout").unwrap().extract().unwrap();
+            assert_eq!(wait_timeout, 1.5);
+            for section in ["stats", "health"] {
+                assert!(dump.get_item(section).unwrap().is_some());
+            }
+            // The dump has to serialize as is for the diagnostics endpoint
+            py.import("json")
+                .unwrap()
+                .call_method1("dumps", (dump,))
+                .unwrap();
+        })
+    }
+
+    #[test]
+    fn test_func_mismatch() {
+        Python::with_gil(|py| {
+            let int = py.get_type::<PyInt>().into_any().unbind();
+            let str = py.get_type::<PyString>().into_any().unbind();
+
+            let pycache = PyCache::with_config(CacheConfig {
+                on_func_mismatch: Some(MismatchPolicy::Raise),
+                ..Default::default()
+            });
+            call_func(&pycache, py, &int, "test", CallOptions::default()).unwrap();
+            let err = call_func(&pycache, py, &str, "test", CallOptions::default()).unwrap_err();
+            assert!(err.is_instance_of::<FunctionMismatch>(py));
+
+            // Partitioning gives each function its own entry under the key
+            let pycache = PyCache::with_config(CacheConfig {
+                on_func_mismatch: Some(MismatchPolicy::Partition),
+                ..Default::default()
+            });
+            let value = call_func(&pycache, py, &int, "test", CallOptions::default()).unwrap();
+            assert_eq!(value.extract::<i32>(py).unwrap(), 0);
+            let value = call_func(&pycache, py, &str, "test", CallOptions::default()).unwrap();
+            assert_eq!(value.extract::<String>(py).unwrap(), "");
+            assert_eq!(pycache.ready_keys(py).len(), 2);
+            assert_eq!(pycache.stats.func_mismatches.load(Ordering::Relaxed), 0);
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        Python::with_gil(|py| {
+            let pycache = PyCache::with_config(CacheConfig::default());
+            let report = pycache.validate(py).unwrap();
+            let ok: bool = report.get_item("ok").unwrap().unwrap().extract().unwrap();
+            assert!(ok);
+            // The self-test cleans up after itself
+            assert!(pycache.ready_keys(py).is_empty());
+
+            let pycache = PyCache::with_config(CacheConfig {
+                on_hit: Some(42i64.into_pyobject(py).unwrap().into_any().unbind()),
+                ..Default::default()
+            });
+            let report = pycache.validate(py).unwrap();
+            let ok: bool = report.get_item("ok").unwrap().unwrap().extract().unwrap();
+            assert!(!ok);
+            let problems: Vec<String> = report
+                .get_item("problems")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(problems, ["on_hit: hook is not callable"]);
+
+            let status = |report: &Bound<'_, PyDict>, name: &str| -> String {
+                let checks: Vec<Bound<'_, PyDict>> = report
+                    .get_item("checks")
+                    .unwrap()
+                    .unwrap()
+                    .extract()
+                    .unwrap();
+                let check = checks
+                    .iter()
+                    .find(|check| get_option::<String>(check, "name").unwrap().unwrap() == name)
+                    .unwrap();
+                get_option(check, "status").unwrap().unwrap()
+            };
+
+            // With a ttl the probe really expires, and its traffic stays out
+            // of the stats, the hooks and the removal log
+            let calls = PyList::empty(py);
+            let pycache = PyCache::with_config(CacheConfig {
+                ttl: Some(Duration::from_secs(60)),
+                on_miss: Some(calls.getattr("append").unwrap().unbind()),
+                removal_log: Some(10),
+                ..Default::default()
+            });
+            let report = pycache.validate(py).unwrap();
+            for name in ["compute", "hit", "evict", "expire"] {
+                assert_eq!(status(&report, name), "ok", "{}", name);
+            }
+            let stats = pycache.stats(py).unwrap();
+            for counter in ["hits", "misses"] {
+                assert_eq!(get_option::<u64>(&stats, counter).unwrap(), Some(0));
+            }
+            let removals = pycache.removals.as_ref().unwrap();
+            assert!(removals.to_list(py, None).unwrap().is_empty());
+            assert!(pycache.ready_keys(py).is_empty());
+            // Hooks run in order, so a real miss shows up alone
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while calls.is_empty() {
+                assert!(Instant::now() < deadline, "hooks were not called");
+                py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+            }
+            assert_eq!(calls.extract::<Vec<String>>().unwrap(), ["test"]);
+
+            // Nothing is stored to hit without store_results
+            let pycache = PyCache::with_config(CacheConfig {
+                store_results: false,
+                ..Default::default()
+            });
+            let report = pycache.validate(py).unwrap();
+            assert_eq!(status(&report, "compute"), "ok");
+            for name in ["hit", "evict", "expire"] {
+                assert_eq!(status(&report, name), "skipped", "{}", name);
+            }
+            assert!(report
+                .get_item("ok")
+                .unwrap()
+                .unwrap()
+                .extract::<bool>()
+                .unwrap());
+        })
+    }
+
+    #[test]
+    fn test_meta_passthrough() {
+        let (check_access, on_miss) = Python::with_gil(|py| {
+            let check_access = py
+                .eval(
+                    c_str!("lambda key, tags, context, meta: meta['user'] == 'alice'"),
+                    None,
+                    None,
+                )
+                .unwrap()
+                .unbind();
+            let on_miss = define(
+                py,
+                c_str!("def f(key, meta):\n    f.calls.append((key, meta['user']))\nf.calls = []"),
+            );
+            (check_access, on_miss)
+        });
+        let pycache = PyCache::with_config(CacheConfig {
+            check_access: Some(check_access),
+            on_miss: Some(Python::with_gil(|py| on_miss.clone_ref(py))),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 42"), None, None).unwrap().unbind();
+            let options = |user: &str| CallOptions {
+                meta: Some(
+                    [("user", user)]
+                        .into_py_dict(py)
+                        .unwrap()
+                        .into_any()
+                        .unbind(),
+                ),
+                ..Default::default()
+            };
+            call_func(&pycache, py, &func, "test", options("alice")).unwrap();
+            let err = call_func(&pycache, py, &func, "test", options("bob")).unwrap_err();
+            assert!(err.is_instance_of::<PyPermissionError>(py));
+
+            // The leader's meta stays with the entry
+            let info = pycache.entry_info(py, "test").unwrap().unwrap();
+            let meta = info.get_item("meta").unwrap().unwrap();
+            let user: String = meta.get_item("user").unwrap().extract().unwrap();
+            assert_eq!(user, "alice");
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let calls = on_miss.getattr(py, "calls").unwrap();
+            while calls.bind(py).len().unwrap() == 0 {
+                assert!(Instant::now() < deadline, "on_miss was not called");
+                py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+            }
+            let calls: Vec<(String, String)> = calls.extract(py).unwrap();
+            assert_eq!(calls, [("test".to_string(), "alice".to_string())]);
+        })
+    }
+
+    #[test]
+    fn test_write_policy() {
+        let slow = Python::with_gil(|py| {
+            define(
+                py,
+                c_str!("import time\ndef f():\n    time.sleep(0.05)\n    return 2"),
+            )
+        });
+        for (policy, expected) in [(WritePolicy::First, 1), (WritePolicy::Last, 2)] {
+            let pycache = PyCache::with_config(CacheConfig {
+                write_policy: policy,
+                ..Default::default()
+            });
+            thread::scope(|scope| {
+                let leader = scope.spawn(|| {
+                    Python::with_gil(|py| {
+                        call_func(&pycache, py, &slow, "test", CallOptions::default()).unwrap();
+                    })
+                });
+                Python::with_gil(|py| {
+                    await_pending(&pycache, py, "test");
+                    let value = 1i64.into_pyobject(py).unwrap().into_any().unbind();
+                    // Only the first policy lets the manual write resolve the flight
+                    let written = pycache.set(py, "test", value, None, None).unwrap();
+                    assert_eq!(written, policy == WritePolicy::First);
+                });
+                leader.join().unwrap();
+            });
+            Python::with_gil(|py| {
+                let value = pycache.lookup(py, "test").unwrap().unwrap();
+                assert_eq!(value.extract::<i64>(py).unwrap(), expected);
+                assert_eq!(pycache.stats.write_conflicts.load(Ordering::Relaxed), 1);
+            });
+        }
+    }
+
+    #[test]
+    fn test_overdue_flights() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["ready"], 1);
+            assert!(pycache.start(py, "stuck").unwrap());
+            assert!(overdue_flights(&pycache.cache, Duration::from_secs(60)).is_empty());
+
+            let overdue = overdue_flights(&pycache.cache, Duration::ZERO);
+            let keys: Vec<_> = overdue.iter().map(|(key, ..)| key.as_str()).collect();
+            assert_eq!(keys, ["stuck"]);
+            // Each flight raises the alarm once
+            assert!(overdue_flights(&pycache.cache, Duration::ZERO).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_metadata_snapshot() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["a"], 1);
+            assert!(pycache.start(py, "pending").unwrap());
+
+            let snapshot = pycache.metadata_snapshot(py).unwrap();
+            let entries = snapshot.get_item("entries").unwrap().unwrap();
+            assert_eq!(entries.len().unwrap(), 1);
+            let entry = entries.get_item(0).unwrap();
+            let key: String = entry.get_item("key").unwrap().extract().unwrap();
+            assert_eq!(key, "a");
+            assert!(!entry.contains("value").unwrap());
+            let version: u32 = snapshot
+                .get_item("version")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(version, METADATA_VERSION);
+        })
+    }
+
+    #[test]
+    fn test_history() {
+        let pycache = PyCache::with_config(CacheConfig {
+            keep_history: 2,
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            for value in 1..=4 {
+                store_all(&pycache, py, &["test"], value);
+            }
+            let history: Vec<i64> = pycache
+                .history(py, "test")
+                .unwrap()
+                .into_iter()
+                .map(|(_, value)| value.extract(py).unwrap())
+                .collect();
+            assert_eq!(history, [4, 3, 2]);
+            assert!(pycache.history(py, "missing").unwrap().is_empty());
+        })
+    }
+
+    // Runs the refresh of `key` on the calling thread
+    fn refresh_now(pycache: &PyCache, py: Python<'_>, key: &str) -> RefreshOutcome {
+        assert!(pycache.refresh.push(key.to_string(), RefreshLane::Normal));
+        let job = pycache.refresh.pop(Duration::ZERO).unwrap();
+        pycache.refresh.forget(&job.key);
+        refresh_entry(
+            py,
+            &pycache.cache,
+            &pycache.config,
+            &pycache.memory,
+            pycache.stripes.as_deref(),
+            &job,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_delta_refresh() {
+        Python::with_gil(|py| {
+            let refresher = py.eval(c_str!("lambda key: [key]"), None, None).unwrap();
+            for (delta_refresh, outcome) in [
+                (true, RefreshOutcome::Unchanged),
+                (false, RefreshOutcome::Updated),
+            ] {
+                let pycache = PyCache::with_config(CacheConfig {
+                    delta_refresh,
+                    ..Default::default()
+                });
+                let value = PyList::new(py, ["test"]).unwrap().into_any().unbind();
+                let refresher = Some(refresher.clone().unbind());
+                pycache
+                    .set(py, "test", value.clone_ref(py), None, refresher)
+                    .unwrap();
+                assert!(refresh_now(&pycache, py, "test") == outcome);
+                // An equal value keeps the object readers already hold
+                let current = pycache.lookup(py, "test").unwrap().unwrap();
+                assert_eq!(current.is(&value), delta_refresh);
+            }
+        })
+    }
+
+    #[test]
+    fn test_refresher_override() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            // Computed entries refresh with the function that computed them
+            let func = py
+                .eval(c_str!("lambda: 'computed'"), None, None)
+                .unwrap()
+                .unbind();
+            call_func(&pycache, py, &func, "computed", CallOptions::default()).unwrap();
+            let refresher = py
+                .eval(c_str!("lambda key: key.upper()"), None, None)
+                .unwrap();
+            let value = "seeded".into_pyobject(py).unwrap().into_any().unbind();
+            pycache
+                .set(py, "seeded", value, None, Some(refresher.unbind()))
+                .unwrap();
+            store_all(&pycache, py, &["plain"], 1);
+
+            assert!(refresh_now(&pycache, py, "seeded") == RefreshOutcome::Updated);
+            let value = pycache.lookup(py, "seeded").unwrap().unwrap();
+            assert_eq!(value.extract::<String>(py).unwrap(), "SEEDED");
+            assert!(refresh_now(&pycache, py, "computed") == RefreshOutcome::Updated);
+            // Without a refresher there is nothing to refresh with
+            assert!(refresh_now(&pycache, py, "plain") == RefreshOutcome::Skipped);
+        })
+    }
+
+    #[test]
+    fn test_compute_timeout() {
+        let pycache = PyCache::with_config(CacheConfig {
+            compute_timeout: Some(Duration::from_millis(20)),
+            ..Default::default()
+        });
+        let slow = Python::with_gil(|py| {
+            define(
+                py,
+                c_str!("import time\ndef f():\n    time.sleep(0.3)\n    return 1"),
+            )
+        });
+
+        thread::scope(|scope| {
+            let leader = scope.spawn(|| {
+                Python::with_gil(|py| {
+                    let _ = call_func(&pycache, py, &slow, "slow", CallOptions::default());
+                })
+            });
+            Python::with_gil(|py| {
+                await_pending(&pycache, py, "slow");
+                // Waiters without a wait timeout still give up on the leader
+                let err = call_func(&pycache, py, &slow, "slow", CallOptions::default());
+                assert!(err.unwrap_err().is_instance_of::<ComputeTimeout>(py));
+            });
+            leader.join().unwrap();
+        });
+        assert_eq!(pycache.stats.compute_overruns.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_expire_at() {
+        let pycache = PyCache::with_config(CacheConfig {
+            ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 42"), None, None).unwrap().unbind();
+            // A wall-clock expiry overrides the cache-wide ttl
+            let options = CallOptions {
+                expires_at: Some(Instant::now()),
+                ..Default::default()
+            };
+            call_func(&pycache, py, &func, "expired", options).unwrap();
+            call_func(&pycache, py, &func, "kept", CallOptions::default()).unwrap();
+            assert!(pycache.lookup(py, "expired").unwrap().is_none());
+            assert!(pycache.lookup(py, "kept").unwrap().is_some());
+
+            let globals = [("datetime", py.import("datetime").unwrap())]
+                .into_py_dict(py)
+                .unwrap();
+            let datetime = py
+                .eval(
+                    c_str!("datetime.datetime.now() + datetime.timedelta(seconds=30)"),
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+            let remaining = instant_from_datetime(&datetime)
+                .unwrap()
+                .saturating_duration_since(Instant::now());
+            assert!(remaining > Duration::from_secs(25) && remaining <= Duration::from_secs(30));
+        })
+    }
+
+    #[test]
+    fn test_degraded() {
+        let pycache = PyCache::with_config(CacheConfig {
+            ttl: Some(Duration::ZERO),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 42"), None, None).unwrap().unbind();
+            store_all(&pycache, py, &["stale"], 1);
+            assert!(!pycache.degraded(true, None));
+
+            // Stale values are served and misses never compute
+            let value = call_func(&pycache, py, &func, "stale", CallOptions::default()).unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 1);
+            let err = call_func(&pycache, py, &func, "missing", CallOptions::default());
+            assert!(err.unwrap_err().is_instance_of::<CacheDegraded>(py));
+            let default = "fallback".into_pyobject(py).unwrap().into_any().unbind();
+            pycache.degraded(true, Some(default));
+            let value = call_func(&pycache, py, &func, "missing", CallOptions::default()).unwrap();
+            assert_eq!(value.extract::<String>(py).unwrap(), "fallback");
+
+            assert!(pycache.degraded(false, None));
+            let value = call_func(&pycache, py, &func, "stale", CallOptions::default()).unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 42);
+        })
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["int"], 42);
+            let value = PyBytes::new(py, b"raw").into_any().unbind();
+            pycache.set(py, "bytes", value, None, None).unwrap();
+
+            assert_eq!(pycache.get_int(py, "int").unwrap(), Some(42));
+            let bytes = pycache.get_bytes(py, "bytes").unwrap().unwrap();
+            assert_eq!(bytes.as_bytes(py), b"raw");
+            assert!(pycache.get_str(py, "missing").unwrap().is_none());
+
+            let err = pycache.get_str(py, "int").unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+        })
+    }
+
+    #[test]
+    fn test_provenance() {
+        Python::with_gil(|py| {
+            let pycache = PyCache::with_config(CacheConfig {
+                record_provenance: true,
+                ..Default::default()
+            });
+            let cache = Bound::new(py, pycache).unwrap();
+            let globals = [("__name__", "loader")].into_py_dict(py).unwrap();
+            globals.set_item("cache", &cache).unwrap();
+            py.run(c_str!("\ncache.set('seeded', 1)"), Some(&globals), None)
+                .unwrap();
+
+            let pycache = cache.get();
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            let options = CallOptions {
+                provenance: Some("backfill".to_string()),
+                ..Default::default()
+            };
+            call_func(pycache, py, &func, "labelled", options).unwrap();
+
+            let provenance = |key: &str| -> Option<String> {
+                let info = pycache.entry_info(py, key).unwrap().unwrap();
+                info.get_item("provenance")
+                    .unwrap()
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            };
+            assert_eq!(provenance("seeded").as_deref(), Some("loader:2"));
+            assert_eq!(provenance("labelled").as_deref(), Some("backfill"));
+        })
+    }
+
+    #[test]
+    fn test_wake_strategies() {
+        for wake in [
+            WakeStrategy::All,
+            WakeStrategy::Chain,
+            WakeStrategy::Batch(2),
+        ] {
+            let pycache = PyCache::with_config(CacheConfig {
+                wake,
+                ..Default::default()
+            });
+            Python::with_gil(|py| assert!(pycache.start(py, "test").unwrap()));
+
+            thread::scope(|scope| {
+                let waiters: Vec<_> = (0..5)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            Python::with_gil(|py| {
+                                let value = pycache.wait_for(py, "test", Some(5.0)).unwrap();
+                                value.extract::<i64>(py).unwrap()
+                            })
+                        })
+                    })
+                    .collect();
+                thread::sleep(Duration::from_millis(20));
+                Python::with_gil(|py| {
+                    let value = 42i64.into_pyobject(py).unwrap().into_any().unbind();
+                    assert!(pycache.complete(py, "test", value).unwrap());
+                });
+                // However they are woken, every waiter gets the value
+                for waiter in waiters {
+                    assert_eq!(waiter.join().unwrap(), 42);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_hit_path() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            let func = py
+                .eval(c_str!("lambda: object()"), None, None)
+                .unwrap()
+                .unbind();
+            let first = call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            let second = call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            // Hits hand out the stored object itself
+            assert!(second.is(&first));
+            assert_eq!(pycache.stats.hits.load(Ordering::Relaxed), 1);
+            assert_eq!(pycache.stats.misses.load(Ordering::Relaxed), 1);
+            let info = pycache.entry_info(py, "test").unwrap().unwrap();
+            let hits: u64 = info.get_item("hits").unwrap().unwrap().extract().unwrap();
+            assert_eq!(hits, 1);
+        })
+    }
+
+    #[test]
+    fn test_lru() {
+        let pycache = PyCache::with_config(CacheConfig {
+            max_size: Some(2),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            for key in ["a", "b", "a", "c"] {
+                call_func(&pycache, py, &func, key, CallOptions::default()).unwrap();
+            }
+            // "a" was used after "b", so "b" is the one evicted
+            let mut keys = pycache.keys(py);
+            keys.sort();
+            assert_eq!(keys, ["a", "c"]);
+        })
+    }
+
+    #[test]
+    fn test_leases() {
+        Python::with_gil(|py| {
+            let cache = Bound::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let int = |value: i64| value.into_pyobject(py).unwrap().into_any().unbind();
+            let lease = Bound::new(py, PyCache::lease(&cache, "test", 60.0).unwrap()).unwrap();
+            let err = PyCache::lease(&cache, "test", 60.0).err().unwrap();
+            assert!(err.is_instance_of::<LeaseHeld>(py));
+
+            // Only the holder may write while the lease lasts
+            let err = cache.get().set(py, "test", int(1), None, None).unwrap_err();
+            assert!(err.is_instance_of::<LeaseHeld>(py));
+            assert!(lease
+                .call_method1("set", (2,))
+                .unwrap()
+                .is_truthy()
+                .unwrap());
+            assert_eq!(cache.get().get_int(py, "test").unwrap(), Some(2));
+
+            assert!(lease.call_method0("release").unwrap().is_truthy().unwrap());
+            assert!(cache.get().set(py, "test", int(3), None, None).unwrap());
+        })
+    }
+
+    #[test]
+    fn test_call_info() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+        let slow = Python::with_gil(|py| {
+            define(
+                py,
+                c_str!("import time\ndef f():\n    time.sleep(0.05)\n    return 1"),
+            )
+        });
+        let call = |py: Python<'_>| {
+            let mut info = CallInfo::default();
+            pycache
+                .call_with_info(
+                    py,
+                    slow.clone_ref(py),
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    "test",
+                    CallOptions::default(),
+                    &mut info,
+                )
+                .unwrap();
+            (info.leader, info.shared)
+        };
+
+        thread::scope(|scope| {
+            let leader = scope.spawn(|| Python::with_gil(call));
+            let waiter = Python::with_gil(|py| {
+                await_pending(&pycache, py, "test");
+                call(py)
+            });
+            assert_eq!(leader.join().unwrap(), (true, 1));
+            assert_eq!(waiter, (false, 1));
+        });
+        // Plain hits were neither led nor shared
+        assert_eq!(Python::with_gil(call), (false, 0));
+    }
+
+    #[test]
+    fn test_bulk_invalidation() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(
+                &pycache,
+                py,
+                &["user:1:name", "user:2:name", "user:2:mail", "item:1"],
+                1,
+            );
+            assert_eq!(pycache.drop_matching("user:?:name").unwrap(), 2);
+            assert_eq!(pycache.drop_prefix("user:").unwrap(), 1);
+            assert_eq!(pycache.drop_matching("*").unwrap(), 1);
+            assert!(pycache.keys(py).is_empty());
+
+            // Leased keys survive bulk drops like they survive drop()
+            store_all(&pycache, py, &["user:1:name", "user:2:name"], 1);
+            let lease = pycache
+                .leases
+                .acquire("user:1:name", Duration::from_secs(60));
+            assert!(lease.is_some());
+            assert!(pycache.drop(py, "user:1:name".to_string()).is_err());
+            assert_eq!(pycache.drop_prefix("user:").unwrap(), 1);
+            assert_eq!(pycache.drop_matching("*").unwrap(), 0);
+            assert_eq!(pycache.keys(py), ["user:1:name"]);
+        })
+    }
+
+    #[test]
+    fn test_stats() {
+        let pycache = PyCache::with_config(CacheConfig {
+            max_size: Some(1),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            for key in ["a", "a", "b"] {
+                call_func(&pycache, py, &func, key, CallOptions::default()).unwrap();
+            }
+            let stat = |name: &str| -> u64 {
+                let stats = pycache.stats(py).unwrap();
+                stats.get_item(name).unwrap().unwrap().extract().unwrap()
+            };
+            assert_eq!(stat("hits"), 1);
+            assert_eq!(stat("misses"), 2);
+            assert_eq!(stat("evictions"), 1);
+            assert_eq!(stat("entries"), 1);
+
+            assert_eq!(stat("timeouts"), 0);
+
+            // Counters reset, gauges keep reporting the current state
+            pycache.reset_stats();
+            assert_eq!(stat("hits"), 0);
+            assert_eq!(stat("misses"), 0);
+            assert_eq!(stat("entries"), 1);
+        });
+
+        // A waiter that timed out is also an abandoned one
+        let pycache = PyCache::with_config(CacheConfig {
+            timeout_policy: TimeoutPolicy::Raise,
+            ..Default::default()
+        });
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            assert!(pycache.start(py, "pending").unwrap());
+            let options = CallOptions {
+                wait_timeout: Some(Some(Duration::from_millis(10))),
+                ..Default::default()
+            };
+            assert!(call_func(&pycache, py, &func, "pending", options).is_err());
+            let stats = pycache.stats(py).unwrap();
+            for name in ["timeouts", "abandoned_waits"] {
+                let count: u64 = stats.get_item(name).unwrap().unwrap().extract().unwrap();
+                assert_eq!(count, 1);
+            }
+        })
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        let globals = Python::with_gil(|py| {
+            let globals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "events = []
+def hook(name):
+    return lambda key, *args: events.append((name, key))"
+                ),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            globals.unbind()
+        });
+        let hook = |name: &str| {
+            Python::with_gil(|py| {
+                let hook = globals.bind(py).get_item("hook").unwrap().unwrap();
+                Some(hook.call1((name,)).unwrap().unbind())
+            })
+        };
+        let pycache = PyCache::with_config(CacheConfig {
+            on_hit: hook("hit"),
+            on_miss: hook("miss"),
+            on_leader_start: hook("leader_start"),
+            on_leader_done: hook("leader_done"),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+
+            // Hooks run on the dispatch thread, in the order they fired
+            let events = globals.bind(py).get_item("events").unwrap().unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while events.len().unwrap() < 4 {
+                assert!(Instant::now() < deadline, "hooks were not called");
+                py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+            }
+            let events: Vec<(String, String)> = events.extract().unwrap();
+            let names: Vec<_> = events.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(names, ["miss", "leader_start", "leader_done", "hit"]);
+        })
+    }
+
+    #[test]
+    fn test_timeout_policy() {
+        let slow = Python::with_gil(|py| {
+            define(
+                py,
+                c_str!(
+                    "import time
+def f():
+    f.calls += 1
+    time.sleep(0.2 if f.calls == 1 else 0)
+    return f.calls
+f.calls = 0"
+                ),
+            )
+        });
+        for (policy, expected) in [(TimeoutPolicy::Fallback, -1), (TimeoutPolicy::Lead, 2)] {
+            Python::with_gil(|py| slow.setattr(py, "calls", 0).unwrap());
+            let pycache = PyCache::with_config(CacheConfig {
+                timeout_policy: policy,
+                timeout_fallback: Some(Python::with_gil(|py| {
+                    (-1i64).into_pyobject(py).unwrap().into_any().unbind()
+                })),
+                ..Default::default()
+            });
+            thread::scope(|scope| {
+                let leader = scope.spawn(|| {
+                    Python::with_gil(|py| {
+                        call_func(&pycache, py, &slow, "test", CallOptions::default()).unwrap();
+                    })
+                });
+                Python::with_gil(|py| {
+                    await_pending(&pycache, py, "test");
+                    let options = CallOptions {
+                        wait_timeout: Some(Some(Duration::from_millis(20))),
+                        ..Default::default()
+                    };
+                    let value = call_func(&pycache, py, &slow, "test", options).unwrap();
+                    assert_eq!(value.extract::<i64>(py).unwrap(), expected);
+                });
+                leader.join().unwrap();
+            });
+        }
+    }
+
+    #[test]
+    fn test_get_many() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["plain"], 1);
+            store_all(&pycache, py, &[&(tenant_prefix("acme") + "key")], 2);
+            assert!(pycache.start(py, "pending").unwrap());
+
+            let keys = vec![
+                "plain".into_pyobject(py).unwrap().into_any(),
+                ("acme", "key").into_pyobject(py).unwrap().into_any(),
+                "pending".into_pyobject(py).unwrap().into_any(),
+                ("other", "key").into_pyobject(py).unwrap().into_any(),
+            ];
+            let values: Vec<Option<i64>> = pycache
+                .get_many(py, keys)
+                .unwrap()
+                .into_iter()
+                .map(|value| value.map(|value| value.extract(py).unwrap()))
+                .collect();
+            assert_eq!(values, [Some(1), Some(2), None, None]);
+        })
+    }
+
+    #[test]
+    fn test_removal_log() {
+        let pycache = PyCache::with_config(CacheConfig {
+            removal_log: Some(10),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["dropped", "cleared"], 1);
+            pycache.drop(py, "dropped".to_string()).unwrap();
+            assert_eq!(pycache.clear(), 1);
+
+            let removed = pycache.recently_removed(py, None).unwrap();
+            let reasons: Vec<(String, String)> = removed
+                .iter()
+                .map(|item| {
+                    let key = item.get_item("key").unwrap().extract().unwrap();
+                    let reason = item.get_item("reason").unwrap().extract().unwrap();
+                    (key, reason)
+                })
+                .collect();
+            assert_eq!(
+                reasons,
+                [
+                    ("cleared".to_string(), "clear".to_string()),
+                    ("dropped".to_string(), "drop".to_string())
+                ]
+            );
+
+            let disabled = PyCache::with_config(CacheConfig::default());
+            assert!(disabled.recently_removed(py, None).is_err());
+        })
+    }
+
+    #[test]
+    fn test_freeze_override() {
+        let pycache = PyCache::with_config(CacheConfig {
+            freeze: true,
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let func = py.eval(c_str!("lambda: [1]"), None, None).unwrap().unbind();
+            let frozen = call_func(&pycache, py, &func, "frozen", CallOptions::default());
+            assert!(frozen.unwrap().bind(py).downcast::<PyTuple>().is_ok());
+
+            // The call's own policy wins over the cache's
+            let options = CallOptions {
+                freeze: Some(false),
+                ..Default::default()
+            };
+            let thawed = call_func(&pycache, py, &func, "thawed", options).unwrap();
+            assert!(thawed.bind(py).downcast::<PyList>().is_ok());
+        })
+    }
+
+    #[test]
+    fn test_kill_switch() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            let func = py
+                .eval(c_str!("lambda: object()"), None, None)
+                .unwrap()
+                .unbind();
+            let cached = call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+
+            // Disabled, calls run the function and leave the cache alone
+            pycache.set_enabled(false);
+            let direct = call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            assert!(!direct.is(&cached));
+            call_func(&pycache, py, &func, "other", CallOptions::default()).unwrap();
+            assert!(pycache.lookup(py, "other").unwrap().is_none());
+            pycache.set_enabled(true);
+            let value = call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            assert!(value.is(&cached));
+
+            let key = tenant_prefix("acme") + "key";
+            assert!(pycache.enable_namespace("acme", false));
+            call_func(&pycache, py, &func, &key, CallOptions::default()).unwrap();
+            assert!(pycache.lookup(py, &key).unwrap().is_none());
+            assert!(!pycache.enable_namespace("acme", true));
+            call_func(&pycache, py, &func, &key, CallOptions::default()).unwrap();
+            assert!(pycache.lookup(py, &key).unwrap().is_some());
+        })
+    }
+
+    #[test]
+    fn test_set_get() {
+        let pycache = PyCache::with_config(CacheConfig {
+            ttl: Some(Duration::ZERO),
+            ..Default::default()
+        });
+
+        Python::with_gil(|py| {
+            let int = |value: i64| value.into_pyobject(py).unwrap().into_any().unbind();
+            // The per-entry ttl wins over the cache's, infinity keeps it for good
+            pycache
+                .set(py, "kept", int(1), Some(f64::INFINITY), None)
+                .unwrap();
+            pycache.set(py, "expired", int(2), None, None).unwrap();
+            assert!(pycache.set(py, "bad", int(3), Some(-1.0), None).is_err());
+
+            let value = pycache.get(py, "kept", None).unwrap().unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 1);
+            let value = pycache.get(py, "expired", Some(int(0))).unwrap().unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 0);
+            assert!(pycache.get(py, "missing", None).unwrap().is_none());
+        })
+    }
+
+    #[test]
+    fn test_emit_stats() {
+        Python::with_gil(|py| {
+            let cache = Bound::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let callback = define(
+                py,
+                c_str!("def f(stats):\n    f.calls.append(stats)\nf.calls = []"),
+            );
+            assert!(PyCache::emit_stats(&cache, callback.clone_ref(py), 0.0).is_err());
+            PyCache::emit_stats(&cache, callback.clone_ref(py), 0.01).unwrap();
+
+            let calls = callback.getattr(py, "calls").unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while calls.bind(py).len().unwrap() == 0 {
+                assert!(Instant::now() < deadline, "stats were not emitted");
+                py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+            }
+            let stats = calls.bind(py).get_item(0).unwrap();
+            assert!(stats.get_item("hits").is_ok());
+
+            // A second emitter is refused instead of replacing the first
+            let err = PyCache::emit_stats(&cache, callback.clone_ref(py), 0.01).unwrap_err();
+            assert!(err.is_instance_of::<PyRuntimeError>(py));
+            cache.get().close(py);
+            let (name, state) = cache.get().supervisor.components().pop().unwrap();
+            assert_eq!(name, "stats-emitter");
+            assert!(state.stopped.load(Ordering::SeqCst));
+            let emitted = calls.bind(py).len().unwrap();
+            py.allow_threads(|| thread::sleep(Duration::from_millis(30)));
+            assert_eq!(calls.bind(py).len().unwrap(), emitted);
+        });
+
+        // Without a strong reference left the emitter stops on its own
+        Python::with_gil(|py| {
+            let cache = Bound::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let callback = py.eval(c_str!("lambda stats: None"), None, None).unwrap();
+            PyCache::emit_stats(&cache, callback.unbind(), 0.01).unwrap();
+            let (_, state) = cache.get().supervisor.components().pop().unwrap();
+            drop(cache);
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !state.stopped.load(Ordering::SeqCst) {
+                assert!(Instant::now() < deadline, "the emitter outlived its cache");
+                py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+            }
+        })
+    }
+
+    #[test]
+    fn test_wait_for_late_flight() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        thread::scope(|scope| {
+            // The reader shows up before anyone started computing
+            let reader = scope.spawn(|| {
+                Python::with_gil(|py| {
+                    let value = pycache.wait_for(py, "test", Some(5.0)).unwrap();
+                    value.extract::<i64>(py).unwrap()
+                })
+            });
+            thread::sleep(Duration::from_millis(20));
+            Python::with_gil(|py| {
+                let func = py.eval(c_str!("lambda: 42"), None, None).unwrap().unbind();
+                call_func(&pycache, py, &func, "test", CallOptions::default()).unwrap();
+            });
+            assert_eq!(reader.join().unwrap(), 42);
+        });
+    }
+
+    #[test]
+    fn test_mapping_protocol() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["a", "b"], 1);
+            pycache
+                .set(py, "expired", py.None(), Some(0.0), None)
+                .unwrap();
+            assert!(pycache.start(py, "pending").unwrap());
+
+            // Only fresh values are visible
+            assert_eq!(pycache.__len__(py), 2);
+            let mut keys = pycache.keys(py);
+            keys.sort();
+            assert_eq!(keys, ["a", "b"]);
+            assert!(pycache.__contains__(py, "a").unwrap());
+            assert!(!pycache.__contains__(py, "expired").unwrap());
+            assert!(!pycache.__contains__(py, "pending").unwrap());
+            let value = pycache.__getitem__(py, "a").unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 1);
+            let err = pycache.__getitem__(py, "pending").unwrap_err();
+            assert!(err.is_instance_of::<PyKeyError>(py));
+            // Reading through the mapping is not a hit
+            assert_eq!(pycache.stats.hits.load(Ordering::Relaxed), 0);
+
+            let empty = PyCache::with_config(CacheConfig::default());
+            assert!(empty.__bool__());
+        })
+    }
+
+    #[test]
+    fn test_clear_drain() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["a", "b"], 1);
+            assert!(pycache.start(py, "pending").unwrap());
+            // Flights in progress are left to finish
+            assert_eq!(pycache.clear(), 2);
+            assert_eq!(pycache.pending_keys(py), ["pending"]);
+
+            store_all(&pycache, py, &["a"], 1);
+            assert!(!pycache.drain(py, Some(0.01)).unwrap());
+            assert_eq!(pycache.ready_keys(py), ["a"]);
+
+            let value = 1i64.into_pyobject(py).unwrap().into_any().unbind();
+            assert!(pycache.complete(py, "pending", value).unwrap());
+            assert!(pycache.drain(py, None).unwrap());
+            assert!(pycache.ready_keys(py).is_empty());
+            assert!(pycache.pending_keys(py).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_close() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["ready"], 1);
+            assert!(pycache.start(py, "pending").unwrap());
+        });
+        pycache.supervisor.spawn(
+            "idle",
+            |shutdown| {
+                while shutdown.sleep(Duration::from_secs(600)) {}
+            },
+        );
+
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                Python::with_gil(|py| {
+                    let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+                    let err = call_func(&pycache, py, &func, "pending", CallOptions::default());
+                    err.unwrap_err().is_instance_of::<CacheClosed>(py)
+                })
+            });
+            Python::with_gil(|py| {
+                let deadline = Instant::now() + Duration::from_secs(5);
+                loop {
+                    let blocked = pycache.inspect(py).unwrap().iter().any(|item| {
+                        let waiters: usize = item.get_item("waiters").unwrap().extract().unwrap();
+                        waiters > 0
+                    });
+                    if blocked {
+                        break;
+                    }
+                    assert!(Instant::now() < deadline, "the waiter never blocked");
+                    py.allow_threads(|| thread::sleep(Duration::from_millis(5)));
+                }
+            });
+            Python::with_gil(|py| pycache.close(py));
+            // Blocked waiters wake up instead of running into their timeout
+            assert!(waiter.join().unwrap());
+        });
+        // Background threads were stopped and joined by close
+        let (name, state) = pycache.supervisor.components().pop().unwrap();
+        assert_eq!(name, "idle");
+        assert!(state.stopped.load(Ordering::SeqCst));
+
+        Python::with_gil(|py| {
+            assert!(pycache.keys(py).is_empty());
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            let err = call_func(&pycache, py, &func, "ready", CallOptions::default());
+            assert!(err.unwrap_err().is_instance_of::<CacheClosed>(py));
+            let closed =
+                |result: PyResult<bool>| result.unwrap_err().is_instance_of::<CacheClosed>(py);
+            assert!(closed(pycache.set(py, "ready", py.None(), None, None)));
+            assert!(closed(pycache.complete(py, "pending", py.None())));
+            assert!(closed(pycache.get(py, "ready", None).map(|_| true)));
+            assert!(closed(pycache.drop_prefix("").map(|_| true)));
+            // Introspection keeps working
+            assert!(pycache.stats(py).is_ok());
+            pycache.close(py);
+        })
+    }
+
+    #[test]
+    fn test_inspect() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["ready"], 1);
+            pycache
+                .set(py, "expired", py.None(), Some(0.0), None)
+                .unwrap();
+            assert!(pycache.start(py, "pending").unwrap());
+
+            assert_eq!(pycache.pending_keys(py), ["pending"]);
+            let mut ready = pycache.ready_keys(py);
+            ready.sort();
+            assert_eq!(ready, ["expired", "ready"]);
+
+            let mut states: Vec<(String, String, bool)> = pycache
+                .inspect(py)
+                .unwrap()
+                .iter()
+                .map(|item| {
+                    let key = item.get_item("key").unwrap().extract().unwrap();
+                    let state = item.get_item("state").unwrap().extract().unwrap();
+                    let external = item.get_item("external").unwrap().extract().unwrap();
+                    (key, state, external)
+                })
+                .collect();
+            states.sort();
+            assert_eq!(
+                states,
+                [
+                    ("expired".to_string(), "expired".to_string(), false),
+                    ("pending".to_string(), "pending".to_string(), true),
+                    ("ready".to_string(), "ready".to_string(), false),
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn test_after_fork() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["ready"], 1);
+            assert!(pycache.start(py, "pending").unwrap());
+
+            // Same generation: nothing happens
+            pycache.after_fork();
+            assert_eq!(pycache.pending_keys(py), ["pending"]);
+
+            // Pretend the process was forked since the cache last looked
+            pycache.generation.store(u64::MAX, Ordering::SeqCst);
+            pycache.after_fork();
+            assert!(pycache.pending_keys(py).is_empty());
+            assert_eq!(pycache.ready_keys(py), ["ready"]);
+            assert_eq!(
+                pycache.stats.forked_flights_dropped.load(Ordering::Relaxed),
+                1
+            );
+        });
+    }
+
+    #[test]
+    fn test_health() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["ready"], 1);
+            assert!(pycache.start(py, "pending").unwrap());
+
+            let health = pycache.health(py).unwrap();
+            assert!(health
+                .get_item("ready")
+                .unwrap()
+                .unwrap()
+                .extract::<bool>()
+                .unwrap());
+            let cache = health.get_item("cache").unwrap().unwrap();
+            assert!(cache
+                .get_item("reachable")
+                .unwrap()
+                .extract::<bool>()
+                .unwrap());
+            assert_eq!(
+                cache
+                    .get_item("entries")
+                    .unwrap()
+                    .extract::<usize>()
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                cache
+                    .get_item("pending")
+                    .unwrap()
+                    .extract::<usize>()
+                    .unwrap(),
+                1
+            );
+
+            let components = health.get_item("components").unwrap().unwrap();
+            for (_, component) in components.downcast::<PyDict>().unwrap().iter() {
+                assert!(component
+                    .get_item("alive")
+                    .unwrap()
+                    .extract::<bool>()
+                    .unwrap());
+            }
+            let rate: f64 = get_option(&health, "failed_call_rate").unwrap().unwrap();
+            assert_eq!(rate, 0.0);
+        });
+
+        // A component that returned is stopped and leaves the cache ready
+        pycache.supervisor.spawn("oneshot", |_| {});
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !pycache
+            .supervisor
+            .components()
+            .iter()
+            .any(|(name, state)| *name == "oneshot" && state.stopped.load(Ordering::SeqCst))
+        {
+            assert!(Instant::now() < deadline, "component did not finish");
+            thread::sleep(Duration::from_millis(5));
+        }
+        Python::with_gil(|py| {
+            let health = pycache.health(py).unwrap();
+            assert_eq!(get_option::<bool>(&health, "ready").unwrap(), Some(true));
+
+            // One of two computations failed
+            let func = py
+                .eval(c_str!("lambda: 1 / 0"), None, None)
+                .unwrap()
+                .unbind();
+            call_func(&pycache, py, &func, "fails", CallOptions::default()).unwrap_err();
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            call_func(&pycache, py, &func, "works", CallOptions::default()).unwrap();
+            let health = pycache.health(py).unwrap();
+            let rate: f64 = get_option(&health, "failed_call_rate").unwrap().unwrap();
+            assert_eq!(rate, 0.5);
+        });
+
+        let pycache = PyCache::with_config(CacheConfig {
+            max_memory: Some(1 << 20),
+            ..Default::default()
+        });
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["a", "b"], 1);
+            let health = pycache.health(py).unwrap();
+            let memory: Bound<'_, PyDict> = get_option(&health, "memory").unwrap().unwrap();
+            let used: usize = get_option(&memory, "used").unwrap().unwrap();
+            let usage: f64 = get_option(&memory, "usage").unwrap().unwrap();
+            assert!(used > 0);
+            assert_eq!(usage, used as f64 / (1 << 20) as f64);
+        });
+    }
+
+    #[test]
+    fn test_await_warm() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+        let keys = vec!["a".to_string(), "b".to_string()];
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["a"], 1);
+            assert!(pycache.start(py, "b").unwrap());
+
+            assert!(!pycache
+                .await_warm(py, keys.clone(), Some(0.05), 1.0)
+                .unwrap());
+            assert!(pycache
+                .await_warm(py, keys.clone(), Some(0.05), 0.5)
+                .unwrap());
+
+            thread::scope(|s| {
+                s.spawn(|| {
+                    thread::sleep(Duration::from_millis(50));
+                    Python::with_gil(|py| {
+                        pycache
+                            .complete(py, "b", 2i64.into_pyobject(py).unwrap().into_any().unbind())
+                            .unwrap();
+                    });
+                });
+                assert!(pycache
+                    .await_warm(py, keys.clone(), Some(5.0), 1.0)
+                    .unwrap());
+            });
+
+            // An expired value is not warm
+            pycache
+                .set(py, "expired", py.None(), Some(0.0), None)
+                .unwrap();
+            assert!(!pycache
+                .await_warm(py, vec!["expired".to_string()], Some(0.05), 1.0)
+                .unwrap());
+        });
+
+        // Keys are looked up the way they are stored
+        Python::with_gil(|py| {
+            let lowercase = PyString::new(py, "lowercase");
+            let pycache = PyCache::with_config(CacheConfig {
+                canonicalize: Some(Canonicalizer::parse(lowercase.as_any()).unwrap()),
+                ..Default::default()
+            });
+            store_all(&pycache, py, &["a"], 1);
+            assert!(pycache
+                .await_warm(py, vec!["A".to_string()], Some(0.05), 1.0)
+                .unwrap());
+        });
+    }
+
+    #[test]
+    fn test_expiry_forecast() {
+        let pycache = PyCache::with_config(CacheConfig::default());
+
+        Python::with_gil(|py| {
+            store_all(&pycache, py, &["forever"], 1);
+            for (key, ttl) in [
+                ("soon", 0.5),
+                ("sooner", 0.2),
+                ("later", 5.0),
+                ("late", 60.0),
+            ] {
+                pycache.set(py, key, py.None(), Some(ttl), None).unwrap();
+            }
+
+            let forecast = pycache.expiry_forecast(py, 2, 1.0).unwrap();
+            let item = |name| forecast.get_item(name).unwrap().unwrap();
+            assert_eq!(item("interval").extract::<f64>().unwrap(), 1.0);
+            assert_eq!(item("buckets").extract::<Vec<usize>>().unwrap(), [2, 0]);
+            assert_eq!(item("later").extract::<usize>().unwrap(), 2);
+            assert_eq!(item("never").extract::<usize>().unwrap(), 1);
+
+            assert!(pycache.expiry_forecast(py, 1, -1.0).is_err());
+        });
+    }
+
+    #[test]
+    fn test_lock_stripes() {
+        // A single stripe, so both keys' waiters park on the same one
+        let pycache = PyCache::with_config(CacheConfig {
+            lock_stripes: Some(1),
+            ..Default::default()
+        });
+        Python::with_gil(|py| {
+            assert!(pycache.start(py, "a").unwrap());
+            assert!(pycache.start(py, "b").unwrap());
+        });
+
+        thread::scope(|scope| {
+            let waiters: Vec<_> = (0..3)
+                .map(|_| {
+                    scope.spawn(|| {
+                        Python::with_gil(|py| {
+                            let value = pycache.wait_for(py, "a", Some(5.0)).unwrap();
+                            value.extract::<i64>(py).unwrap()
+                        })
+                    })
+                })
+                .collect();
+            let late = scope.spawn(|| {
+                Python::with_gil(|py| {
+                    let err = pycache.wait_for(py, "b", Some(5.0)).unwrap_err();
+                    err.is_instance_of::<PyValueError>(py)
+                })
+            });
+            thread::sleep(Duration::from_millis(20));
+            Python::with_gil(|py| {
+                let value = 42i64.into_pyobject(py).unwrap().into_any().unbind();
+                assert!(pycache.complete(py, "a", value).unwrap());
+            });
+            for waiter in waiters {
+                assert_eq!(waiter.join().unwrap(), 42);
+            }
+            // Waking "a" left "b" pending
+            assert!(!late.is_finished());
+
+            // A new flight for "b" right after the failure does not take the
+            // error away from the waiter of the failed one
+            Python::with_gil(|py| {
+                let err = PyValueError::new_err("boom").into_value(py).into_any();
+                assert!(pycache.fail(py, "b", err.into_bound(py)).unwrap());
+                assert!(pycache.start(py, "b").unwrap());
+            });
+            assert!(late.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_nowait_pool() {
+        Python::with_gil(|py| {
+            let cache = Bound::new(
+                py,
+                PyCache::with_config(CacheConfig {
+                    call_workers: 2,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+            let func = define(
+                py,
+                c_str!("def f(x):\n    import time\n    time.sleep(0.01)\n    return x"),
+            );
+            let handles: Vec<_> = (0..10i64)
+                .map(|index| {
+                    let args = PyTuple::new(py, [index]).unwrap().into_any().unbind();
+                    let handle = PyCache::py_call_nowait(
+                        &cache,
+                        func.clone_ref(py),
+                        args,
+                        PyDict::new(py).into_any().unbind(),
+                        format!("key:{}", index),
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        Some(5.0),
+                        None,
+                    )
+                    .unwrap();
+                    Bound::new(py, handle).unwrap()
+                })
+                .collect();
+            for (index, handle) in handles.iter().enumerate() {
+                let value = handle.call_method1("result", (5.0,)).unwrap();
+                assert_eq!(value.extract::<usize>().unwrap(), index);
+            }
+            // The calls queued up for two threads instead of starting ten
+            assert_eq!(cache.get().calls.started(), 2);
+            cache.get().close(py);
+        })
+    }
+
+    #[test]
+    fn test_call_with_info_options() {
+        Python::with_gil(|py| {
+            let cache = Bound::new(
+                py,
+                PyCache::with_config(CacheConfig {
+                    timeout_policy: TimeoutPolicy::Raise,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+            let func = py.eval(c_str!("lambda: 1"), None, None).unwrap().unbind();
+            let call = |key: &str, timeout: Option<f64>| {
+                PyCache::py_call_with_info(
+                    &cache,
+                    func.clone_ref(py),
+                    PyTuple::empty(py).into_any().unbind(),
+                    PyDict::new(py).into_any().unbind(),
+                    key.to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    timeout,
+                    Some(true),
+                )
+            };
+            // The per-call timeout bounds the wait on someone else's flight
+            assert!(cache.get().start(py, "pending").unwrap());
+            let err = call("pending", Some(0.01)).unwrap_err();
+            assert!(err.is_instance_of::<PyTimeoutError>(py));
+
+            let (value, leader, _) = call("fresh", None).unwrap();
+            assert_eq!(value.extract::<i64>(py).unwrap(), 1);
+            assert!(leader);
         })
     }
 }
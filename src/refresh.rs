@@ -0,0 +1,258 @@
+use crate::trace::{key_hash, unix_now};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum RefreshLane {
+    High,
+    Normal,
+    Low,
+}
+
+impl RefreshLane {
+    const ALL: [RefreshLane; 3] = [RefreshLane::High, RefreshLane::Normal, RefreshLane::Low];
+
+    pub(crate) fn parse(lane: &str) -> PyResult<Self> {
+        match lane {
+            "high" => Ok(RefreshLane::High),
+            "normal" => Ok(RefreshLane::Normal),
+            "low" => Ok(RefreshLane::Low),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown refresh priority '{}', expected 'high', 'normal' or 'low'",
+                lane
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            RefreshLane::High => "high",
+            RefreshLane::Normal => "normal",
+            RefreshLane::Low => "low",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum RefreshOutcome {
+    Skipped,
+    Updated,
+    Unchanged,
+}
+
+pub(crate) struct RefreshJob {
+    pub(crate) key: String,
+    // Compute `key` with the loader of another entry instead of its own
+    pub(crate) source: Option<String>,
+    pub(crate) lane: RefreshLane,
+    enqueued_at: Instant,
+}
+
+#[derive(Default)]
+struct Lanes {
+    jobs: [VecDeque<RefreshJob>; 3],
+    delayed: Vec<(Instant, RefreshJob)>,
+    queued: HashSet<String>,
+    failures: HashMap<String, u32>,
+}
+
+impl Lanes {
+    fn is_idle(&self) -> bool {
+        self.jobs.iter().all(VecDeque::is_empty)
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.delayed.iter().map(|(due, _)| *due).min()
+    }
+
+    fn promote_due(&mut self, now: Instant) {
+        let (due, delayed) = std::mem::take(&mut self.delayed)
+            .into_iter()
+            .partition(|(due, _)| *due <= now);
+        self.delayed = delayed;
+        for (_, job) in due {
+            self.jobs[job.lane.index()].push_back(job);
+        }
+    }
+}
+
+// Each lane is drained only once every higher priority lane is empty, so a
+// backlog of long-tail keys never delays the few that matter.
+#[derive(Default)]
+pub(crate) struct RefreshQueue {
+    lanes: Mutex<Lanes>,
+    available: Condvar,
+    completed: AtomicU64,
+    unchanged: AtomicU64,
+    latency_micros: AtomicU64,
+    max_latency_micros: AtomicU64,
+}
+
+impl RefreshQueue {
+    pub(crate) fn push(&self, key: String, lane: RefreshLane) -> bool {
+        self.push_from(key, None, lane)
+    }
+
+    pub(crate) fn push_from(&self, key: String, source: Option<String>, lane: RefreshLane) -> bool {
+        let mut lanes = self.lanes.lock().expect("Unable to lock refresh queue!");
+        if !lanes.queued.insert(key.clone()) {
+            return false;
+        }
+        lanes.jobs[lane.index()].push_back(RefreshJob {
+            key,
+            source,
+            lane,
+            enqueued_at: Instant::now(),
+        });
+        self.available.notify_one();
+        true
+    }
+
+    pub(crate) fn pop(&self, timeout: Duration) -> Option<RefreshJob> {
+        let mut lanes = self.lanes.lock().expect("Unable to lock refresh queue!");
+        if lanes.is_idle() {
+            let wait = lanes.next_due().map_or(timeout, |due| {
+                due.saturating_duration_since(Instant::now()).min(timeout)
+            });
+            lanes = self
+                .available
+                .wait_timeout(lanes, wait)
+                .expect("Unable to lock refresh queue!")
+                .0;
+        }
+        lanes.promote_due(Instant::now());
+        let job = lanes.jobs.iter_mut().find_map(|jobs| jobs.pop_front())?;
+        lanes.queued.remove(&job.key);
+        Some(job)
+    }
+
+    // Failed keys wait out an exponential, jittered backoff before the next
+    // attempt rather than being retried on the next access.
+    pub(crate) fn retry_later(&self, job: RefreshJob) -> u32 {
+        let mut lanes = self.lanes.lock().expect("Unable to lock refresh queue!");
+        let failures = lanes.failures.entry(job.key.clone()).or_insert(0);
+        *failures += 1;
+        let failures = *failures;
+        if lanes.queued.insert(job.key.clone()) {
+            let delay = backoff(failures, key_hash(&job.key) ^ (unix_now() * 1e9) as u64);
+            let now = Instant::now();
+            lanes.delayed.push((
+                now + delay,
+                RefreshJob {
+                    enqueued_at: now,
+                    ..job
+                },
+            ));
+        }
+        failures
+    }
+
+    pub(crate) fn forget(&self, key: &str) {
+        let mut lanes = self.lanes.lock().expect("Unable to lock refresh queue!");
+        lanes.failures.remove(key);
+    }
+
+    pub(crate) fn failing(&self) -> Vec<(String, u32)> {
+        let lanes = self.lanes.lock().expect("Unable to lock refresh queue!");
+        lanes
+            .failures
+            .iter()
+            .map(|(key, failures)| (key.clone(), *failures))
+            .collect()
+    }
+
+    pub(crate) fn complete(&self, job: &RefreshJob, outcome: RefreshOutcome) {
+        self.forget(&job.key);
+        if outcome == RefreshOutcome::Unchanged {
+            self.unchanged.fetch_add(1, Ordering::Relaxed);
+        }
+        let latency = job.enqueued_at.elapsed().as_micros() as u64;
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.latency_micros.fetch_add(latency, Ordering::Relaxed);
+        self.max_latency_micros
+            .fetch_max(latency, Ordering::Relaxed);
+    }
+
+    pub(crate) fn depths(&self) -> Vec<(&'static str, usize)> {
+        let lanes = self.lanes.lock().expect("Unable to lock refresh queue!");
+        RefreshLane::ALL
+            .iter()
+            .map(|lane| (lane.as_str(), lanes.jobs[lane.index()].len()))
+            .collect()
+    }
+
+    pub(crate) fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn unchanged(&self) -> u64 {
+        self.unchanged.load(Ordering::Relaxed)
+    }
+
+    // Seconds from enqueueing to a finished refresh
+    pub(crate) fn mean_latency(&self) -> f64 {
+        match self.completed() {
+            0 => 0.0,
+            completed => {
+                self.latency_micros.load(Ordering::Relaxed) as f64 / completed as f64 / 1e6
+            }
+        }
+    }
+
+    pub(crate) fn max_latency(&self) -> f64 {
+        self.max_latency_micros.load(Ordering::Relaxed) as f64 / 1e6
+    }
+}
+
+fn backoff(failures: u32, seed: u64) -> Duration {
+    let delay = INITIAL_BACKOFF
+        .saturating_mul(1 << (failures - 1).min(16))
+        .min(MAX_BACKOFF);
+    // Spread retries over +/-20% so keys failing together do not retry together
+    let jitter = 0.8 + (seed % 1000) as f64 / 1000.0 * 0.4;
+    delay.mul_f64(jitter)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lanes() {
+        let queue = RefreshQueue::default();
+        assert!(queue.push("tail:1".to_string(), RefreshLane::Low));
+        assert!(queue.push("user:1".to_string(), RefreshLane::Normal));
+        assert!(queue.push("config".to_string(), RefreshLane::High));
+        assert!(!queue.push("config".to_string(), RefreshLane::Low));
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop(Duration::ZERO))
+            .map(|job| job.key)
+            .collect();
+        assert_eq!(order, ["config", "user:1", "tail:1"]);
+        assert!(queue.push("config".to_string(), RefreshLane::High));
+
+        let job = queue.pop(Duration::ZERO).unwrap();
+        assert_eq!(queue.retry_later(job), 1);
+        assert!(queue.pop(Duration::ZERO).is_none());
+        assert!(!queue.push("config".to_string(), RefreshLane::High));
+        assert_eq!(queue.failing(), [("config".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert!(backoff(1, 0) >= Duration::from_millis(800));
+        assert!(backoff(3, 999) <= Duration::from_millis(4800));
+        assert!(backoff(40, 500) <= MAX_BACKOFF.mul_f64(1.2));
+    }
+}
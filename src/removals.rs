@@ -0,0 +1,93 @@
+use crate::trace::unix_now;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+struct Removal {
+    key: String,
+    reason: &'static str,
+    timestamp: f64,
+    size: usize,
+}
+
+// The last few entries that left the cache and why, for answering "where did
+// my entry go" after the fact.
+pub(crate) struct RemovalLog {
+    capacity: usize,
+    removals: Mutex<VecDeque<Removal>>,
+}
+
+impl RemovalLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            removals: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+        }
+    }
+
+    pub(crate) fn record(&self, key: &str, reason: &'static str, size: usize) {
+        let removal = Removal {
+            key: key.to_string(),
+            reason,
+            timestamp: unix_now(),
+            size,
+        };
+        let mut removals = self.removals.lock().expect("Unable to lock removal log!");
+        if removals.len() == self.capacity {
+            removals.pop_front();
+        }
+        removals.push_back(removal);
+    }
+
+    // Newest first, optionally only those of `key`
+    pub(crate) fn to_list<'py>(
+        &self,
+        py: Python<'py>,
+        key: Option<&str>,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let removals = self.removals.lock().expect("Unable to lock removal log!");
+        let list = PyList::empty(py);
+        for removal in removals
+            .iter()
+            .rev()
+            .filter(|removal| key.is_none_or(|key| removal.key == key))
+        {
+            let item = PyDict::new(py);
+            item.set_item("key", &removal.key)?;
+            item.set_item("reason", removal.reason)?;
+            item.set_item("timestamp", removal.timestamp)?;
+            item.set_item("size", removal.size)?;
+            list.append(item)?;
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_removal_log() {
+        pyo3::prepare_freethreaded_python();
+        let log = RemovalLog::new(2);
+        log.record("a", "drop", 1);
+        log.record("b", "size", 2);
+        log.record("a", "expired", 3);
+        Python::with_gil(|py| {
+            let all = log.to_list(py, None).unwrap();
+            assert_eq!(all.len(), 2);
+            let newest = all.get_item(0).unwrap();
+            assert_eq!(
+                newest
+                    .get_item("reason")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "expired"
+            );
+            assert_eq!(log.to_list(py, Some("b")).unwrap().len(), 1);
+        });
+    }
+}
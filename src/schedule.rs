@@ -0,0 +1,195 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// Catching up after a stalled scheduler never looks back further than this
+const MAX_CATCH_UP_MINUTES: u64 = 24 * 60;
+
+// Five field cron expression (minute hour day-of-month month day-of-week),
+// always evaluated in UTC.
+pub(crate) struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+// Days since the unix epoch to a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl CronSchedule {
+    pub(crate) fn parse(expression: &str) -> PyResult<Self> {
+        let invalid = || {
+            PyValueError::new_err(format!(
+                "Invalid cron expression '{}', expected 'minute hour day month weekday'",
+                expression
+            ))
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(invalid());
+        };
+        // Both 0 and 7 mean Sunday
+        let weekdays = parse_field(weekday, 0, 7).ok_or_else(invalid)?;
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).ok_or_else(invalid)?,
+            hours: parse_field(hour, 0, 23).ok_or_else(invalid)?,
+            days: parse_field(day, 1, 31).ok_or_else(invalid)?,
+            months: parse_field(month, 1, 12).ok_or_else(invalid)?,
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    // `minute` counts minutes since the unix epoch
+    pub(crate) fn matches(&self, minute: u64) -> bool {
+        let days = (minute / (24 * 60)) as i64;
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday
+        let weekday = (days + 4).rem_euclid(7);
+        let day_matches = self.days & (1 << day) != 0;
+        let weekday_matches = self.weekdays & (1 << weekday) != 0;
+        // Like cron, a restricted day and weekday match when either does
+        let date_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day_matches || weekday_matches,
+            _ => day_matches && weekday_matches,
+        };
+        self.minutes & (1 << (minute % 60)) != 0
+            && self.hours & (1 << (minute / 60 % 24)) != 0
+            && self.months & (1 << month) != 0
+            && date_matches
+    }
+}
+
+struct Invalidation {
+    schedule: CronSchedule,
+    prefix: String,
+    checked_until: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    invalidations: Mutex<Vec<Invalidation>>,
+    pub(crate) fired: AtomicU64,
+}
+
+impl Scheduler {
+    pub(crate) fn add(&self, schedule: CronSchedule, prefix: String, minute: u64) {
+        let mut invalidations = self
+            .invalidations
+            .lock()
+            .expect("Unable to lock scheduler!");
+        invalidations.push(Invalidation {
+            schedule,
+            prefix,
+            checked_until: minute,
+        });
+    }
+
+    // Prefixes whose schedule matched any minute since the last check
+    pub(crate) fn due(&self, minute: u64) -> Vec<String> {
+        let mut invalidations = self
+            .invalidations
+            .lock()
+            .expect("Unable to lock scheduler!");
+        let mut due = Vec::new();
+        for invalidation in invalidations.iter_mut() {
+            let since = invalidation
+                .checked_until
+                .max(minute.saturating_sub(MAX_CATCH_UP_MINUTES));
+            if (since + 1..=minute).any(|minute| invalidation.schedule.matches(minute)) {
+                due.push(invalidation.prefix.clone());
+                self.fired.fetch_add(1, Ordering::Relaxed);
+            }
+            invalidation.checked_until = invalidation.checked_until.max(minute);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2024-03-15 (a Friday) 00:00 UTC
+    const FRIDAY: u64 = 1710460800 / 60;
+
+    #[test]
+    fn test_cron() {
+        let midnight = CronSchedule::parse("0 0 * * *").unwrap();
+        assert!(midnight.matches(FRIDAY));
+        assert!(!midnight.matches(FRIDAY + 1));
+        assert!(midnight.matches(FRIDAY + 24 * 60));
+
+        let quarter_hours = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert!(quarter_hours.matches(FRIDAY + 9 * 60 + 45));
+        assert!(!quarter_hours.matches(FRIDAY + 9 * 60 + 50));
+        assert!(!quarter_hours.matches(FRIDAY + 24 * 60 + 9 * 60));
+
+        let sundays = CronSchedule::parse("30 6 * * 7").unwrap();
+        assert!(sundays.matches(FRIDAY + 2 * 24 * 60 + 6 * 60 + 30));
+
+        let ides = CronSchedule::parse("0 0 15 3 *").unwrap();
+        assert!(ides.matches(FRIDAY));
+        assert!(!ides.matches(FRIDAY + 24 * 60));
+
+        assert!(CronSchedule::parse("0 0 * *").is_err());
+        assert!(CronSchedule::parse("60 0 * * *").is_err());
+        assert!(CronSchedule::parse("*/0 0 * * *").is_err());
+    }
+
+    #[test]
+    fn test_scheduler() {
+        let scheduler = Scheduler::default();
+        let midnight = CronSchedule::parse("0 0 * * *").unwrap();
+        scheduler.add(midnight, "reports:".to_string(), FRIDAY - 10);
+
+        assert!(scheduler.due(FRIDAY - 1).is_empty());
+        assert_eq!(scheduler.due(FRIDAY + 5), ["reports:"]);
+        assert!(scheduler.due(FRIDAY + 6).is_empty());
+        assert_eq!(scheduler.fired.load(Ordering::Relaxed), 1);
+    }
+}
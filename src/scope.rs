@@ -0,0 +1,215 @@
+use crate::config::optional_secs;
+use crate::handle::{Completion, FlightHandle};
+use crate::py_waiter::{CallOptions, PyCache};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Background flights tied to a block of code: when the scope exits, every
+// flight submitted through it has finished. Leaving with an exception, or
+// calling `cancel`, first asks the flights nobody else waits on to stop.
+#[pyclass(frozen)]
+pub struct FlightScope {
+    cache: Py<PyCache>,
+    spawned: Mutex<Vec<(String, Arc<Completion>)>>,
+    closed: AtomicBool,
+}
+
+impl FlightScope {
+    pub(crate) fn new(cache: Py<PyCache>) -> Self {
+        Self {
+            cache,
+            spawned: Mutex::new(Vec::new()),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn spawn(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+    ) -> PyResult<Arc<Completion>> {
+        let mut spawned = self.spawned.lock().expect("Unable to lock scope!");
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(PyRuntimeError::new_err("Flight scope is already closed"));
+        }
+        let completion = PyCache::spawn_call(
+            self.cache.clone_ref(py),
+            py_func,
+            args,
+            kwargs,
+            key.clone(),
+            CallOptions::default(),
+        )?;
+        spawned.push((key, completion.clone()));
+        Ok(completion)
+    }
+
+    // Waits for every submitted flight; false if `timeout` ran out first
+    fn wait_all(&self, py: Python<'_>, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let spawned: Vec<Arc<Completion>> = self
+            .spawned
+            .lock()
+            .expect("Unable to lock scope!")
+            .iter()
+            .map(|(_, completion)| completion.clone())
+            .collect();
+        py.allow_threads(|| {
+            spawned
+                .iter()
+                .all(|completion| completion.wait_finished(deadline))
+        })
+    }
+
+    fn close(&self, py: Python<'_>, cancel: bool) {
+        self.closed.store(true, Ordering::SeqCst);
+        if cancel {
+            self.cancel(py);
+        }
+    }
+}
+
+#[pymethods]
+impl FlightScope {
+    fn submit(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+    ) -> PyResult<FlightHandle> {
+        let completion = self.spawn(py, py_func, args, kwargs, key.clone())?;
+        Ok(FlightHandle::new(key, completion))
+    }
+
+    // Like `submit`, for callers that only want the value in the cache
+    fn prefetch(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+    ) -> PyResult<()> {
+        self.spawn(py, py_func, args, kwargs, key)?;
+        Ok(())
+    }
+
+    // Returns how many flights were asked to stop
+    fn cancel(&self, py: Python<'_>) -> usize {
+        let spawned: Vec<(String, Arc<Completion>)> = self
+            .spawned
+            .lock()
+            .expect("Unable to lock scope!")
+            .iter()
+            .map(|(key, completion)| (key.clone(), completion.clone()))
+            .collect();
+        let cache = self.cache.get();
+        let mut cancelled = 0;
+        for (key, completion) in spawned {
+            if completion.cancel() && cache.cancel_flight(py, &key) {
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
+    // `timeout` in seconds, waiting indefinitely without one
+    #[pyo3(signature = (timeout=None))]
+    fn join(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<bool> {
+        Ok(self.wait_all(py, optional_secs(timeout)?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.spawned.lock().expect("Unable to lock scope!").len()
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        exc_type: Option<Bound<'_, PyAny>>,
+        _exc_value: Option<Bound<'_, PyAny>>,
+        _traceback: Option<Bound<'_, PyAny>>,
+    ) -> bool {
+        self.close(py, exc_type.is_some());
+        self.wait_all(py, None);
+        false
+    }
+
+    fn __aenter__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let future = running_loop(slf.py())?.call_method0("create_future")?;
+        future.call_method1("set_result", (slf,))?;
+        Ok(future)
+    }
+
+    // Waits on an executor thread so the event loop keeps running
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __aexit__<'py>(
+        slf: &Bound<'py, Self>,
+        exc_type: Option<Bound<'py, PyAny>>,
+        _exc_value: Option<Bound<'py, PyAny>>,
+        _traceback: Option<Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        slf.get().close(py, exc_type.is_some());
+        running_loop(py)?.call_method1("run_in_executor", (py.None(), slf.getattr("_drain")?))
+    }
+
+    fn _drain(&self, py: Python<'_>) {
+        self.wait_all(py, None);
+    }
+}
+
+fn running_loop(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    py.import("asyncio")?.call_method0("get_running_loop")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::CacheConfig;
+    use pyo3::ffi::c_str;
+    use pyo3::types::{PyDict, PyTuple};
+
+    #[test]
+    fn test_scope() {
+        Python::with_gil(|py| {
+            let pycache = Py::new(
+                py,
+                PyCache::with_config(CacheConfig {
+                    wait_timeout: Some(Duration::from_secs(10)),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+            let pyfunc = py.eval(c_str!("lambda: 42"), None, None).unwrap();
+            let scope = FlightScope::new(pycache.clone_ref(py));
+            for key in ["a", "b"] {
+                scope
+                    .prefetch(
+                        py,
+                        pyfunc.clone().unbind(),
+                        PyTuple::empty(py).into_any().unbind(),
+                        PyDict::new(py).into_any().unbind(),
+                        key.to_string(),
+                    )
+                    .unwrap();
+            }
+            assert!(scope.join(py, None).unwrap());
+            assert!(pycache.get().lookup(py, "a").unwrap().is_some());
+            assert!(pycache.get().lookup(py, "b").unwrap().is_some());
+        })
+    }
+}
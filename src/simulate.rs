@@ -0,0 +1,202 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{BTreeMap, HashMap};
+
+struct Policy {
+    name: Option<String>,
+    max_size: Option<usize>,
+    ttl: Option<f64>,
+}
+
+impl Policy {
+    fn from_dict(policy: &Bound<'_, PyDict>) -> PyResult<Self> {
+        Ok(Self {
+            name: get_option(policy, "name")?,
+            max_size: get_option(policy, "max_size")?,
+            ttl: get_option(policy, "ttl")?,
+        })
+    }
+}
+
+pub(crate) fn get_option<'py, T: FromPyObject<'py>>(
+    dict: &Bound<'py, PyDict>,
+    name: &str,
+) -> PyResult<Option<T>> {
+    match dict.get_item(name)? {
+        Some(value) if !value.is_none() => Ok(Some(value.extract()?)),
+        _ => Ok(None),
+    }
+}
+
+#[derive(Default, Debug, PartialEq)]
+struct SimulationResult {
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+    expirations: u64,
+}
+
+struct SimulatedEntry {
+    inserted_at: f64,
+    last_used: u64,
+}
+
+fn replay(trace: &[(f64, String)], policy: &Policy) -> SimulationResult {
+    let mut result = SimulationResult::default();
+    let mut entries: HashMap<&str, SimulatedEntry> = HashMap::new();
+    let mut recency: BTreeMap<u64, &str> = BTreeMap::new();
+
+    for (tick, (timestamp, key)) in trace.iter().enumerate() {
+        let tick = tick as u64;
+        let key = key.as_str();
+
+        if let Some(entry) = entries.get(key) {
+            let expired = policy
+                .ttl
+                .is_some_and(|ttl| timestamp - entry.inserted_at >= ttl);
+            if expired {
+                recency.remove(&entry.last_used);
+                entries.remove(key);
+                result.expirations += 1;
+            }
+        }
+
+        match entries.get_mut(key) {
+            Some(entry) => {
+                result.hits += 1;
+                recency.remove(&entry.last_used);
+                entry.last_used = tick;
+                recency.insert(tick, key);
+            }
+            None => {
+                result.misses += 1;
+                if let Some(max_size) = policy.max_size {
+                    while entries.len() >= max_size.max(1) {
+                        let (_, evicted) = recency.pop_first().expect("Recency out of sync!");
+                        entries.remove(evicted);
+                        result.evictions += 1;
+                    }
+                }
+                entries.insert(
+                    key,
+                    SimulatedEntry {
+                        inserted_at: *timestamp,
+                        last_used: tick,
+                    },
+                );
+                recency.insert(tick, key);
+            }
+        }
+    }
+    result
+}
+
+#[pyfunction]
+pub fn simulate<'py>(
+    py: Python<'py>,
+    trace: Vec<(f64, String)>,
+    policies: Vec<Bound<'py, PyDict>>,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let policies = policies
+        .iter()
+        .map(Policy::from_dict)
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let results = py.allow_threads(|| {
+        policies
+            .iter()
+            .map(|policy| replay(&trace, policy))
+            .collect::<Vec<_>>()
+    });
+
+    policies
+        .iter()
+        .zip(results)
+        .map(|(policy, result)| {
+            let report = PyDict::new(py);
+            report.set_item("name", policy.name.as_deref())?;
+            report.set_item("max_size", policy.max_size)?;
+            report.set_item("ttl", policy.ttl)?;
+            report.set_item("hits", result.hits)?;
+            report.set_item("misses", result.misses)?;
+            report.set_item("evictions", result.evictions)?;
+            report.set_item("expirations", result.expirations)?;
+            let total = result.hits + result.misses;
+            let hit_rate = if total == 0 {
+                0.0
+            } else {
+                result.hits as f64 / total as f64
+            };
+            report.set_item("hit_rate", hit_rate)?;
+            Ok(report)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(keys: &[(f64, &str)]) -> Vec<(f64, String)> {
+        keys.iter()
+            .map(|(timestamp, key)| (*timestamp, key.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_replay() {
+        let trace = trace(&[
+            (0.0, "a"),
+            (1.0, "b"),
+            (2.0, "a"),
+            (3.0, "c"),
+            (4.0, "b"),
+            (10.0, "a"),
+        ]);
+
+        let unbounded = Policy {
+            name: None,
+            max_size: None,
+            ttl: None,
+        };
+        assert_eq!(
+            replay(&trace, &unbounded),
+            SimulationResult {
+                hits: 3,
+                misses: 3,
+                evictions: 0,
+                expirations: 0
+            }
+        );
+
+        let lru = Policy {
+            name: None,
+            max_size: Some(2),
+            ttl: None,
+        };
+        assert_eq!(
+            replay(&trace, &lru),
+            SimulationResult {
+                hits: 1,
+                misses: 5,
+                evictions: 3,
+                expirations: 0
+            }
+        );
+
+        let ttl = Policy {
+            name: None,
+            max_size: None,
+            ttl: Some(5.0),
+        };
+        assert_eq!(
+            replay(&trace, &ttl),
+            SimulationResult {
+                hits: 2,
+                misses: 4,
+                evictions: 0,
+                expirations: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::popularity::PopularitySketch;
+use crate::simulate::get_option;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+// Written into every snapshot so that other dicts are told apart from it
+pub(crate) const METADATA_FORMAT: &str = "rustflight.metadata";
+pub(crate) const METADATA_VERSION: u32 = 2;
+
+type Migration = fn(&Bound<'_, PyDict>) -> PyResult<()>;
+
+// `MIGRATIONS[n - 1]` upgrades a version n snapshot to version n + 1
+const MIGRATIONS: [Migration; METADATA_VERSION as usize - 1] = [add_format];
+
+// Version 1 had no format marker
+fn add_format(snapshot: &Bound<'_, PyDict>) -> PyResult<()> {
+    snapshot.set_item("format", METADATA_FORMAT)
+}
+
+// Checks that `snapshot` is a metadata snapshot this release can read and
+// returns a copy upgraded to the current version. Snapshots written by a
+// newer release are refused rather than half understood.
+#[pyfunction]
+pub fn migrate_snapshot<'py>(snapshot: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyDict>> {
+    let version: u32 = snapshot
+        .get_item("version")?
+        .ok_or_else(|| PyValueError::new_err("Not a metadata snapshot, missing 'version'"))?
+        .extract()?;
+    if version == 0 || version > METADATA_VERSION {
+        return Err(PyValueError::new_err(format!(
+            "Metadata snapshot version {} is not supported, expected 1 to {}",
+            version, METADATA_VERSION
+        )));
+    }
+    match get_option::<String>(snapshot, "format")?.as_deref() {
+        Some(METADATA_FORMAT) => {}
+        None if version == 1 => {}
+        Some(format) => {
+            return Err(PyValueError::new_err(format!(
+                "Not a metadata snapshot, format is '{}'",
+                format
+            )))
+        }
+        None => {
+            return Err(PyValueError::new_err(
+                "Not a metadata snapshot, missing 'format'",
+            ))
+        }
+    }
+
+    let migrated = snapshot.copy()?;
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(&migrated)?;
+    }
+    migrated.set_item("version", METADATA_VERSION)?;
+    Ok(migrated)
+}
+
+struct EntryMetadata {
+    key: String,
+    hits: u64,
+    weight: usize,
+}
+
+impl EntryMetadata {
+    fn from_dict(entry: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let key = entry
+            .get_item("key")?
+            .ok_or_else(|| PyValueError::new_err("Snapshot entry without a 'key'"))?
+            .extract()?;
+        Ok(Self {
+            key,
+            hits: get_option(entry, "hits")?.unwrap_or(0),
+            weight: get_option(entry, "weight")?.unwrap_or(0),
+        })
+    }
+}
+
+// Most hit entries first; among equally popular ones the cheaper to hold
+// come first.
+fn rank(mut entries: Vec<EntryMetadata>) -> Vec<String> {
+    entries.sort_by(|a, b| {
+        b.hits
+            .cmp(&a.hits)
+            .then(a.weight.cmp(&b.weight))
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    entries.into_iter().map(|entry| entry.key).collect()
+}
+
+#[pyfunction]
+#[pyo3(signature = (snapshot, limit=None))]
+pub fn warmup_order(snapshot: &Bound<'_, PyDict>, limit: Option<usize>) -> PyResult<Vec<String>> {
+    let snapshot = &migrate_snapshot(snapshot)?;
+    let entries = snapshot
+        .get_item("entries")?
+        .ok_or_else(|| PyValueError::new_err("Not a metadata snapshot, missing 'entries'"))?;
+    let entries = entries
+        .try_iter()?
+        .map(|entry| EntryMetadata::from_dict(entry?.downcast()?))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    // Traffic across restarts says more than hits since the entry was filled
+    let mut entries = entries;
+    if let Some(sketch) = get_option::<Bound<'_, PyDict>>(snapshot, "popularity")? {
+        let sketch = PopularitySketch::from_dict(&sketch)?;
+        for entry in &mut entries {
+            entry.hits = sketch.estimate(&entry.key) as u64;
+        }
+    }
+    let mut order = rank(entries);
+    if let Some(limit) = limit {
+        order.truncate(limit);
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(key: &str, hits: u64, weight: usize) -> EntryMetadata {
+        EntryMetadata {
+            key: key.to_string(),
+            hits,
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_migrate_snapshot() {
+        Python::with_gil(|py| {
+            let old = PyDict::new(py);
+            old.set_item("version", 1).unwrap();
+            let migrated = migrate_snapshot(&old).unwrap();
+            assert_eq!(
+                get_option::<u32>(&migrated, "version").unwrap(),
+                Some(METADATA_VERSION)
+            );
+            assert_eq!(
+                get_option::<String>(&migrated, "format")
+                    .unwrap()
+                    .as_deref(),
+                Some(METADATA_FORMAT)
+            );
+
+            let newer = PyDict::new(py);
+            newer.set_item("version", METADATA_VERSION + 1).unwrap();
+            newer.set_item("format", METADATA_FORMAT).unwrap();
+            assert!(migrate_snapshot(&newer).is_err());
+
+            let foreign = PyDict::new(py);
+            foreign.set_item("version", METADATA_VERSION).unwrap();
+            assert!(migrate_snapshot(&foreign).is_err());
+        });
+    }
+
+    #[test]
+    fn test_rank() {
+        let order = rank(vec![
+            entry("cold", 1, 10),
+            entry("hot:big", 50, 1000),
+            entry("hot:small", 50, 10),
+            entry("warm", 7, 10),
+        ]);
+        assert_eq!(order, ["hot:small", "hot:big", "warm", "cold"]);
+    }
+}
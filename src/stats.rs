@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Default)]
+pub(crate) struct CacheStats {
+    pub(crate) hits: AtomicU64,
+    pub(crate) misses: AtomicU64,
+    pub(crate) coalesced_waits: AtomicU64,
+    // Waiters that left before the flight resolved, timed out or interrupted
+    pub(crate) abandoned_waits: AtomicU64,
+    pub(crate) wait_timeouts: AtomicU64,
+    pub(crate) unconsumed_results: AtomicU64,
+    pub(crate) retries: AtomicU64,
+    pub(crate) access_denied: AtomicU64,
+    pub(crate) quota_exceeded: AtomicU64,
+    pub(crate) func_mismatches: AtomicU64,
+    pub(crate) write_conflicts: AtomicU64,
+    pub(crate) inflight_alarms: AtomicU64,
+    pub(crate) compute_overruns: AtomicU64,
+    pub(crate) failed_calls: AtomicU64,
+    pub(crate) elections_lost: AtomicU64,
+    pub(crate) handoffs: AtomicU64,
+    pub(crate) size_evictions: AtomicU64,
+    pub(crate) forked_flights_dropped: AtomicU64,
+    pub(crate) degraded_misses: AtomicU64,
+    pub(crate) bypassed_calls: AtomicU64,
+    pub(crate) fallback_stale: AtomicU64,
+    pub(crate) fallback_default: AtomicU64,
+    pub(crate) fallback_timeouts: AtomicU64,
+    pub(crate) orphaned_pendings_reclaimed: AtomicU64,
+}
+
+impl CacheStats {
+    pub(crate) fn reset(&self) {
+        let counters = [
+            &self.hits,
+            &self.misses,
+            &self.coalesced_waits,
+            &self.abandoned_waits,
+            &self.wait_timeouts,
+            &self.unconsumed_results,
+            &self.retries,
+            &self.access_denied,
+            &self.quota_exceeded,
+            &self.func_mismatches,
+            &self.write_conflicts,
+            &self.inflight_alarms,
+            &self.compute_overruns,
+            &self.failed_calls,
+            &self.elections_lost,
+            &self.handoffs,
+            &self.size_evictions,
+            &self.forked_flights_dropped,
+            &self.degraded_misses,
+            &self.bypassed_calls,
+            &self.fallback_stale,
+            &self.fallback_default,
+            &self.fallback_timeouts,
+            &self.orphaned_pendings_reclaimed,
+        ];
+        for counter in counters {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::trace::key_hash;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+// A fixed pool of condition variables that entries share, picked by key
+// hash, instead of each entry carrying its own. Every flight resolved on a
+// stripe moves its generation, so waiters of other keys on the same stripe
+// wake too and go back to sleep if their own flight is still pending.
+pub(crate) struct LockStripes {
+    stripes: Box<[Arc<LockStripe>]>,
+}
+
+pub(crate) struct LockStripe {
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl LockStripes {
+    pub(crate) fn new(stripes: usize) -> Self {
+        let stripes = (0..stripes.max(1))
+            .map(|_| {
+                Arc::new(LockStripe {
+                    generation: Mutex::new(0),
+                    changed: Condvar::new(),
+                })
+            })
+            .collect();
+        Self { stripes }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Arc<LockStripe> {
+        self.stripes[(key_hash(key) % self.stripes.len() as u64) as usize].clone()
+    }
+}
+
+impl LockStripe {
+    pub(crate) fn generation(&self) -> u64 {
+        *self.generation.lock().expect("Unable to lock stripe!")
+    }
+
+    // Called by whoever resolves a flight on this stripe, after updating it
+    pub(crate) fn wake(&self) {
+        *self.generation.lock().expect("Unable to lock stripe!") += 1;
+        self.changed.notify_all();
+    }
+
+    // Blocks until the generation moves past `seen` or `timeout` passes
+    pub(crate) fn wait_changed(&self, seen: u64, timeout: Duration) {
+        let generation = self.generation.lock().expect("Unable to lock stripe!");
+        let _ = self
+            .changed
+            .wait_timeout_while(generation, timeout, |generation| *generation == seen)
+            .expect("Unable to lock stripe!");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn test_lock_stripes() {
+        let stripes = LockStripes::new(0);
+        assert_eq!(stripes.stripes.len(), 1);
+        let stripes = LockStripes::new(8);
+        assert!(Arc::ptr_eq(&stripes.get("user:1"), &stripes.get("user:1")));
+
+        let stripe = stripes.get("user:1");
+        let seen = stripe.generation();
+        // Nothing resolved, so the wait runs out
+        let started = Instant::now();
+        stripe.wait_changed(seen, Duration::from_millis(20));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                stripe.wake();
+            });
+            stripe.wait_changed(seen, Duration::from_secs(10));
+        });
+        assert_eq!(stripe.generation(), seen + 1);
+        // A wakeup that already happened is not waited for again
+        let started = Instant::now();
+        stripe.wait_changed(seen, Duration::from_secs(10));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::py_log;
+use crate::threads::ThreadSettings;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub(crate) struct ComponentState {
+    pub(crate) alive: AtomicBool,
+    // Set once `body` returned, i.e. the component finished on purpose
+    pub(crate) stopped: AtomicBool,
+    pub(crate) restarts: AtomicU64,
+    pub(crate) last_error: Mutex<Option<String>>,
+}
+
+impl ComponentState {
+    fn new() -> Self {
+        Self {
+            alive: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            restarts: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+}
+
+// Handed to every component so its sleeps end early once the supervisor
+// shuts down, instead of each loop polling a flag of its own.
+#[derive(Default)]
+pub(crate) struct Shutdown {
+    stopping: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl Shutdown {
+    // Sleeps for `duration`, returning false right away once a shutdown was
+    // requested so the caller can return.
+    pub(crate) fn sleep(&self, duration: Duration) -> bool {
+        let stopping = self.stopping.lock().expect("Unable to lock supervisor!");
+        let (stopping, _) = self
+            .changed
+            .wait_timeout_while(stopping, duration, |stopping| !*stopping)
+            .expect("Unable to lock supervisor!");
+        !*stopping
+    }
+
+    pub(crate) fn requested(&self) -> bool {
+        *self.stopping.lock().expect("Unable to lock supervisor!")
+    }
+
+    pub(crate) fn request(&self) {
+        *self.stopping.lock().expect("Unable to lock supervisor!") = true;
+        self.changed.notify_all();
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Supervisor {
+    components: Mutex<BTreeMap<&'static str, Arc<ComponentState>>>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    shutdown: Arc<Shutdown>,
+    threads: Arc<ThreadSettings>,
+}
+
+impl Supervisor {
+    pub(crate) fn new(threads: ThreadSettings) -> Self {
+        Self {
+            components: Mutex::default(),
+            handles: Mutex::default(),
+            shutdown: Arc::default(),
+            threads: Arc::new(threads),
+        }
+    }
+
+    // Runs `body` on a named thread until it returns, restarting it with
+    // exponential backoff whenever it panics. `body` should return soon after
+    // the shutdown it is handed was requested. Does nothing once the
+    // supervisor was shut down.
+    pub(crate) fn spawn<F>(&self, name: &'static str, body: F)
+    where
+        F: Fn(&Shutdown) + Send + 'static,
+    {
+        let mut handles = self.handles.lock().expect("Unable to lock supervisor!");
+        if self.shutdown.requested() {
+            return;
+        }
+        let state = Arc::new(ComponentState::new());
+        self.components
+            .lock()
+            .expect("Unable to lock supervisor!")
+            .insert(name, state.clone());
+
+        let threads = self.threads.clone();
+        let shutdown = self.shutdown.clone();
+        let handle = thread::Builder::new()
+            .name(format!("rustflight-{}", name))
+            .spawn(move || {
+                threads.apply(name);
+                let mut backoff = INITIAL_BACKOFF;
+                loop {
+                    state.alive.store(true, Ordering::SeqCst);
+                    let started = Instant::now();
+                    match panic::catch_unwind(AssertUnwindSafe(|| body(&shutdown))) {
+                        Ok(()) => break,
+                        Err(payload) => {
+                            state.alive.store(false, Ordering::SeqCst);
+                            state.restarts.fetch_add(1, Ordering::SeqCst);
+                            let message = panic_message(&*payload);
+                            py_log::log(
+                                py_log::ERROR,
+                                &format!(
+                                    "rustflight component '{}' died ({}), restarting in {:?}",
+                                    name, message, backoff
+                                ),
+                            );
+                            *state.last_error.lock().unwrap() = Some(message);
+
+                            if started.elapsed() > MAX_BACKOFF {
+                                backoff = INITIAL_BACKOFF;
+                            }
+                            if !shutdown.sleep(backoff) {
+                                break;
+                            }
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                    }
+                }
+                state.stopped.store(true, Ordering::SeqCst);
+                state.alive.store(false, Ordering::SeqCst);
+            })
+            .expect("Unable to spawn background thread!");
+        handles.push(handle);
+    }
+
+    // Asks every component to stop and waits for their threads to exit. A
+    // component calling this is not waited for, it exits once it returns.
+    pub(crate) fn shutdown(&self) {
+        let handles = {
+            let mut handles = self.handles.lock().expect("Unable to lock supervisor!");
+            self.shutdown.request();
+            std::mem::take(&mut *handles)
+        };
+        let current = thread::current().id();
+        for handle in handles {
+            if handle.thread().id() != current {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    pub(crate) fn is_running(&self, name: &str) -> bool {
+        self.components
+            .lock()
+            .expect("Unable to lock supervisor!")
+            .get(name)
+            .is_some_and(|state| !state.stopped.load(Ordering::SeqCst))
+    }
+
+    pub(crate) fn components(&self) -> Vec<(&'static str, Arc<ComponentState>)> {
+        self.components
+            .lock()
+            .expect("Unable to lock supervisor!")
+            .iter()
+            .map(|(name, state)| (*name, state.clone()))
+            .collect()
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_restart() {
+        let supervisor = Supervisor::default();
+        let runs = Arc::new(AtomicU64::new(0));
+        let body_runs = runs.clone();
+        supervisor.spawn("flaky", move |_| {
+            if body_runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("first run");
+            }
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (name, state) = supervisor.components().pop().unwrap();
+        assert_eq!(name, "flaky");
+        while runs.load(Ordering::SeqCst) < 2 || state.alive.load(Ordering::SeqCst) {
+            assert!(Instant::now() < deadline, "component was not restarted");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(state.restarts.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            state.last_error.lock().unwrap().as_deref(),
+            Some("first run")
+        );
+        // Returning is a clean exit, not a death
+        assert!(state.stopped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let supervisor = Supervisor::default();
+        let ticks = Arc::new(AtomicU64::new(0));
+        let body_ticks = ticks.clone();
+        supervisor.spawn("ticker", move |shutdown| {
+            while shutdown.sleep(Duration::from_millis(5)) {
+                body_ticks.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        supervisor.spawn("sleeper", |shutdown| {
+            shutdown.sleep(Duration::from_secs(600));
+        });
+
+        let started = Instant::now();
+        supervisor.shutdown();
+        // The long sleep was cut short and both threads were joined
+        assert!(started.elapsed() < Duration::from_secs(5));
+        for (_, state) in supervisor.components() {
+            assert!(state.stopped.load(Ordering::SeqCst));
+            assert!(!state.alive.load(Ordering::SeqCst));
+        }
+        let seen = ticks.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(ticks.load(Ordering::SeqCst), seen);
+
+        // Nothing new starts after a shutdown
+        supervisor.spawn("late", |_| {});
+        assert_eq!(supervisor.components().len(), 2);
+        assert!(!Shutdown::default().requested());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::py_waiter::{instant_from_datetime, CallOptions, PyCache};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+const UNLIMITED: usize = usize::MAX;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum QuotaPolicy {
+    Evict,
+    Skip,
+    Raise,
+}
+
+impl QuotaPolicy {
+    fn parse(policy: &str) -> PyResult<Self> {
+        match policy {
+            "evict" => Ok(QuotaPolicy::Evict),
+            "skip" => Ok(QuotaPolicy::Skip),
+            "raise" => Ok(QuotaPolicy::Raise),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown quota policy '{}', expected 'evict', 'skip' or 'raise'",
+                policy
+            ))),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            QuotaPolicy::Evict => "evict",
+            QuotaPolicy::Skip => "skip",
+            QuotaPolicy::Raise => "raise",
+        }
+    }
+}
+
+pub(crate) struct TenantState {
+    pub(crate) name: String,
+    max_entries: AtomicUsize,
+    max_memory: AtomicUsize,
+    policy: Mutex<QuotaPolicy>,
+    pub(crate) entries: AtomicUsize,
+    pub(crate) memory: AtomicUsize,
+    pub(crate) hits: AtomicU64,
+    pub(crate) misses: AtomicU64,
+    pub(crate) evictions: AtomicU64,
+}
+
+impl TenantState {
+    pub(crate) fn new(name: String) -> Self {
+        Self {
+            name,
+            max_entries: AtomicUsize::new(UNLIMITED),
+            max_memory: AtomicUsize::new(UNLIMITED),
+            policy: Mutex::new(QuotaPolicy::Evict),
+            entries: AtomicUsize::new(0),
+            memory: AtomicUsize::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn set_quota(
+        &self,
+        max_entries: Option<usize>,
+        max_memory: Option<usize>,
+        on_quota: Option<&str>,
+    ) -> PyResult<()> {
+        if let Some(on_quota) = on_quota {
+            *self.policy.lock().unwrap() = QuotaPolicy::parse(on_quota)?;
+        }
+        if let Some(max_entries) = max_entries {
+            self.max_entries.store(max_entries, Ordering::Relaxed);
+        }
+        if let Some(max_memory) = max_memory {
+            self.max_memory.store(max_memory, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn policy(&self) -> QuotaPolicy {
+        *self.policy.lock().unwrap()
+    }
+
+    pub(crate) fn over_entries(&self) -> bool {
+        self.entries.load(Ordering::Relaxed) >= self.max_entries.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn over_memory(&self) -> bool {
+        self.memory.load(Ordering::Relaxed) > self.max_memory.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn prefix(&self) -> String {
+        tenant_prefix(&self.name)
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("tenant:{}:{}", self.name, key)
+    }
+}
+
+pub(crate) fn tenant_prefix(name: &str) -> String {
+    format!("tenant:{}:", name)
+}
+
+fn limit(value: usize) -> Option<usize> {
+    (value != UNLIMITED).then_some(value)
+}
+
+#[pyclass(frozen)]
+pub struct TenantView {
+    cache: Py<PyCache>,
+    state: Arc<TenantState>,
+}
+
+impl TenantView {
+    pub(crate) fn new(cache: Py<PyCache>, state: Arc<TenantState>) -> Self {
+        Self { cache, state }
+    }
+}
+
+#[pymethods]
+impl TenantView {
+    #[getter]
+    fn name(&self) -> String {
+        self.state.name.clone()
+    }
+
+    #[pyo3(signature = (py_func, args, kwargs, key, *, tags=None, context=None, meta=None, expire_at=None, provenance=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_call(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyAny>,
+        args: Py<PyAny>,
+        kwargs: Py<PyAny>,
+        key: String,
+        tags: Option<Py<PyAny>>,
+        context: Option<Py<PyAny>>,
+        meta: Option<Py<PyAny>>,
+        expire_at: Option<&Bound<'_, PyAny>>,
+        provenance: Option<String>,
+    ) -> PyResult<Py<PyAny>> {
+        let options = CallOptions {
+            tags,
+            context,
+            meta,
+            expires_at: expire_at.map(instant_from_datetime).transpose()?,
+            provenance,
+            tenant: Some(self.state.clone()),
+            wait_timeout: None,
+            freeze: None,
+        };
+        let key = self.state.key(&self.cache.get().canonical_key(py, &key)?);
+        PyCache::call_bounded(&self.cache, py, py_func, args, kwargs, &key, options)
+    }
+
+    fn drop(&self, py: Python<'_>, key: String) -> PyResult<()> {
+        let cache = self.cache.get();
+        cache.remove(&self.state.key(&cache.canonical_key(py, &key)?));
+        Ok(())
+    }
+
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let state = &self.state;
+        let stats = PyDict::new(py);
+        stats.set_item("entries", state.entries.load(Ordering::Relaxed))?;
+        stats.set_item("memory", state.memory.load(Ordering::Relaxed))?;
+        stats.set_item(
+            "max_entries",
+            limit(state.max_entries.load(Ordering::Relaxed)),
+        )?;
+        stats.set_item(
+            "max_memory",
+            limit(state.max_memory.load(Ordering::Relaxed)),
+        )?;
+        stats.set_item("hits", state.hits.load(Ordering::Relaxed))?;
+        stats.set_item("misses", state.misses.load(Ordering::Relaxed))?;
+        stats.set_item("evictions", state.evictions.load(Ordering::Relaxed))?;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::CacheConfig;
+    use crate::errors::QuotaExceeded;
+    use pyo3::ffi::c_str;
+    use pyo3::types::PyTuple;
+
+    fn tenant(py: Python<'_>, cache: &Py<PyCache>, name: &str, on_quota: &str) -> TenantView {
+        let state = Arc::new(TenantState::new(name.to_string()));
+        state.set_quota(Some(2), None, Some(on_quota)).unwrap();
+        TenantView::new(cache.clone_ref(py), state)
+    }
+
+    fn call(view: &TenantView, py: Python<'_>, key: &str) -> PyResult<Py<PyAny>> {
+        let func = py.eval(c_str!("lambda: 42"), None, None)?;
+        view.py_call(
+            py,
+            func.unbind(),
+            PyTuple::empty(py).into_any().unbind(),
+            PyDict::new(py).into_any().unbind(),
+            key.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn stat(view: &TenantView, py: Python<'_>, name: &str) -> usize {
+        view.stats(py)
+            .unwrap()
+            .get_item(name)
+            .unwrap()
+            .unwrap()
+            .extract()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_tenant_quota() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let cache = Py::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let acme = tenant(py, &cache, "acme", "evict");
+            let other = tenant(py, &cache, "other", "evict");
+            for key in ["a", "b", "c"] {
+                call(&acme, py, key).unwrap();
+            }
+            call(&other, py, "a").unwrap();
+
+            // The least recently used entry of the tenant over quota goes
+            assert_eq!(stat(&acme, py, "entries"), 2);
+            assert_eq!(stat(&acme, py, "evictions"), 1);
+            assert_eq!(stat(&other, py, "entries"), 1);
+            let lookup = |key: &str| cache.get().lookup(py, key).unwrap().is_some();
+            assert!(!lookup("tenant:acme:a"));
+            assert!(lookup("tenant:acme:c"));
+            assert!(lookup("tenant:other:a"));
+        });
+    }
+
+    #[test]
+    fn test_quota_policy() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let cache = Py::new(py, PyCache::with_config(CacheConfig::default())).unwrap();
+            let strict = tenant(py, &cache, "strict", "raise");
+            let lenient = tenant(py, &cache, "lenient", "skip");
+            for view in [&strict, &lenient] {
+                call(view, py, "a").unwrap();
+                call(view, py, "b").unwrap();
+            }
+
+            let err = call(&strict, py, "c").unwrap_err();
+            assert!(err.is_instance_of::<QuotaExceeded>(py));
+            // Skipping still computes, it just does not store the result
+            let value = call(&lenient, py, "c").unwrap();
+            assert_eq!(value.extract::<i32>(py).unwrap(), 42);
+            assert!(cache
+                .get()
+                .lookup(py, "tenant:lenient:c")
+                .unwrap()
+                .is_none());
+            assert_eq!(stat(&lenient, py, "entries"), 2);
+            assert_eq!(stat(&lenient, py, "evictions"), 0);
+
+            let stats = cache.get().stats(py).unwrap();
+            let exceeded: u64 = stats
+                .get_item("quota_exceeded")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(exceeded, 2);
+        });
+    }
+}
@@ -0,0 +1,141 @@
+use crate::py_log;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+// OS scheduling settings for the background threads, so they can be kept
+// below the threads serving requests.
+#[derive(Clone, Default)]
+pub(crate) struct ThreadSettings {
+    pub(crate) nice: Option<i32>,
+    pub(crate) affinity: Option<Vec<usize>>,
+}
+
+impl ThreadSettings {
+    pub(crate) fn parse(nice: Option<i32>, affinity: Option<Vec<usize>>) -> PyResult<Self> {
+        if nice.is_some_and(|nice| !(-20..=19).contains(&nice)) {
+            return Err(PyValueError::new_err(
+                "thread_nice must be between -20 and 19",
+            ));
+        }
+        if affinity.as_ref().is_some_and(|cpus| cpus.is_empty()) {
+            return Err(PyValueError::new_err(
+                "thread_affinity must name at least one CPU",
+            ));
+        }
+        Ok(Self { nice, affinity })
+    }
+
+    // Applies to the calling thread; failures are logged so a missing
+    // permission never stops the thread itself
+    pub(crate) fn apply(&self, name: &str) {
+        if let Some(nice) = self.nice {
+            if let Err(err) = os::set_nice(nice) {
+                py_log::log(
+                    py_log::WARNING,
+                    &format!(
+                        "Unable to set nice {} on rustflight-{}: {}",
+                        nice, name, err
+                    ),
+                );
+            }
+        }
+        if let Some(cpus) = &self.affinity {
+            if let Err(err) = os::set_affinity(cpus) {
+                py_log::log(
+                    py_log::WARNING,
+                    &format!("Unable to set CPU affinity on rustflight-{}: {}", name, err),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::io;
+    use std::mem::size_of_val;
+    use std::os::raw::{c_int, c_uint, c_ulong};
+
+    const PRIO_PROCESS: c_int = 0;
+    // Matches glibc's fixed-size cpu_set_t
+    const CPU_SETSIZE: usize = 1024;
+    const WORD_BITS: usize = c_ulong::BITS as usize;
+
+    extern "C" {
+        fn gettid() -> c_int;
+        fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const c_ulong) -> c_int;
+    }
+
+    // Linux keeps the nice value per thread, addressed by its thread id
+    pub(super) fn set_nice(nice: i32) -> io::Result<()> {
+        let result = unsafe { setpriority(PRIO_PROCESS, gettid() as c_uint, nice) };
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn set_affinity(cpus: &[usize]) -> io::Result<()> {
+        let mut mask = [0 as c_ulong; CPU_SETSIZE / WORD_BITS];
+        for &cpu in cpus {
+            if cpu >= CPU_SETSIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CPU {} is out of range", cpu),
+                ));
+            }
+            mask[cpu / WORD_BITS] |= 1 << (cpu % WORD_BITS);
+        }
+        // A pid of 0 targets the calling thread
+        let result = unsafe { sched_setaffinity(0, size_of_val(&mask), mask.as_ptr()) };
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod os {
+    use std::io;
+
+    pub(super) fn set_nice(_nice: i32) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn set_affinity(_cpus: &[usize]) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_thread_settings() {
+        assert!(ThreadSettings::parse(Some(20), None).is_err());
+        assert!(ThreadSettings::parse(None, Some(Vec::new())).is_err());
+        let settings = ThreadSettings::parse(Some(10), Some(vec![0])).unwrap();
+
+        // Only the thread applying the settings is affected
+        let nice = thread::spawn(move || {
+            settings.apply("test");
+            thread_nice()
+        });
+        if cfg!(target_os = "linux") {
+            assert_eq!(nice.join().unwrap(), Some(10));
+            assert_ne!(thread_nice(), Some(10));
+        }
+    }
+
+    // The nice value of the calling thread, as reported by procfs
+    fn thread_nice() -> Option<i32> {
+        let stat = std::fs::read_to_string("/proc/thread-self/stat").ok()?;
+        // Fields after the parenthesized command name start at the state
+        let fields: Vec<_> = stat[stat.rfind(')')? + 2..].split(' ').collect();
+        fields.get(16)?.parse().ok()
+    }
+}
@@ -0,0 +1,54 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+pub(crate) fn parse_bucket(bucket: &str) -> PyResult<u64> {
+    let invalid = || {
+        PyValueError::new_err(format!(
+            "Invalid time bucket '{}', expected e.g. '30s', '5m', '1h' or '1d'",
+            bucket
+        ))
+    };
+    let split = bucket
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(bucket.len());
+    let (amount, unit) = bucket.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    match amount * unit {
+        0 => Err(invalid()),
+        seconds => Ok(seconds),
+    }
+}
+
+// Buckets are named by their start as a unix timestamp, so every process
+// agrees on the key for a given bucket.
+pub(crate) fn bucket_key(template: &str, bucket_start: u64) -> String {
+    match template.contains("{}") {
+        true => template.replacen("{}", &bucket_start.to_string(), 1),
+        false => format!("{}:{}", template, bucket_start),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(parse_bucket("5m").unwrap(), 300);
+        assert_eq!(parse_bucket("90").unwrap(), 90);
+        assert_eq!(parse_bucket("1d").unwrap(), 86400);
+        assert!(parse_bucket("0s").is_err());
+        assert!(parse_bucket("5w").is_err());
+        assert!(parse_bucket("m").is_err());
+
+        assert_eq!(bucket_key("metrics:{}", 300), "metrics:300");
+        assert_eq!(bucket_key("metrics", 300), "metrics:300");
+    }
+}
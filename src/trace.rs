@@ -0,0 +1,179 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy)]
+pub(crate) enum TraceKind {
+    Hit,
+    Miss,
+    Wait,
+}
+
+impl TraceKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TraceKind::Hit => "hit",
+            TraceKind::Miss => "miss",
+            TraceKind::Wait => "wait",
+        }
+    }
+}
+
+struct TraceEvent {
+    timestamp: f64,
+    key_hash: u64,
+    kind: TraceKind,
+    meta: Option<Py<PyAny>>,
+}
+
+pub(crate) struct AccessTrace {
+    capacity: usize,
+    events: Mutex<VecDeque<TraceEvent>>,
+}
+
+impl AccessTrace {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            events: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+        }
+    }
+
+    pub(crate) fn record(&self, key: &str, kind: TraceKind, meta: Option<Py<PyAny>>) {
+        let event = TraceEvent {
+            timestamp: unix_now(),
+            key_hash: key_hash(key),
+            kind,
+            meta,
+        };
+        let mut events = self.events.lock().unwrap();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    pub(crate) fn export<'py>(&self, py: Python<'py>, format: &str) -> PyResult<Bound<'py, PyAny>> {
+        let events = self.events.lock().unwrap();
+        match format {
+            "csv" => {
+                let json = py.import("json")?;
+                let mut csv = String::from("timestamp,key_hash,event,meta\n");
+                for event in events.iter() {
+                    let meta = match &event.meta {
+                        Some(meta) => {
+                            let meta: String = json.call_method1("dumps", (meta,))?.extract()?;
+                            format!("\"{}\"", meta.replace('"', "\"\""))
+                        }
+                        None => String::new(),
+                    };
+                    let _ = writeln!(
+                        csv,
+                        "{:.6},{:016x},{},{}",
+                        event.timestamp,
+                        event.key_hash,
+                        event.kind.as_str(),
+                        meta
+                    );
+                }
+                Ok(csv.into_pyobject(py)?.into_any())
+            }
+            "list" => {
+                let rows = events.iter().map(|event| {
+                    (
+                        event.timestamp,
+                        format!("{:016x}", event.key_hash),
+                        event.kind.as_str(),
+                        event.meta.as_ref().map(|meta| meta.clone_ref(py)),
+                    )
+                });
+                Ok(PyList::new(py, rows)?.into_any())
+            }
+            "arrow" => {
+                let columns = PyDict::new(py);
+                columns.set_item(
+                    "timestamp",
+                    events
+                        .iter()
+                        .map(|event| event.timestamp)
+                        .collect::<Vec<_>>(),
+                )?;
+                columns.set_item(
+                    "key_hash",
+                    events
+                        .iter()
+                        .map(|event| format!("{:016x}", event.key_hash))
+                        .collect::<Vec<_>>(),
+                )?;
+                columns.set_item(
+                    "event",
+                    events
+                        .iter()
+                        .map(|event| event.kind.as_str())
+                        .collect::<Vec<_>>(),
+                )?;
+                columns.set_item(
+                    "meta",
+                    events
+                        .iter()
+                        .map(|event| event.meta.as_ref().map(|meta| meta.clone_ref(py)))
+                        .collect::<Vec<_>>(),
+                )?;
+                drop(events);
+                py.import("pyarrow")?.call_method1("table", (columns,))
+            }
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown trace format '{}', expected 'csv', 'list' or 'arrow'",
+                format
+            ))),
+        }
+    }
+}
+
+// FNV-1a, so exported hashes are stable across processes.
+pub(crate) fn key_hash(key: impl AsRef<[u8]>) -> u64 {
+    key.as_ref().iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub(crate) fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        pyo3::prepare_freethreaded_python();
+        let trace = AccessTrace::new(2);
+        trace.record("a", TraceKind::Miss, None);
+        trace.record("a", TraceKind::Hit, None);
+        trace.record("b", TraceKind::Wait, None);
+        Python::with_gil(|py| {
+            let rows: Vec<(f64, String, String, Option<Py<PyAny>>)> =
+                trace.export(py, "list").unwrap().extract().unwrap();
+            let events: Vec<_> = rows
+                .iter()
+                .map(|(_, hash, kind, _)| (hash.as_str(), kind.as_str()))
+                .collect();
+            let hash = format!("{:016x}", key_hash("a"));
+            let other = format!("{:016x}", key_hash("b"));
+            assert_eq!(events, [(hash.as_str(), "hit"), (other.as_str(), "wait")]);
+
+            let csv: String = trace.export(py, "csv").unwrap().extract().unwrap();
+            assert_eq!(csv.lines().count(), 3);
+            assert!(csv.starts_with("timestamp,key_hash,event,meta\n"));
+            assert!(trace.export(py, "xml").is_err());
+        });
+    }
+}